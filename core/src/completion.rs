@@ -0,0 +1,100 @@
+// Shell completion scripts generated from option definitions
+// (`--dump-completion bash`)
+
+use crate::{HelpOption, OptionDefinition, ValueHint, help_model};
+
+/// Shells `--dump-completion` can generate a script for
+pub const COMPLETION_SHELLS: [&str; 1] = ["bash"];
+
+/// Generates a bash completion script for `command_name`, to be sourced or
+/// installed under `bash-completion/completions/`. Every visible option is
+/// offered (`--no-NAME` too for negatable ones); after an option that takes
+/// a path the script completes file or directory names, after any other
+/// value-taking option nothing, and otherwise file names for the arguments.
+pub fn generate_bash_completion(command_name: &str, defs: &[OptionDefinition]) -> String {
+    let model = help_model(command_name, "", "", defs);
+    let options: Vec<&HelpOption> = model.options.iter().filter(|option| !option.hidden).collect();
+    let function = format!(
+        "_{}",
+        command_name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect::<String>()
+    );
+
+    let mut words = Vec::new();
+    for option in &options {
+        if let Some(flag) = option.short_flag {
+            words.push(format!("-{}", flag));
+        }
+        words.push(format!("--{}", option.long_name));
+        if option.negatable {
+            words.push(format!("--no-{}", option.long_name));
+        }
+    }
+
+    // Options whose value is the next word, by what that value is
+    let spellings = |hint: ValueHint| {
+        options.iter()
+            .filter(|option| option.value_name.is_some() && !option.value_optional && option.value_hint == hint)
+            .flat_map(|option| {
+                let short = option.short_flag.map(|flag| format!("-{}", flag));
+                short.into_iter().chain([format!("--{}", option.long_name)])
+            })
+            .collect::<Vec<_>>()
+            .join("|")
+    };
+    let mut cases = String::new();
+    for (hint, action) in [
+        (ValueHint::FilePath, "COMPREPLY=($(compgen -f -- \"$cur\"))\n            "),
+        (ValueHint::DirPath, "COMPREPLY=($(compgen -d -- \"$cur\"))\n            "),
+        (ValueHint::Any, ""),
+    ] {
+        let pattern = spellings(hint);
+        if !pattern.is_empty() {
+            cases.push_str(&format!("        {})\n            {}return\n            ;;\n", pattern, action));
+        }
+    }
+
+    let mut script = format!("# bash completion for {}\n", command_name);
+    script.push_str(&format!("{}()\n{{\n", function));
+    script.push_str("    local cur=\"${COMP_WORDS[COMP_CWORD]}\"\n");
+    script.push_str("    local prev=\"${COMP_WORDS[COMP_CWORD-1]}\"\n");
+    script.push_str("    COMPREPLY=()\n");
+    if !cases.is_empty() {
+        script.push_str(&format!("    case \"$prev\" in\n{}    esac\n", cases));
+    }
+    script.push_str("    if [[ \"$cur\" == -* ]]; then\n");
+    script.push_str(&format!("        COMPREPLY=($(compgen -W \"{}\" -- \"$cur\"))\n", words.join(" ")));
+    script.push_str("        return\n    fi\n");
+    script.push_str("    COMPREPLY=($(compgen -f -- \"$cur\"))\n}\n");
+    script.push_str(&format!("complete -F {} {}\n", function, command_name));
+    script
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{NO_SHORT_FLAG, standard_options, validate_file_path, validate_positive_integer};
+
+    #[test]
+    fn test_bash_completion_script() {
+        let mut defs = standard_options();
+        defs.push(OptionDefinition::new('m', "base64").negatable().help("Use base64"));
+        defs.push(OptionDefinition::new(NO_SHORT_FLAG, "output").value().validator(validate_file_path).value_hint(ValueHint::FilePath).help("Output"));
+        defs.push(OptionDefinition::new(NO_SHORT_FLAG, "dir").value().value_hint(ValueHint::DirPath).help("Dir"));
+        defs.push(OptionDefinition::new(NO_SHORT_FLAG, "indent").value().validator(validate_positive_integer).help("N"));
+        defs.push(OptionDefinition::new(NO_SHORT_FLAG, "secret").hidden().help("Hidden"));
+
+        let script = generate_bash_completion("uuencode", &defs);
+        for def in defs.iter().filter(|def| !def.hidden) {
+            assert!(script.contains(&format!("--{}", def.name)), "missing --{}", def.name);
+        }
+        assert!(script.contains(" -m ") && script.contains("--no-base64"));
+        assert!(!script.contains("secret"));
+        assert!(script.contains("        --output)\n            COMPREPLY=($(compgen -f -- \"$cur\"))\n"));
+        assert!(script.contains("        --dir)\n            COMPREPLY=($(compgen -d -- \"$cur\"))\n"));
+        assert!(script.contains("        --indent)\n            return\n"));
+        assert!(script.starts_with("# bash completion for uuencode\n_uuencode()\n{\n"));
+        assert!(script.ends_with("complete -F _uuencode uuencode\n"));
+
+        assert!(generate_bash_completion("my-tool", &defs).ends_with("complete -F _my_tool my-tool\n"));
+    }
+}
@@ -0,0 +1,311 @@
+// Option config files (--load-opts) and the effective configuration they
+// produce once merged with the command line
+
+use std::collections::HashMap;
+use std::ffi::OsString;
+use std::io;
+use std::path::Path;
+
+use crate::{OptionDefinition, ParseError, ParsedCommand, ValidationError, ValueProvenance};
+
+/// Former name of the source of an effective value, which is now a
+/// [`ValueProvenance`]: `OptionSource::CommandLine` became
+/// `ValueProvenance::Cli` (or `Default` for an option given without a value)
+#[deprecated(note = "use ValueProvenance")]
+pub type OptionSource = ValueProvenance;
+
+/// One option as seen after merging all sources
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveOption {
+    pub name: String,
+    /// None when the option is set without a value
+    pub value: Option<OsString>,
+    pub source: ValueProvenance,
+}
+
+/// Reads a config file of options, one per line as `name` or `name=value`
+/// (a leading `--` is allowed). Blank lines and `#` comments are ignored.
+pub fn load_config_file(path: &Path) -> io::Result<Vec<(String, Option<OsString>)>> {
+    let text = std::fs::read_to_string(path)?;
+    let entries = text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(|line| {
+            let line = line.strip_prefix("--").unwrap_or(line);
+            match line.split_once('=') {
+                Some((name, value)) => (name.trim().to_string(), Some(OsString::from(value.trim()))),
+                None => (line.to_string(), None),
+            }
+        })
+        .collect();
+    Ok(entries)
+}
+
+/// The effective value and source of every option that is set, in
+/// definition order. Values given on the command line (explicitly or as a
+/// bare option's default) override environment variables, which override
+/// config file values; `--no-NAME` on the command line cancels NAME in the
+/// config file, as does an option on the command line that it `conflicts`
+/// with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EffectiveConfig {
+    options: Vec<EffectiveOption>,
+    unset: Vec<String>,
+}
+
+impl EffectiveConfig {
+    /// Merges `parsed` with the `config` entries, checking config entries
+    /// against the option definitions the same way the parser would
+    pub fn resolve(
+        option_definitions: &[OptionDefinition],
+        parsed: &ParsedCommand,
+        config: &[(String, Option<OsString>)],
+    ) -> Result<Self, ParseError> {
+        let mut from_config: HashMap<&str, &Option<OsString>> = HashMap::new();
+        for (name, value) in config {
+            let def = option_definitions.iter().find(|d| d.name == *name)
+                .ok_or_else(|| ParseError::UnknownOption(format!("{} (in config file)", name)))?;
+            match value {
+                Some(v) if def.has_value || def.has_optional_value => {
+                    if let Some(validator) = &def.validator {
+                        validator(v).map_err(ParseError::ValidationError)?;
+                    }
+                }
+                Some(_) => {
+                    return Err(ParseError::ValidationError(ValidationError::new(
+                        format!("Option '{}' does not accept a value", name)
+                    )));
+                }
+                None if def.has_value => return Err(ParseError::MissingValue(name.clone())),
+                None => {}
+            }
+            from_config.insert(name, value);
+        }
+
+        let mut options = Vec::new();
+        let mut unset = Vec::new();
+        for def in option_definitions {
+            if let Some(value) = parsed.options.get(&def.name) {
+                options.push(EffectiveOption {
+                    name: def.name.clone(),
+                    value: value.clone(),
+                    source: parsed.value_provenance(&def.name),
+                });
+            } else if let Some(value) = from_config.get(def.name.as_str())
+                && !parsed.negated.contains(&def.name)
+                && !conflicts_with_command_line(option_definitions, def, parsed)
+            {
+                options.push(EffectiveOption {
+                    name: def.name.clone(),
+                    value: (*value).clone(),
+                    source: ValueProvenance::ConfigFile,
+                });
+            } else {
+                unset.push(def.name.clone());
+            }
+        }
+
+        // The config file's own options get the parser's `requires` and
+        // `conflicts` checks, against everything that is set
+        let is_set = |name: &String| options.iter().any(|o| o.name == *name);
+        for option in options.iter().filter(|o| o.source == ValueProvenance::ConfigFile) {
+            let def = option_definitions.iter().find(|d| d.name == option.name).expect("merged from a definition");
+            if let Some(required) = def.requires.iter().find(|name| !is_set(name)) {
+                return Err(ParseError::UnmetDependency { option: def.name.clone(), requires: required.clone() });
+            }
+            if let Some(conflict) = def.conflicts.iter().find(|name| is_set(name)) {
+                return Err(ParseError::InvalidFlagCombination(format!(
+                    "--{} cannot be combined with --{}", def.name, conflict
+                )));
+            }
+        }
+        Ok(Self { options, unset })
+    }
+
+    /// Returns the options that are set, in definition order
+    pub fn options(&self) -> &[EffectiveOption] {
+        &self.options
+    }
+
+    /// Returns the effective entry for one option, if it is set
+    pub fn get(&self, name: &str) -> Option<&EffectiveOption> {
+        self.options.iter().find(|o| o.name == name)
+    }
+
+    /// Adds the config file values to `parsed`, so they take effect
+    pub fn apply(&self, parsed: &mut ParsedCommand) {
+        for option in self.options.iter().filter(|o| o.source == ValueProvenance::ConfigFile) {
+            parsed.options.insert(option.name.clone(), option.value.clone());
+            parsed.from_config.insert(option.name.clone());
+        }
+    }
+
+    /// Describes every defined option on its own line: its effective value
+    /// and source, or that it is not set
+    pub fn describe(&self) -> String {
+        let mut text = String::new();
+        for option in &self.options {
+            // `--show-config` has always said "command line" for these
+            let source = match option.source {
+                ValueProvenance::Cli => "command line".to_string(),
+                source => source.to_string(),
+            };
+            match &option.value {
+                Some(value) => text.push_str(&format!("{} = {:?} ({})\n", option.name, value, source)),
+                None => text.push_str(&format!("{} set ({})\n", option.name, source)),
+            }
+        }
+        for name in &self.unset {
+            text.push_str(&format!("{} not set\n", name));
+        }
+        text
+    }
+}
+
+/// Whether `def` conflicts (in either direction) with an option set in
+/// `parsed`, which then keeps the config file from setting it
+fn conflicts_with_command_line(option_definitions: &[OptionDefinition], def: &OptionDefinition, parsed: &ParsedCommand) -> bool {
+    def.conflicts.iter().any(|name| parsed.options.contains_key(name))
+        || option_definitions.iter()
+            .any(|other| parsed.options.contains_key(&other.name) && other.conflicts.contains(&def.name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parse_command_line, standard_options, Parser, NO_SHORT_FLAG};
+    use crate::tests::scratch_path;
+
+    fn options() -> Vec<OptionDefinition> {
+        let mut options = standard_options();
+        options.extend([
+            OptionDefinition::new('m', "base64").negatable(),
+            OptionDefinition::new('o', "output").value(),
+            OptionDefinition::new('i', "indent").value(),
+        ]);
+        options
+    }
+
+    #[test]
+    fn test_command_line_overrides_config_file() {
+        let path = scratch_path("config");
+        std::fs::write(&path, "# defaults\noutput=from-config.uue\n--base64\n\n").unwrap();
+        let config = load_config_file(&path).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        let options = options();
+        let args = ["test-cmd", "--output", "from-cli.uue"].iter().map(OsString::from);
+        let mut parsed = parse_command_line(&options, args).unwrap();
+        let effective = EffectiveConfig::resolve(&options, &parsed, &config).unwrap();
+
+        let output = effective.get("output").unwrap();
+        assert_eq!(output.value, Some(OsString::from("from-cli.uue")));
+        assert_eq!(output.source, ValueProvenance::Cli);
+        assert_eq!(effective.get("base64").unwrap().source, ValueProvenance::ConfigFile);
+        assert!(effective.get("indent").is_none());
+
+        let description = effective.describe();
+        assert!(description.contains("output = \"from-cli.uue\" (command line)\n"));
+        assert!(description.contains("base64 set (config file)\n"));
+        assert!(description.contains("indent not set\n"));
+
+        effective.apply(&mut parsed);
+        assert!(parsed.is_option_set("base64"));
+        assert_eq!(parsed.value_provenance("base64"), ValueProvenance::ConfigFile);
+        assert_eq!(parsed.option_value("output"), Some(std::ffi::OsStr::new("from-cli.uue")));
+
+        // --no-base64 cancels the config file's --base64
+        let args = ["test-cmd", "--no-base64"].iter().map(OsString::from);
+        let mut parsed = parse_command_line(&options, args).unwrap();
+        let effective = EffectiveConfig::resolve(&options, &parsed, &config).unwrap();
+        assert!(effective.get("base64").is_none());
+        effective.apply(&mut parsed);
+        assert_eq!(parsed.option_bool("base64"), Some(false));
+    }
+
+    #[test]
+    fn test_environment_ranks_between_command_line_and_config_file() {
+        const VAR: &str = "SHARUTILS_TEST_CONFIG_ENV_OUTPUT";
+        let mut options = options();
+        options.push(OptionDefinition::new('l', "level").value().default_value("1").env_var(VAR));
+        let config = vec![("level".to_string(), Some(OsString::from("3")))];
+        // A stand-in environment, as changing the real one races with
+        // every other test thread that reads it
+        let environment = std::cell::RefCell::new(None);
+        let lookup = |var: &str| {
+            assert_eq!(var, VAR);
+            environment.borrow().clone()
+        };
+        let parser = Parser::new(&options).unwrap().with_env(&lookup);
+        let resolve = |args: &[&str]| {
+            let args = std::iter::once("test-cmd").chain(args.iter().copied()).map(OsString::from);
+            let parsed = parser.parse(args).unwrap();
+            EffectiveConfig::resolve(&options, &parsed, &config).unwrap()
+        };
+
+        *environment.borrow_mut() = Some(OsString::from("2"));
+        let effective = resolve(&[]);
+        let level = effective.get("level").unwrap();
+        assert_eq!((level.value.as_deref(), level.source), (Some("2".as_ref()), ValueProvenance::Env));
+        assert!(effective.describe().contains("level = \"2\" (env)\n"));
+
+        let level = resolve(&["--level=4"]).get("level").cloned().unwrap();
+        assert_eq!((level.value.as_deref(), level.source), (Some("4".as_ref()), ValueProvenance::Cli));
+        // A bare option gets its default_value, which still beats the environment
+        let level = resolve(&["-l"]).get("level").cloned().unwrap();
+        assert_eq!((level.value.as_deref(), level.source), (Some("1".as_ref()), ValueProvenance::Default));
+
+        *environment.borrow_mut() = None;
+        let effective = resolve(&[]);
+        let level = effective.get("level").unwrap();
+        assert_eq!((level.value.as_deref(), level.source), (Some("3".as_ref()), ValueProvenance::ConfigFile));
+        assert!(effective.describe().contains("level = \"3\" (config file)\n"));
+    }
+
+    #[test]
+    fn test_config_file_entries_are_checked() {
+        let options = options();
+        let parsed = parse_command_line(&options, ["test-cmd"].iter().map(OsString::from)).unwrap();
+
+        let unknown = vec![("bogus".to_string(), None)];
+        assert!(matches!(
+            EffectiveConfig::resolve(&options, &parsed, &unknown),
+            Err(ParseError::UnknownOption(_))
+        ));
+        let missing = vec![("output".to_string(), None)];
+        assert!(matches!(
+            EffectiveConfig::resolve(&options, &parsed, &missing),
+            Err(ParseError::MissingValue(_))
+        ));
+    }
+
+    #[test]
+    fn test_config_file_entries_follow_dependency_rules() {
+        let mut options = options();
+        options.push(OptionDefinition::new(NO_SHORT_FLAG, "resume").requires("output").conflicts("indent"));
+        let parse = |args: &[&str]| {
+            parse_command_line(&options, std::iter::once("test-cmd").chain(args.iter().copied()).map(OsString::from)).unwrap()
+        };
+        let config = vec![("resume".to_string(), None)];
+
+        // Satisfied by the command line, or left out when it conflicts with it
+        let effective = EffectiveConfig::resolve(&options, &parse(&["--output=x"]), &config).unwrap();
+        assert_eq!(effective.get("resume").unwrap().source, ValueProvenance::ConfigFile);
+        let effective = EffectiveConfig::resolve(&options, &parse(&["--output=x", "--indent=2"]), &config).unwrap();
+        assert!(effective.get("resume").is_none());
+
+        assert_eq!(
+            EffectiveConfig::resolve(&options, &parse(&[]), &config),
+            Err(ParseError::UnmetDependency { option: "resume".to_string(), requires: "output".to_string() })
+        );
+        let config = vec![
+            ("resume".to_string(), None),
+            ("output".to_string(), Some(OsString::from("x"))),
+            ("indent".to_string(), Some(OsString::from("2"))),
+        ];
+        assert_eq!(
+            EffectiveConfig::resolve(&options, &parse(&[]), &config),
+            Err(ParseError::InvalidFlagCombination("--resume cannot be combined with --indent".to_string()))
+        );
+    }
+}
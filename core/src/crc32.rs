@@ -0,0 +1,82 @@
+// CRC-32 checksums (the IEEE 802.3 polynomial used by zip, gzip and PNG),
+// reported per block by `uudecode --probe`
+
+/// The reversed IEEE polynomial
+const POLYNOMIAL: u32 = 0xedb8_8320;
+
+/// Remainders for every byte value, built at compile time
+const TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ POLYNOMIAL } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
+
+/// Incremental CRC-32 checksum
+#[derive(Debug, Clone)]
+pub struct Crc32 {
+    crc: u32,
+}
+
+impl Crc32 {
+    /// Starts a checksum of no data
+    pub fn new() -> Self {
+        Self { crc: !0 }
+    }
+
+    /// Feeds more data into the checksum
+    pub fn update(&mut self, data: &[u8]) {
+        for &byte in data {
+            self.crc = TABLE[((self.crc ^ byte as u32) & 0xff) as usize] ^ (self.crc >> 8);
+        }
+    }
+
+    /// Returns the checksum of everything fed in so far
+    pub fn finalize(&self) -> u32 {
+        !self.crc
+    }
+}
+
+impl Default for Crc32 {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_known_checksums() {
+        let crc = |data: &[u8]| {
+            let mut crc = Crc32::new();
+            crc.update(data);
+            crc.finalize()
+        };
+        assert_eq!(crc(b""), 0);
+        assert_eq!(crc(b"123456789"), 0xcbf4_3926);
+        assert_eq!(crc(b"The quick brown fox jumps over the lazy dog"), 0x414f_a339);
+    }
+
+    #[test]
+    fn test_incremental_matches_one_shot() {
+        let data = b"Hello, World! ".repeat(100);
+        let mut whole = Crc32::new();
+        whole.update(&data);
+        let mut pieces = Crc32::new();
+        for chunk in data.chunks(7) {
+            pieces.update(chunk);
+        }
+        assert_eq!(pieces.finalize(), whole.finalize());
+    }
+}
@@ -0,0 +1,1495 @@
+// Uudecoding functionality
+// Ported from the decoding loops in GNU sharutils uudecode.c
+
+use std::fmt;
+use std::io::{self, BufRead, BufReader, Read, Write};
+
+use crate::{PartNumber, parse_mtime_comment, parse_prefilter_comment};
+use crate::numbered::check_line_number;
+
+/// Errors that can occur while decoding an encoded stream
+#[derive(Debug)]
+pub enum DecodeError {
+    /// Reading the input or writing the decoded output failed
+    Io(io::Error),
+    /// EOF was reached before any `begin` header was found
+    NoBeginLine,
+    /// A `begin` line was found but could not be parsed
+    InvalidHeader(String),
+    /// A data line could not be decoded
+    CorruptLine { line_number: usize, reason: String },
+    /// EOF was reached before the end-of-encoding marker
+    TruncatedData,
+    /// No `begin` header was found within the first `limit` bytes
+    HeaderScanLimit { limit: u64 },
+    /// `verify_sha256` was requested but the block has no `# sha256:` line
+    #[cfg(feature = "sha256")]
+    MissingSha256,
+    /// The decoded data does not match the block's `# sha256:` line
+    #[cfg(feature = "sha256")]
+    Sha256Mismatch { expected: String, actual: String },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DecodeError::Io(e) => write!(f, "I/O error: {}", e),
+            DecodeError::NoBeginLine => write!(f, "No `begin' line"),
+            DecodeError::InvalidHeader(msg) => write!(f, "Invalid header: {}", msg),
+            DecodeError::CorruptLine { line_number, reason } => {
+                write!(f, "Corrupt data on line {}: {}", line_number, reason)
+            }
+            DecodeError::TruncatedData => write!(f, "Short file: no end-of-encoding marker found"),
+            DecodeError::HeaderScanLimit { limit } => {
+                write!(f, "No `begin' line within the first {} bytes", limit)
+            }
+            #[cfg(feature = "sha256")]
+            DecodeError::MissingSha256 => write!(f, "No `# sha256:' line after the block"),
+            #[cfg(feature = "sha256")]
+            DecodeError::Sha256Mismatch { expected, actual } => {
+                write!(f, "SHA-256 mismatch: expected {}, got {}", expected, actual)
+            }
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+impl From<io::Error> for DecodeError {
+    fn from(e: io::Error) -> Self {
+        DecodeError::Io(e)
+    }
+}
+
+impl From<DecodeError> for io::Error {
+    /// I/O errors are passed through; anything wrong with the encoded data
+    /// becomes an `InvalidData` error
+    fn from(e: DecodeError) -> Self {
+        match e {
+            DecodeError::Io(e) => e,
+            e => io::Error::new(io::ErrorKind::InvalidData, e),
+        }
+    }
+}
+
+/// The encoding used by a block, as announced by its header
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Encoding {
+    /// Traditional uuencoding (`begin`)
+    Uu,
+    /// Base64 encoding (`begin-base64`)
+    Base64,
+    /// xxencoding: a `begin` header followed by lines in the xx alphabet.
+    /// Only reported by [`detect_encoding`]; it cannot be decoded.
+    Xx,
+    /// No header was found. Only reported by [`detect_encoding`].
+    Unknown,
+}
+
+impl std::str::FromStr for Encoding {
+    type Err = String;
+
+    /// Parses the decodable encodings, as named by `--input-format`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "uu" => Ok(Encoding::Uu),
+            "base64" => Ok(Encoding::Base64),
+            _ => Err(format!("Unknown input format '{}' (expected uu or base64)", s)),
+        }
+    }
+}
+
+/// Information recovered from a `begin` header line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedHeader {
+    pub encoding: Encoding,
+    pub mode: u32,
+    /// The header had no valid mode, so `mode` is the 644 default
+    pub mode_defaulted: bool,
+    pub name: String,
+    /// Set when the block was preceded by a `# part N of M` comment
+    pub part: Option<PartNumber>,
+    /// Source modification time (seconds since the Unix epoch), set when the
+    /// block was preceded by a `# mtime: SECONDS` comment
+    pub mtime: Option<u64>,
+    /// Prefilter stages to undo after decoding, set when the block was
+    /// preceded by a `# prefilter: NAMES` comment
+    pub prefilter: Option<String>,
+}
+
+/// Base64 alphabet accepted when decoding `begin-base64` blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Base64Variant {
+    /// Accept both the standard and the URL-safe alphabet (lenient default)
+    #[default]
+    Any,
+    /// Only the standard alphabet (`+` and `/`)
+    Standard,
+    /// Only the URL-safe alphabet (`-` and `_`)
+    UrlSafe,
+}
+
+impl std::str::FromStr for Base64Variant {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "any" => Ok(Base64Variant::Any),
+            "standard" => Ok(Base64Variant::Standard),
+            "url" => Ok(Base64Variant::UrlSafe),
+            _ => Err(format!("Unknown base64 variant '{}' (expected standard, url or any)", s)),
+        }
+    }
+}
+
+/// Default for [`DecodeOptions::max_header_scan`]: 1 GiB
+pub const DEFAULT_MAX_HEADER_SCAN: u64 = 1 << 30;
+
+/// Settings that control how an encoded stream is read
+#[derive(Debug, Clone)]
+pub struct DecodeOptions {
+    /// Strip the leading indentation found on the `begin` line from every
+    /// following line of the block (for blocks embedded in indented text)
+    pub strip_indent: bool,
+    /// Base64 alphabet(s) accepted in `begin-base64` blocks
+    pub base64_variant: Base64Variant,
+    /// Encoding the caller expects every block to use; a header announcing
+    /// a different one is rejected. This is a check, not a shortcut: headers
+    /// are parsed the same way with or without it, and decoding never runs
+    /// [`detect_encoding`].
+    pub input_format: Option<Encoding>,
+    /// Decode every block as `input_format` whatever its header announces,
+    /// instead of rejecting mismatched headers
+    pub force_input_format: bool,
+    /// Reject headers without a valid octal mode instead of defaulting to 644
+    pub strict_headers: bool,
+    /// Accept a block cut off by EOF before its end marker, keeping the data
+    /// decoded so far, instead of failing with `TruncatedData`
+    pub allow_truncated: bool,
+    /// Maximum number of bytes scanned for the first `begin` header before
+    /// giving up, so a huge file without any block fails in bounded time
+    pub max_header_scan: u64,
+    /// Expect every data line to carry the `NNNNNN CCCC ` prefix written by
+    /// `--numbered-lines`, and reject dropped, reordered or damaged lines
+    pub numbered_lines: bool,
+    /// Reject traditional data lines whose length character disagrees with
+    /// the number of data characters on the line, instead of trusting it
+    pub strict_line_lengths: bool,
+    /// Require a `# sha256: <hex>` line after each block and check it
+    /// against the decoded data
+    #[cfg(feature = "sha256")]
+    pub verify_sha256: bool,
+}
+
+impl Default for DecodeOptions {
+    fn default() -> Self {
+        Self {
+            strip_indent: false,
+            base64_variant: Base64Variant::default(),
+            input_format: None,
+            force_input_format: false,
+            strict_headers: false,
+            allow_truncated: false,
+            max_header_scan: DEFAULT_MAX_HEADER_SCAN,
+            numbered_lines: false,
+            strict_line_lengths: false,
+            #[cfg(feature = "sha256")]
+            verify_sha256: false,
+        }
+    }
+}
+
+/// DEC macro equivalent - decode a single character of traditional uuencoding
+#[inline]
+fn dec(c: u8) -> u8 {
+    c.wrapping_sub(b' ') & 0o77
+}
+
+/// Returns the 6-bit value of a base64 character in the given alphabet,
+/// or None if it isn't one
+fn base64_value(c: u8, variant: Base64Variant) -> Option<u8> {
+    match (c, variant) {
+        (b'A'..=b'Z', _) => Some(c - b'A'),
+        (b'a'..=b'z', _) => Some(c - b'a' + 26),
+        (b'0'..=b'9', _) => Some(c - b'0' + 52),
+        (b'+', Base64Variant::Standard | Base64Variant::Any) => Some(62),
+        (b'/', Base64Variant::Standard | Base64Variant::Any) => Some(63),
+        (b'-', Base64Variant::UrlSafe | Base64Variant::Any) => Some(62),
+        (b'_', Base64Variant::UrlSafe | Base64Variant::Any) => Some(63),
+        _ => None,
+    }
+}
+
+/// Decodes base64 text into bytes, ignoring whitespace and stopping at padding
+fn base64_decode_bytes(text: &[u8], variant: Base64Variant) -> Result<Vec<u8>, String> {
+    let mut out = Vec::with_capacity(text.len() / 4 * 3);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+
+    for &c in text {
+        if c == b'=' {
+            break;
+        }
+        if c.is_ascii_whitespace() {
+            continue;
+        }
+        let value = base64_value(c, variant)
+            .ok_or_else(|| format!("invalid base64 character {:?}", c as char))?;
+        acc = (acc << 6) | value as u32;
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((acc >> bits) as u8);
+        }
+    }
+
+    Ok(out)
+}
+
+/// Decode a filename written with `--encode-file-name` (unpadded base64)
+pub fn base64_decode_filename(encoded: &str) -> Option<String> {
+    let bytes = base64_decode_bytes(encoded.as_bytes(), Base64Variant::Standard).ok()?;
+    String::from_utf8(bytes).ok()
+}
+
+/// Mode given to blocks whose header has no usable mode
+const DEFAULT_HEADER_MODE: u32 = 0o644;
+
+/// Parses a `begin[-base64][-encoded] mode name` header line.
+/// Returns Ok(None) if the line is not a header at all.
+///
+/// Tokens may be separated by any run of spaces or tabs; everything after the
+/// mode, including spaces, is the name, less trailing whitespace. A missing
+/// mode defaults to 644 (see [`DecodedHeader::mode_defaulted`]) if the rest
+/// of the line is a single word; more words are prose, not a name. A numeric
+/// mode that isn't valid octal or is above 7777 is an error.
+pub fn parse_uudecode_header(line: &str) -> Result<Option<DecodedHeader>, DecodeError> {
+    parse_uudecode_header_with(line, false)
+}
+
+/// Like [`parse_uudecode_header`], but when `strict` a missing or invalid
+/// mode is an error instead of defaulting to 644
+pub fn parse_uudecode_header_with(line: &str, strict: bool) -> Result<Option<DecodedHeader>, DecodeError> {
+    let Some(rest) = line.strip_prefix("begin") else {
+        return Ok(None);
+    };
+
+    let (encoding, rest) = match rest.strip_prefix("-base64") {
+        Some(r) => (Encoding::Base64, r),
+        None => (Encoding::Uu, rest),
+    };
+    let (encoded_name, rest) = match rest.strip_prefix("-encoded") {
+        Some(r) => (true, r),
+        None => (false, rest),
+    };
+    if !rest.starts_with([' ', '\t']) {
+        return Ok(None);
+    }
+
+    let is_blank = |c: char| c == ' ' || c == '\t';
+    let rest = rest.trim_start_matches(is_blank).trim_end();
+    let (mode_str, after_mode) = rest.split_once(is_blank).unwrap_or((rest, ""));
+    let name = after_mode.trim_start_matches(is_blank);
+    // A number is meant as a mode, so don't fall back to treating it as a name
+    if !mode_str.is_empty() && mode_str.bytes().all(|b| b.is_ascii_digit()) {
+        match u32::from_str_radix(mode_str, 8) {
+            Ok(mode) if mode <= 0o7777 => {}
+            Ok(_) => return Err(DecodeError::InvalidHeader(format!("mode {} is out of range (at most 7777)", mode_str))),
+            Err(_) => return Err(DecodeError::InvalidHeader(format!("invalid octal mode {:?}", mode_str))),
+        }
+    }
+    let (mode, mode_defaulted, name) = match u32::from_str_radix(mode_str, 8) {
+        Ok(mode) if !name.is_empty() => (mode, false, name),
+        Ok(_) => return Err(DecodeError::InvalidHeader(format!("missing file name in {:?}", line))),
+        Err(_) if strict => {
+            return Err(DecodeError::InvalidHeader(format!("invalid mode {:?}", mode_str)));
+        }
+        // No mode at all: a lone word is the name, but "begin the meeting
+        // at noon" is prose
+        Err(_) if !name.is_empty() => {
+            return Err(DecodeError::InvalidHeader(format!("no mode before the file name in {:?}", line)));
+        }
+        Err(_) if !rest.is_empty() => (DEFAULT_HEADER_MODE, true, rest),
+        Err(_) => return Err(DecodeError::InvalidHeader(format!("missing file name in {:?}", line))),
+    };
+
+    let name = if encoded_name {
+        base64_decode_filename(name)
+            .ok_or_else(|| DecodeError::InvalidHeader(format!("invalid encoded file name {:?}", name)))?
+    } else {
+        name.to_string()
+    };
+
+    Ok(Some(DecodedHeader { encoding, mode, mode_defaulted, name, part: None, mtime: None, prefilter: None }))
+}
+
+/// Reports the encoding of the first block in `reader` without consuming
+/// any input, so the caller can decode from the same reader afterwards.
+///
+/// Only the data already buffered by `reader` (as returned by `fill_buf`) is
+/// examined; use a `BufReader` with a larger capacity if the header may be
+/// preceded by a lot of text. Plain `begin` headers are told apart from
+/// xxencoding by their first data line: uuencoded lines never contain
+/// lowercase letters, while full xxencoded lines always start with one.
+pub fn detect_encoding<R: BufRead>(reader: &mut R) -> Result<Encoding, DecodeError> {
+    let buffered = reader.fill_buf()?;
+    let mut lines = buffered.split(|&b| b == b'\n').map(|line| line.strip_suffix(b"\r").unwrap_or(line));
+    while let Some(line) = lines.next() {
+        // Malformed headers are skipped as the decoder does
+        let Ok(Some(header)) = parse_uudecode_header(&String::from_utf8_lossy(line)) else {
+            continue;
+        };
+        if header.encoding == Encoding::Uu
+            && lines.next().is_some_and(|data| data.iter().any(u8::is_ascii_lowercase))
+        {
+            return Ok(Encoding::Xx);
+        }
+        return Ok(header.encoding);
+    }
+    Ok(Encoding::Unknown)
+}
+
+/// Checks that a traditional data line's length character matches its data:
+/// N bytes are carried by exactly 4 * ceil(N / 3) characters
+fn check_line_length(line: &[u8]) -> Result<(), String> {
+    let Some(&len_char) = line.first() else {
+        return Ok(());
+    };
+    let declared = dec(len_char) as usize;
+    let data_chars = line.len() - 1;
+    if declared.div_ceil(3) * 4 == data_chars {
+        return Ok(());
+    }
+    Err(format!(
+        "length character '{}' declares {} bytes, but the line has {} data characters",
+        len_char as char, declared, data_chars
+    ))
+}
+
+/// Whether `c` is a character of the traditional alphabet (space to backtick)
+fn is_uu_char(c: u8) -> bool {
+    (b' '..=b'`').contains(&c)
+}
+
+/// Whether `line` can be the first data line of a block in `encoding`. A
+/// header without a mode is only trusted if such a line follows it.
+fn is_first_data_line(line: &[u8], encoding: Encoding, options: &DecodeOptions) -> bool {
+    let data = if options.numbered_lines {
+        match check_line_number(line, 1) {
+            Ok(prefix_len) => &line[prefix_len..],
+            Err(_) => return false,
+        }
+    } else {
+        line
+    };
+    // An empty block goes straight to its trailer
+    let trailer: &[u8] = if encoding == Encoding::Base64 { b"====" } else { b"`" };
+    if data == trailer || (encoding == Encoding::Uu && data == b" ") {
+        return true;
+    }
+    match encoding {
+        Encoding::Uu => !data.is_empty() && uudecode_line(data, &mut Vec::new()).is_ok(),
+        Encoding::Base64 => !data.is_empty() && base64_decode_bytes(data, options.base64_variant).is_ok(),
+        Encoding::Xx | Encoding::Unknown => false,
+    }
+}
+
+/// Decodes a single traditional uuencoded data line into `out`.
+/// Returns the number of bytes the line declared (0 marks the end of data).
+///
+/// The line must carry enough characters for the bytes it declares (a
+/// group's trailing padding character may be missing) and they must all be
+/// in the traditional alphabet; characters past them are ignored here and
+/// only checked by `strict_line_lengths`.
+fn uudecode_line(line: &[u8], out: &mut Vec<u8>) -> Result<usize, String> {
+    let Some(&len_char) = line.first() else {
+        return Ok(0);
+    };
+    if !is_uu_char(len_char) {
+        return Err(format!("invalid length character {:?}", len_char as char));
+    }
+    let n = dec(len_char) as usize;
+    let data = &line[1..];
+    let needed = (n * 4).div_ceil(3);
+    if data.len() < needed {
+        return Err(format!(
+            "length character '{}' declares {} bytes, but the line only has {} data characters",
+            len_char as char, n, data.len()
+        ));
+    }
+    if let Some(position) = data[..needed].iter().position(|&c| !is_uu_char(c)) {
+        return Err(format!("invalid character {:?} at column {}", data[position] as char, position + 2));
+    }
+    let char_at = |i: usize| dec(data.get(i).copied().unwrap_or(b'`'));
+
+    let mut remaining = n;
+    let mut pos = 0;
+    while remaining > 0 {
+        let (c0, c1, c2, c3) = (char_at(pos), char_at(pos + 1), char_at(pos + 2), char_at(pos + 3));
+        let bytes = [
+            (c0 << 2) | (c1 >> 4),
+            (c1 << 4) | (c2 >> 2),
+            (c2 << 6) | c3,
+        ];
+        let take = remaining.min(3);
+        out.extend_from_slice(&bytes[..take]);
+        remaining -= take;
+        pos += 4;
+    }
+
+    Ok(n)
+}
+
+/// Streaming decoder over an encoded input that may contain several blocks.
+///
+/// Call [`Decoder::next_header`] to find the next `begin` line (skipping any
+/// text before it), then [`Decoder::decode_body`] to write that block's data.
+pub struct Decoder<R: BufRead> {
+    inner: R,
+    options: DecodeOptions,
+    line_number: usize,
+    blocks_started: usize,
+    bytes_read: u64,
+    found_header: bool,
+    indent: Vec<u8>,
+    line: Vec<u8>,
+    /// Sequence number of the last numbered data line in the current block
+    sequence: u64,
+    /// The last block ended at EOF without its end marker
+    truncated: bool,
+    /// `line` was read ahead and is returned again by the next `next_line`
+    replay: bool,
+}
+
+impl<R: BufRead> Decoder<R> {
+    pub fn new(inner: R, options: DecodeOptions) -> Self {
+        Self {
+            inner,
+            options,
+            line_number: 0,
+            blocks_started: 0,
+            bytes_read: 0,
+            found_header: false,
+            indent: Vec::new(),
+            line: Vec::new(),
+            sequence: 0,
+            truncated: false,
+            replay: false,
+        }
+    }
+
+    /// Returns the 1-based number of the last line read
+    pub fn line_number(&self) -> usize {
+        self.line_number
+    }
+
+    /// Returns true if the last block decoded was cut off by EOF before its
+    /// end marker (only accepted with `allow_truncated`)
+    pub fn truncated(&self) -> bool {
+        self.truncated
+    }
+
+    /// Handles EOF inside a block: an error, unless truncated blocks are allowed
+    fn end_of_input(&mut self) -> Result<(), DecodeError> {
+        if !self.options.allow_truncated {
+            return Err(DecodeError::TruncatedData);
+        }
+        self.truncated = true;
+        Ok(())
+    }
+
+    /// Reads the next line into `self.line` with its line terminator (`\n` or
+    /// `\r\n`) and the block indent removed. Returns false at EOF.
+    fn next_line(&mut self) -> io::Result<bool> {
+        if self.replay {
+            self.replay = false;
+            self.line_number += 1;
+        } else {
+            self.line.clear();
+            let read = self.inner.read_until(b'\n', &mut self.line)?;
+            if read == 0 {
+                return Ok(false);
+            }
+            self.line_number += 1;
+            self.bytes_read += read as u64;
+
+            if self.line.last() == Some(&b'\n') {
+                self.line.pop();
+                if self.line.last() == Some(&b'\r') {
+                    self.line.pop();
+                }
+            }
+        }
+
+        if !self.indent.is_empty() && self.line.starts_with(&self.indent) {
+            self.line.drain(..self.indent.len());
+        }
+        Ok(true)
+    }
+
+    /// Reads the line after a header ahead, leaving it to be read again, and
+    /// reports whether it is a data line for `encoding` once `indent` is removed
+    fn data_line_follows(&mut self, encoding: Encoding, indent: &[u8]) -> io::Result<bool> {
+        if !self.next_line()? {
+            return Ok(false);
+        }
+        self.replay = true;
+        self.line_number -= 1;
+        let line = self.line.strip_prefix(indent).unwrap_or(&self.line);
+        Ok(is_first_data_line(line, encoding, &self.options))
+    }
+
+    fn corrupt(&self, reason: String) -> DecodeError {
+        DecodeError::CorruptLine { line_number: self.line_number, reason }
+    }
+
+    /// With `numbered_lines`, checks and removes the sequence number and
+    /// checksum prefix of the block line just read
+    fn strip_line_number(&mut self) -> Result<(), DecodeError> {
+        if !self.options.numbered_lines {
+            return Ok(());
+        }
+        self.sequence += 1;
+        let prefix_len = check_line_number(&self.line, self.sequence).map_err(|reason| self.corrupt(reason))?;
+        self.line.drain(..prefix_len);
+        Ok(())
+    }
+
+    /// Scans forward to the next `begin` header. Returns Ok(None) at EOF.
+    /// The search for the first header is bounded by `max_header_scan`.
+    ///
+    /// Lines that start like a header but aren't one (prose such as "begin
+    /// 644" or "begin the meeting at noon" in a mail body) are skipped, unless
+    /// `strict_headers` is set. If such lines were seen but no header follows
+    /// at all, the result is [`DecodeError::NoBeginLine`] rather than Ok(None).
+    pub fn next_header(&mut self) -> Result<Option<DecodedHeader>, DecodeError> {
+        self.indent.clear();
+        let mut part = None;
+        let mut mtime = None;
+        let mut prefilter = None;
+        let mut malformed = false;
+        loop {
+            if !self.next_line()? {
+                if malformed && !self.found_header {
+                    return Err(DecodeError::NoBeginLine);
+                }
+                return Ok(None);
+            }
+            if !self.found_header && self.bytes_read > self.options.max_header_scan {
+                return Err(DecodeError::HeaderScanLimit { limit: self.options.max_header_scan });
+            }
+            let text = String::from_utf8_lossy(&self.line).into_owned();
+            let trimmed = if self.options.strip_indent { text.trim_start() } else { &text };
+            let parsed = match parse_uudecode_header_with(trimmed, self.options.strict_headers) {
+                Err(_) if !self.options.strict_headers => {
+                    malformed = true;
+                    None
+                }
+                result => result?,
+            };
+            if let Some(mut header) = parsed {
+                let indent = self.line[..text.len() - trimmed.len()].to_vec();
+                let encoding = match self.options.input_format {
+                    Some(forced) if self.options.force_input_format => forced,
+                    _ => header.encoding,
+                };
+                // Without a mode, only the data after it tells a header from
+                // prose such as "begin meeting." in a mail body
+                if header.mode_defaulted && !self.data_line_follows(encoding, &indent)? {
+                    malformed = true;
+                    part = None;
+                    mtime = None;
+                    prefilter = None;
+                    continue;
+                }
+                if let Some(expected) = self.options.input_format
+                    && header.encoding != expected
+                    && !self.options.force_input_format
+                {
+                    return Err(DecodeError::InvalidHeader(format!(
+                        "expected {:?} input but the header announces {:?}", expected, header.encoding
+                    )));
+                }
+                header.encoding = encoding;
+                self.indent = indent;
+                header.part = part;
+                header.mtime = mtime;
+                header.prefilter = prefilter;
+                self.found_header = true;
+                return Ok(Some(header));
+            }
+            // A part comment only applies to the block that immediately follows it
+            part = PartNumber::parse_comment(&text);
+            // mtime and prefilter comments may be followed by other comments
+            // before the header
+            if let Some(seconds) = parse_mtime_comment(&text) {
+                mtime = Some(seconds);
+            } else if let Some(names) = parse_prefilter_comment(&text) {
+                prefilter = Some(names);
+            } else if !text.trim_start().starts_with('#') {
+                mtime = None;
+                prefilter = None;
+            }
+        }
+    }
+
+    /// Finds and decodes the next block, streaming it to `sink` as described
+    /// for [`decode_with_sink`]. Returns Ok(None) at EOF. After an error the
+    /// decoder can keep going: the next call scans for the following header.
+    pub fn decode_next_block(
+        &mut self,
+        sink: &mut impl FnMut(&DecodedBlockMeta, &[u8]) -> io::Result<()>,
+    ) -> Result<Option<DecodedBlockMeta>, DecodeError> {
+        let Some(header) = self.next_header()? else {
+            return Ok(None);
+        };
+        let meta = DecodedBlockMeta { block_index: self.blocks_started, header };
+        self.blocks_started += 1;
+        sink(&meta, &[])?;
+        self.decode_body_chunks(&meta.header, |chunk| {
+            if chunk.is_empty() { Ok(()) } else { sink(&meta, chunk) }
+        })?;
+        Ok(Some(meta))
+    }
+
+    /// Decodes the body of the block introduced by `header`, writing the raw
+    /// bytes to `output` and consuming the end-of-encoding marker.
+    pub fn decode_body<W: Write>(
+        &mut self,
+        header: &DecodedHeader,
+        output: &mut W,
+    ) -> Result<(), DecodeError> {
+        self.decode_body_chunks(header, |chunk| output.write_all(chunk))
+    }
+
+    /// Decodes the body of the block introduced by `header`, passing each
+    /// decoded chunk (one per data line) to `emit`.
+    fn decode_body_chunks(
+        &mut self,
+        header: &DecodedHeader,
+        emit: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<(), DecodeError> {
+        #[cfg(feature = "sha256")]
+        if self.options.verify_sha256 {
+            let mut emit = emit;
+            let mut hasher = crate::Sha256::new();
+            self.decode_body_data(header, |chunk| {
+                hasher.update(chunk);
+                emit(chunk)
+            })?;
+            return self.verify_sha256_line(&hasher.finalize());
+        }
+        self.decode_body_data(header, emit)
+    }
+
+    /// Checks the `# sha256: <hex>` line that must follow the block just decoded
+    #[cfg(feature = "sha256")]
+    fn verify_sha256_line(&mut self, digest: &[u8; 32]) -> Result<(), DecodeError> {
+        if !self.next_line()? {
+            return Err(DecodeError::MissingSha256);
+        }
+        let text = String::from_utf8_lossy(&self.line);
+        let expected = crate::parse_sha256_line(&text)
+            .ok_or(DecodeError::MissingSha256)?
+            .to_ascii_lowercase();
+        let actual = crate::sha256_hex(digest);
+        if expected != actual {
+            return Err(DecodeError::Sha256Mismatch { expected, actual });
+        }
+        Ok(())
+    }
+
+    /// Decodes the data lines of a block up to and including its end marker
+    fn decode_body_data(
+        &mut self,
+        header: &DecodedHeader,
+        mut emit: impl FnMut(&[u8]) -> io::Result<()>,
+    ) -> Result<(), DecodeError> {
+        let mut decoded = Vec::with_capacity(64);
+        self.sequence = 0;
+        self.truncated = false;
+        match header.encoding {
+            Encoding::Uu => loop {
+                if !self.next_line()? {
+                    self.end_of_input()?;
+                    break;
+                }
+                self.strip_line_number()?;
+                if self.line == b"end" {
+                    break;
+                }
+                if self.options.strict_line_lengths {
+                    check_line_length(&self.line).map_err(|reason| self.corrupt(reason))?;
+                }
+                decoded.clear();
+                let declared = uudecode_line(&self.line, &mut decoded).map_err(|reason| self.corrupt(reason))?;
+                if declared == 0 {
+                    // The zero-length line must be followed by "end"
+                    if !self.next_line()? {
+                        self.end_of_input()?;
+                    } else {
+                        self.strip_line_number()?;
+                        if self.line != b"end" {
+                            return Err(DecodeError::TruncatedData);
+                        }
+                    }
+                    break;
+                }
+                emit(&decoded)?;
+            },
+            Encoding::Base64 => {
+                let mut pending = Vec::new();
+                loop {
+                    if !self.next_line()? {
+                        self.end_of_input()?;
+                        break;
+                    }
+                    self.strip_line_number()?;
+                    if self.line == b"====" {
+                        break;
+                    }
+                    pending.extend_from_slice(&self.line);
+                    // Decode whole quads and carry any remainder to the next line
+                    let whole = pending.len() - pending.len() % 4;
+                    decoded = base64_decode_bytes(&pending[..whole], self.options.base64_variant)
+                        .map_err(|reason| self.corrupt(reason))?;
+                    emit(&decoded)?;
+                    pending.drain(..whole);
+                }
+                if !pending.is_empty() {
+                    decoded = base64_decode_bytes(&pending, self.options.base64_variant)
+                        .map_err(|reason| self.corrupt(reason))?;
+                    emit(&decoded)?;
+                }
+            }
+            Encoding::Xx | Encoding::Unknown => {
+                return Err(DecodeError::InvalidHeader(format!("cannot decode {:?} data", header.encoding)));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Describes the block a chunk passed to a [`decode_with_sink`] sink belongs to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodedBlockMeta {
+    /// 0-based position of the block in the input
+    pub block_index: usize,
+    pub header: DecodedHeader,
+}
+
+/// Decodes every block in `input`, streaming the decoded bytes to `sink`
+/// instead of a writer, so callers can send them anywhere (a file per block,
+/// a hasher, a socket).
+///
+/// The sink is called once with an empty chunk when each block starts, so
+/// empty blocks are still seen, then once per non-empty decoded chunk. An error
+/// returned by the sink stops decoding and is reported as [`DecodeError::Io`].
+/// Returns the number of blocks decoded.
+pub fn decode_with_sink<R: BufRead>(
+    input: R,
+    options: &DecodeOptions,
+    mut sink: impl FnMut(&DecodedBlockMeta, &[u8]) -> io::Result<()>,
+) -> Result<usize, DecodeError> {
+    let mut decoder = Decoder::new(input, options.clone());
+    let mut blocks = 0;
+    while decoder.decode_next_block(&mut sink)?.is_some() {
+        blocks += 1;
+    }
+    Ok(blocks)
+}
+
+/// Decodes the next encoded block from `input`, writing the decoded bytes to
+/// `output`. Lines before the `begin` header are skipped.
+///
+/// Returns Ok(None) if EOF is reached before any header was found.
+pub fn decode_block<R: BufRead, W: Write>(
+    input: &mut R,
+    output: &mut W,
+    options: &DecodeOptions,
+) -> Result<Option<DecodedHeader>, DecodeError> {
+    let mut decoder = Decoder::new(input, options.clone());
+    match decoder.next_header()? {
+        Some(header) => {
+            decoder.decode_body(&header, output)?;
+            Ok(Some(header))
+        }
+        None => Ok(None),
+    }
+}
+
+/// What [`decode`] recovered from a block's header, so the caller can create
+/// the right output file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DecodeResult {
+    /// Output file name from the header, as written there
+    pub name: String,
+    /// Permission bits from the header (such as `0o644`)
+    pub mode: u32,
+}
+
+/// Decodes the first block in `input` with the default options, writing the
+/// decoded bytes to `output`. Lines before the `begin` header are skipped.
+///
+/// A thin wrapper over [`decode_block`] for callers that only have a `Read`;
+/// the input is buffered, so it may be read past the end of the block.
+/// Input without any block is a [`DecodeError::NoBeginLine`], and every
+/// decoding error is returned as an `InvalidData` I/O error.
+pub fn decode(input: &mut dyn Read, mut output: &mut dyn Write) -> io::Result<DecodeResult> {
+    let mut input = BufReader::new(input);
+    match decode_block(&mut input, &mut output, &DecodeOptions::default())? {
+        Some(header) => Ok(DecodeResult { name: header.name, mode: header.mode }),
+        None => Err(DecodeError::NoBeginLine.into()),
+    }
+}
+
+/// Like [`decode`], but decodes every block in `input`, as GNU uudecode
+/// does with concatenated streams. Each block's data goes to the writer
+/// `open_output` returns for it, so blocks can land in distinct files; text
+/// between blocks is skipped. Returns one result per block, in input order.
+pub fn decode_all<W: Write>(
+    input: &mut dyn Read,
+    mut open_output: impl FnMut(&DecodeResult) -> io::Result<W>,
+) -> io::Result<Vec<DecodeResult>> {
+    let mut decoder = Decoder::new(BufReader::new(input), DecodeOptions::default());
+    let mut results = Vec::new();
+    while let Some(header) = decoder.next_header()? {
+        let result = DecodeResult { name: header.name.clone(), mode: header.mode };
+        let mut output = open_output(&result)?;
+        decoder.decode_body(&header, &mut output)?;
+        output.flush()?;
+        results.push(result);
+    }
+    if results.is_empty() {
+        return Err(DecodeError::NoBeginLine.into());
+    }
+    Ok(results)
+}
+
+/// Decodes a `data:[MEDIATYPE][;PARAMETERS];base64,PAYLOAD` URI (RFC 2397),
+/// as found in web pages and JSON. The media type is ignored; the payload
+/// must be base64, in either alphabet, and may contain whitespace.
+pub fn decode_data_uri(uri: &str) -> Result<Vec<u8>, DecodeError> {
+    let uri = uri.trim();
+    let rest = uri.get(..5)
+        .filter(|scheme| scheme.eq_ignore_ascii_case("data:"))
+        .map(|_| &uri[5..])
+        .ok_or_else(|| DecodeError::InvalidHeader("not a data URI (expected 'data:')".to_string()))?;
+    let (media_type, payload) = rest.split_once(',')
+        .ok_or_else(|| DecodeError::InvalidHeader("data URI has no ',' before its data".to_string()))?;
+    let is_base64 = media_type.rsplit(';').next().is_some_and(|param| param.eq_ignore_ascii_case("base64"));
+    if !is_base64 {
+        return Err(DecodeError::InvalidHeader("data URI is not base64 encoded".to_string()));
+    }
+    base64_decode_bytes(payload.as_bytes(), Base64Variant::Any)
+        .map_err(|reason| DecodeError::CorruptLine { line_number: 1, reason })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{encode, write_uuencode_header, write_uuencode_trailer, IndentWriter};
+    use crate::tests::scratch_path;
+
+    fn encode_block(data: &[u8], use_base64: bool, indent: usize) -> Vec<u8> {
+        let mut out = IndentWriter::new(Vec::new(), indent);
+        write_uuencode_header(&mut out, 0o644, "test.bin", use_base64, false).unwrap();
+        encode(&mut &data[..], &mut out, use_base64).unwrap();
+        write_uuencode_trailer(&mut out, use_base64).unwrap();
+        out.into_inner()
+    }
+
+    /// Decodes the first block of `encoded`, which must have one
+    fn decode_first_block(encoded: &[u8], options: &DecodeOptions) -> (DecodedHeader, Vec<u8>) {
+        let mut decoded = Vec::new();
+        let header = decode_block(&mut &encoded[..], &mut decoded, options)
+            .unwrap()
+            .expect("no block found");
+        (header, decoded)
+    }
+
+    #[test]
+    fn test_roundtrip_traditional() {
+        let data: Vec<u8> = (0..=255u8).cycle().take(1000).collect();
+        let encoded = encode_block(&data, false, 0);
+        let (header, decoded) = decode_first_block(&encoded, &DecodeOptions::default());
+
+        assert_eq!(header.encoding, Encoding::Uu);
+        assert_eq!(header.mode, 0o644);
+        assert_eq!(header.name, "test.bin");
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_roundtrip_traditional_every_length() {
+        // Every partial group and partial line up to two full 45-byte lines
+        let data: Vec<u8> = (0..=255u8).rev().collect();
+        for len in 0..=91 {
+            let (header, decoded) = decode_first_block(&encode_block(&data[..len], false, 0), &DecodeOptions::default());
+            assert_eq!((header.name.as_str(), header.mode), ("test.bin", 0o644));
+            assert_eq!(decoded, &data[..len], "length {}", len);
+        }
+    }
+
+    #[test]
+    fn test_decode_reports_name_and_mode() {
+        let data: Vec<u8> = (0..=255u8).collect();
+        let mut encoded = b"Subject: the attachment\n\n".to_vec();
+        encoded.extend(crate::encode_to_bytes(&data, "bytes.bin", 0o600, false));
+
+        let mut decoded = Vec::new();
+        let result = decode(&mut &encoded[..], &mut decoded).unwrap();
+        assert_eq!(result, DecodeResult { name: "bytes.bin".to_string(), mode: 0o600 });
+        assert_eq!(decoded, data);
+
+        let error = decode(&mut &b"no block here\n"[..], &mut Vec::new()).unwrap_err();
+        assert_eq!((error.kind(), error.to_string().as_str()), (io::ErrorKind::InvalidData, "No `begin' line"));
+        let error = decode(&mut &b"begin 644 x\n#86)C\n"[..], &mut Vec::new()).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_decode_all_writes_each_block_to_its_own_output() {
+        let mut encoded = crate::encode_to_bytes(b"first file", "one.txt", 0o644, false);
+        encoded.extend(b"-- \ngarbage between the blocks\n");
+        encoded.extend(crate::encode_to_bytes(b"second file", "two.txt", 0o600, true));
+
+        let dir = scratch_path("decode-all");
+        std::fs::create_dir_all(&dir).unwrap();
+        let results = decode_all(&mut &encoded[..], |result| std::fs::File::create(dir.join(&result.name)))
+            .unwrap();
+        let contents = |name: &str| std::fs::read(dir.join(name)).unwrap();
+        let (first, second) = (contents("one.txt"), contents("two.txt"));
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(results, vec![
+            DecodeResult { name: "one.txt".to_string(), mode: 0o644 },
+            DecodeResult { name: "two.txt".to_string(), mode: 0o600 },
+        ]);
+        assert_eq!((first.as_slice(), second.as_slice()), (&b"first file"[..], &b"second file"[..]));
+
+        let error = decode_all(&mut &b"no blocks\n"[..], |_| Ok(Vec::new())).unwrap_err();
+        assert_eq!(error.to_string(), "No `begin' line");
+    }
+
+    #[test]
+    fn test_roundtrip_base64() {
+        let data: Vec<u8> = (0..=255u8).rev().cycle().take(1001).collect();
+        let encoded = encode_block(&data, true, 0);
+        let (header, decoded) = decode_first_block(&encoded, &DecodeOptions::default());
+
+        assert_eq!(header.encoding, Encoding::Base64);
+        assert_eq!(decoded, data);
+    }
+
+    #[test]
+    fn test_roundtrip_empty_input() {
+        // No data lines: traditional blocks still carry the zero-length line
+        assert_eq!(encode_block(b"", false, 0), b"begin 644 test.bin\n`\nend\n");
+        assert_eq!(encode_block(b"", true, 0), b"begin-base64 644 test.bin\n====\n");
+
+        let strict = DecodeOptions { strict_headers: true, strict_line_lengths: true, ..DecodeOptions::default() };
+        for use_base64 in [false, true] {
+            for options in [DecodeOptions::default(), strict.clone()] {
+                let (header, decoded) = decode_first_block(&encode_block(b"", use_base64, 0), &options);
+                assert_eq!(header.name, "test.bin");
+                assert!(decoded.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_roundtrip_with_indent_stripped() {
+        let data = b"indented payload for embedding in config files".repeat(3);
+        for use_base64 in [false, true] {
+            let encoded = encode_block(&data, use_base64, 4);
+            assert!(encoded.split(|&b| b == b'\n')
+                .filter(|l| !l.is_empty())
+                .all(|l| l.starts_with(b"    ")));
+
+            let options = DecodeOptions { strip_indent: true, ..Default::default() };
+            let (_, decoded) = decode_first_block(&encoded, &options);
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn test_decode_with_sink_streams_each_block() {
+        let first: Vec<u8> = (0..200u8).collect();
+        let mut encoded = encode_block(&first, false, 0);
+        encoded.extend(encode_block(b"second block", true, 0));
+
+        let mut blocks: Vec<(String, Vec<u8>)> = Vec::new();
+        let count = decode_with_sink(&encoded[..], &DecodeOptions::default(), |meta, chunk| {
+            if blocks.len() == meta.block_index {
+                blocks.push((meta.header.name.clone(), Vec::new()));
+            }
+            blocks[meta.block_index].1.extend_from_slice(chunk);
+            Ok(())
+        }).unwrap();
+
+        assert_eq!(count, 2);
+        assert_eq!(blocks[0], ("test.bin".to_string(), first));
+        assert_eq!(blocks[1].1, b"second block");
+    }
+
+    #[test]
+    fn test_decode_with_sink_stops_on_sink_error() {
+        let encoded = encode_block(b"payload", false, 0);
+        let result = decode_with_sink(&encoded[..], &DecodeOptions::default(), |_, _| {
+            Err(io::Error::other("sink full"))
+        });
+        assert!(matches!(result, Err(DecodeError::Io(e)) if e.to_string() == "sink full"));
+    }
+
+    #[test]
+    fn test_header_scan_limit() {
+        let mut input = b"not an encoded block\n".repeat(100);
+        input.extend(encode_block(b"late payload", false, 0));
+        let options = DecodeOptions { max_header_scan: 1000, ..Default::default() };
+
+        let mut decoded = Vec::new();
+        let result = decode_block(&mut &input[..], &mut decoded, &options);
+        assert!(matches!(result, Err(DecodeError::HeaderScanLimit { limit: 1000 })));
+
+        // The same input is fine once the header falls within the limit
+        let options = DecodeOptions { max_header_scan: 4096, ..Default::default() };
+        let (_, decoded) = decode_first_block(&input, &options);
+        assert_eq!(decoded, b"late payload");
+    }
+
+    #[cfg(feature = "sha256")]
+    fn encode_block_with_sha256(data: &[u8]) -> Vec<u8> {
+        let mut out = encode_block(data, true, 0);
+        let mut hasher = crate::Sha256::new();
+        hasher.update(data);
+        crate::write_sha256_line(&mut out, &hasher.finalize()).unwrap();
+        out
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn test_sha256_roundtrip() {
+        let data: Vec<u8> = (0..500u32).map(|i| (i % 7) as u8).collect();
+        let encoded = encode_block_with_sha256(&data);
+        let options = DecodeOptions { verify_sha256: true, ..Default::default() };
+
+        let (_, decoded) = decode_first_block(&encoded, &options);
+        assert_eq!(decoded, data);
+    }
+
+    #[cfg(feature = "sha256")]
+    #[test]
+    fn test_sha256_detects_tampering() {
+        let encoded = encode_block_with_sha256(b"original payload");
+        let tampered = String::from_utf8(encoded).unwrap().replace("b3JpZ2lu", "b3JpZ2lO");
+        let options = DecodeOptions { verify_sha256: true, ..Default::default() };
+
+        let mut decoded = Vec::new();
+        let result = decode_block(&mut tampered.as_bytes(), &mut decoded, &options);
+        assert!(matches!(result, Err(DecodeError::Sha256Mismatch { .. })));
+
+        let unsigned = encode_block(b"original payload", true, 0);
+        let result = decode_block(&mut &unsigned[..], &mut decoded, &options);
+        assert!(matches!(result, Err(DecodeError::MissingSha256)));
+    }
+
+    #[test]
+    fn test_decoder_continues_after_corrupt_block() {
+        let mut encoded = encode_block(b"first block", true, 0);
+        let corrupt_at = encoded.iter().position(|&b| b == b'\n').unwrap() + 1;
+        encoded[corrupt_at] = b'!';
+        encoded.extend(encode_block(b"second block", true, 0));
+
+        let mut decoder = Decoder::new(&encoded[..], DecodeOptions::default());
+        let mut decoded = Vec::new();
+        let mut sink = |_: &DecodedBlockMeta, chunk: &[u8]| {
+            decoded.extend_from_slice(chunk);
+            Ok(())
+        };
+        assert!(matches!(decoder.decode_next_block(&mut sink), Err(DecodeError::CorruptLine { .. })));
+        let meta = decoder.decode_next_block(&mut sink).unwrap().unwrap();
+        assert_eq!(meta.block_index, 1);
+        assert!(decoder.decode_next_block(&mut sink).unwrap().is_none());
+        assert_eq!(decoded, b"second block");
+    }
+
+    /// Converts standard base64 data lines to the URL-safe alphabet
+    fn to_url_safe(encoded: &[u8]) -> Vec<u8> {
+        let text = String::from_utf8(encoded.to_vec()).unwrap();
+        let (header, body) = text.split_once('\n').unwrap();
+        format!("{}\n{}", header, body.replace('+', "-").replace('/', "_")).into_bytes()
+    }
+
+    #[test]
+    fn test_base64_variants() {
+        // Bytes chosen so the standard encoding contains both '+' and '/'
+        let data: Vec<u8> = [0xfb, 0xff, 0xbf, 0xfe, 0x3e, 0x3f].repeat(20);
+        let standard = encode_block(&data, true, 0);
+        let url_safe = to_url_safe(&standard);
+        assert_ne!(standard, url_safe);
+
+        let with_variant = |variant| DecodeOptions { base64_variant: variant, ..Default::default() };
+
+        // The lenient default accepts both alphabets
+        assert_eq!(decode_first_block(&standard, &DecodeOptions::default()).1, data);
+        assert_eq!(decode_first_block(&url_safe, &DecodeOptions::default()).1, data);
+
+        assert_eq!(decode_first_block(&standard, &with_variant(Base64Variant::Standard)).1, data);
+        assert_eq!(decode_first_block(&url_safe, &with_variant(Base64Variant::UrlSafe)).1, data);
+
+        let result = decode_block(&mut &url_safe[..], &mut Vec::new(), &with_variant(Base64Variant::Standard));
+        assert!(matches!(result, Err(DecodeError::CorruptLine { line_number: 2, .. })));
+        let result = decode_block(&mut &standard[..], &mut Vec::new(), &with_variant(Base64Variant::UrlSafe));
+        assert!(matches!(result, Err(DecodeError::CorruptLine { .. })));
+    }
+
+    #[test]
+    fn test_encoded_filename_header() {
+        let mut out = Vec::new();
+        write_uuencode_header(&mut out, 0o600, "my file.txt", false, true).unwrap();
+        let line = String::from_utf8(out).unwrap();
+        let header = parse_uudecode_header(line.trim_end()).unwrap().unwrap();
+        assert_eq!(header.name, "my file.txt");
+        assert_eq!(header.mode, 0o600);
+    }
+
+    #[test]
+    fn test_decoder_reads_consecutive_blocks() {
+        let mut encoded = b"Some mail text\n".to_vec();
+        encoded.extend(encode_block(b"first", false, 0));
+        encoded.extend(b"-- \nsignature\n");
+        encoded.extend(encode_block(b"second", true, 0));
+
+        let mut decoder = Decoder::new(&encoded[..], DecodeOptions::default());
+        let mut outputs = Vec::new();
+        while let Some(header) = decoder.next_header().unwrap() {
+            let mut data = Vec::new();
+            decoder.decode_body(&header, &mut data).unwrap();
+            outputs.push((header.encoding, data));
+        }
+
+        assert_eq!(outputs, vec![
+            (Encoding::Uu, b"first".to_vec()),
+            (Encoding::Base64, b"second".to_vec()),
+        ]);
+    }
+
+    #[test]
+    fn test_leading_garbage_is_skipped() {
+        let mut encoded = b"Received: from mail.example.com\nSubject: begin here\n\n".to_vec();
+        encoded.extend(b"beginning of the message\nbegin 644\nbegin 648 not a mode\nbegin\t\n");
+        encoded.extend(b"begin the meeting at noon please.\nbegin now\nbegin-base64 soon\n\n");
+        encoded.extend(encode_block(b"attached", false, 0));
+        let (header, data) = decode_first_block(&encoded, &DecodeOptions::default());
+        assert_eq!((header.name.as_str(), data.as_slice()), ("test.bin", &b"attached"[..]));
+
+        // strict_headers still rejects the first malformed header
+        let strict = DecodeOptions { strict_headers: true, ..DecodeOptions::default() };
+        assert!(matches!(
+            decode_block(&mut &encoded[..], &mut Vec::new(), &strict),
+            Err(DecodeError::InvalidHeader(_))
+        ));
+
+        // Without any valid header: no block, or no begin line if some
+        // malformed ones were skipped
+        assert!(matches!(decode_block(&mut &b"just text\n"[..], &mut Vec::new(), &DecodeOptions::default()), Ok(None)));
+        let error = decode_block(&mut &b"text\nbegin 648 x\nbegin 644\nbegin at noon\n"[..], &mut Vec::new(), &DecodeOptions::default())
+            .unwrap_err();
+        assert!(matches!(error, DecodeError::NoBeginLine));
+    }
+
+    #[test]
+    fn test_part_comment_is_attached_to_header() {
+        let mut encoded = Vec::new();
+        crate::write_part_comment(&mut encoded, PartNumber::new(1, 2).unwrap()).unwrap();
+        encoded.extend(encode_block(b"part one", false, 0));
+        encoded.extend(encode_block(b"unnumbered", false, 0));
+
+        let mut decoder = Decoder::new(&encoded[..], DecodeOptions::default());
+        let first = decoder.next_header().unwrap().unwrap();
+        assert_eq!(first.part, Some(PartNumber::new(1, 2).unwrap()));
+        decoder.decode_body(&first, &mut Vec::new()).unwrap();
+        let second = decoder.next_header().unwrap().unwrap();
+        assert_eq!(second.part, None);
+    }
+
+    #[test]
+    fn test_missing_end_is_truncated() {
+        let encoded = b"begin 644 x\n#86)C\n";
+        let result = decode_block(&mut &encoded[..], &mut Vec::new(), &DecodeOptions::default());
+        assert!(matches!(result, Err(DecodeError::TruncatedData)));
+    }
+
+    fn encode_numbered_block(data: &[u8], use_base64: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        write_uuencode_header(&mut out, 0o644, "test.bin", use_base64, false).unwrap();
+        let mut body = crate::NumberedLineWriter::new(&mut out);
+        encode(&mut &data[..], &mut body, use_base64).unwrap();
+        write_uuencode_trailer(&mut body, use_base64).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_strict_line_lengths() {
+        let strict = DecodeOptions { strict_line_lengths: true, ..Default::default() };
+        // 46 bytes: a full 45-byte line and a 1-byte line padded to 4 characters
+        let data = vec![b'q'; 46];
+        let encoded = crate::encode_to_bytes(&data, "lengths.txt", 0o644, false);
+        assert_eq!(decode_first_block(&encoded, &strict).1, data);
+
+        // Claim 42 bytes on the first line ('J' instead of 'M'); the line
+        // still holds them, so only strict mode notices
+        let damaged = String::from_utf8(encoded).unwrap().replacen("\nM", "\nJ", 1).into_bytes();
+        match decode_block(&mut &damaged[..], &mut Vec::new(), &strict) {
+            Err(DecodeError::CorruptLine { line_number: 2, reason }) => {
+                assert!(reason.contains("declares 42 bytes, but the line has 60 data characters"), "{}", reason);
+            }
+            other => panic!("expected a line length error, got {:?}", other),
+        }
+        let (_, lenient) = decode_first_block(&damaged, &DecodeOptions::default());
+        assert_eq!(lenient.len(), 43);
+    }
+
+    #[test]
+    fn test_corrupt_line_lengths_and_characters() {
+        let corrupt = |encoded: &str| match decode_block(&mut encoded.as_bytes(), &mut Vec::new(), &DecodeOptions::default()) {
+            Err(DecodeError::CorruptLine { line_number, reason }) => (line_number, reason),
+            other => panic!("expected a corrupt line, got {:?}", other),
+        };
+        let valid = String::from_utf8(crate::encode_to_bytes(b"Cat", "cat.txt", 0o644, false)).unwrap();
+        assert_eq!(valid, "begin 644 cat.txt\n#0V%T\n`\nend\n");
+
+        // Truncated: 3 bytes need 4 characters (the padding of a final
+        // partial group may be missing, but not data)
+        let (line, reason) = corrupt("Subject: cat\n\nbegin 644 cat.txt\n#0V\n`\nend\n");
+        assert_eq!(line, 4);
+        assert_eq!(reason, "length character '#' declares 3 bytes, but the line only has 2 data characters");
+        // Over-long length byte: 'M' claims a full 45-byte line
+        let (line, reason) = corrupt("begin 644 cat.txt\nM0V%T\n`\nend\n");
+        assert_eq!((line, reason.as_str()), (2, "length character 'M' declares 45 bytes, but the line only has 4 data characters"));
+        // Characters outside the space..backtick alphabet
+        let (line, reason) = corrupt("begin 644 cat.txt\n#0v%T\n`\nend\n");
+        assert_eq!((line, reason.as_str()), (2, "invalid character 'v' at column 3"));
+        let (_, reason) = corrupt("begin 644 cat.txt\nc0V%T\n`\nend\n");
+        assert_eq!(reason, "invalid length character 'c'");
+
+        // Trailing characters past the declared data are tolerated
+        let (_, data) = decode_first_block(b"begin 644 cat.txt\n#0V%T``\n`\nend\n", &DecodeOptions::default());
+        assert_eq!(data, b"Cat");
+        let (_, data) = decode_first_block(b"begin 644 a.txt\n!80\n`\nend\n", &DecodeOptions::default());
+        assert_eq!(data, b"a");
+    }
+
+    #[test]
+    fn test_numbered_lines_roundtrip() {
+        let data: Vec<u8> = (0..200u32).map(|i| (i * 7 % 256) as u8).collect();
+        let options = DecodeOptions { numbered_lines: true, ..Default::default() };
+        for use_base64 in [false, true] {
+            let encoded = encode_numbered_block(&data, use_base64);
+            assert_eq!(decode_first_block(&encoded, &options).1, data);
+        }
+    }
+
+    #[test]
+    fn test_numbered_lines_detect_dropped_line() {
+        let data = vec![b'x'; 200];
+        let options = DecodeOptions { numbered_lines: true, ..Default::default() };
+        for use_base64 in [false, true] {
+            let encoded = encode_numbered_block(&data, use_base64);
+            let mut lines: Vec<&[u8]> = encoded.split_inclusive(|&b| b == b'\n').collect();
+            lines.remove(2);
+            let damaged = lines.concat();
+
+            let result = decode_block(&mut &damaged[..], &mut Vec::new(), &options);
+            match result {
+                Err(DecodeError::CorruptLine { line_number: 3, reason }) => {
+                    assert!(reason.contains("expected line number 2, found 3"), "{}", reason);
+                }
+                other => panic!("expected a dropped line error, got {:?}", other),
+            }
+
+            // The trailer is numbered too, so losing the last data line shows
+            let mut lines: Vec<&[u8]> = encoded.split_inclusive(|&b| b == b'\n').collect();
+            let last_data = lines.len() - if use_base64 { 2 } else { 3 };
+            lines.remove(last_data);
+            let damaged = lines.concat();
+            match decode_block(&mut &damaged[..], &mut Vec::new(), &options) {
+                Err(DecodeError::CorruptLine { line_number, reason }) => {
+                    assert_eq!(line_number, last_data + 1);
+                    let expected = format!("expected line number {}, found {}", last_data, last_data + 1);
+                    assert!(reason.contains(&expected), "{}", reason);
+                }
+                other => panic!("expected a dropped line error, got {:?}", other),
+            }
+        }
+    }
+
+    #[test]
+    fn test_detect_encoding() {
+        let mut uu = b"Some mail text\n".to_vec();
+        uu.extend(encode_block(b"uuencoded data", false, 0));
+        let base64 = encode_block(b"base64 data", true, 0);
+        let xx = b"begin 644 test.bin\nhI4Jl9U2r7T+h2k6N8rEf0o3l0xC8\n+\nend\n".to_vec();
+
+        for (encoded, expected) in [
+            (&uu, Encoding::Uu),
+            (&base64, Encoding::Base64),
+            (&xx, Encoding::Xx),
+        ] {
+            assert_eq!(detect_encoding(&mut &encoded[..]).unwrap(), expected);
+        }
+        assert_eq!(detect_encoding(&mut &b"no header here\n"[..]).unwrap(), Encoding::Unknown);
+        assert_eq!(detect_encoding(&mut &b""[..]).unwrap(), Encoding::Unknown);
+
+        // Detection leaves the input in place for decoding
+        let mut reader = io::BufReader::new(&uu[..]);
+        assert_eq!(detect_encoding(&mut reader).unwrap(), Encoding::Uu);
+        let mut decoded = Vec::new();
+        decode_block(&mut reader, &mut decoded, &DecodeOptions::default()).unwrap();
+        assert_eq!(decoded, b"uuencoded data");
+    }
+
+    #[test]
+    fn test_input_format_hint() {
+        let base64 = encode_block(b"hinted data", true, 0);
+        let uu = encode_block(b"hinted data", false, 0);
+        let hint = |format: &str, force| DecodeOptions {
+            input_format: Some(format.parse().unwrap()),
+            force_input_format: force,
+            ..Default::default()
+        };
+
+        assert_eq!(decode_first_block(&base64, &hint("base64", false)).1, b"hinted data");
+        let result = decode_block(&mut &uu[..], &mut Vec::new(), &hint("base64", false));
+        assert!(matches!(result, Err(DecodeError::InvalidHeader(_))));
+
+        // Forcing decodes the body as the hinted format despite the header
+        let mut mislabeled = b"begin 644 test.bin\n".to_vec();
+        mislabeled.extend(&base64[base64.iter().position(|&b| b == b'\n').unwrap() + 1..]);
+        let (header, data) = decode_first_block(&mislabeled, &hint("base64", true));
+        assert_eq!(header.encoding, Encoding::Base64);
+        assert_eq!(data, b"hinted data");
+
+        assert!("xx".parse::<Encoding>().is_err());
+    }
+
+    #[test]
+    fn test_irregular_header_spacing() {
+        let header = parse_uudecode_header("begin   644\t my file.txt").unwrap().unwrap();
+        assert_eq!(header.mode, 0o644);
+        assert!(!header.mode_defaulted);
+        assert_eq!(header.name, "my file.txt");
+
+        let header = parse_uudecode_header("begin-base64  600   data.bin").unwrap().unwrap();
+        assert_eq!((header.encoding, header.mode, header.name.as_str()), (Encoding::Base64, 0o600, "data.bin"));
+    }
+
+    #[test]
+    fn test_header_names_and_modes() {
+        let header = parse_uudecode_header("begin 644 my file.txt").unwrap().unwrap();
+        assert_eq!((header.mode, header.name.as_str()), (0o644, "my file.txt"));
+        let header = parse_uudecode_header("begin 0755 a  b\r").unwrap().unwrap();
+        assert_eq!((header.mode, header.name.as_str()), (0o755, "a  b"));
+        let header = parse_uudecode_header("begin 644 report.txt \t\r").unwrap().unwrap();
+        assert_eq!(header.name, "report.txt");
+
+        let error = parse_uudecode_header("begin 648 file.txt").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid header: invalid octal mode \"648\"");
+        let error = parse_uudecode_header("begin 17777 file.txt").unwrap_err();
+        assert_eq!(error.to_string(), "Invalid header: mode 17777 is out of range (at most 7777)");
+        assert!(parse_uudecode_header("begin 99999999999 file.txt").is_err());
+        assert_eq!(parse_uudecode_header("begin 4755 setuid").unwrap().unwrap().mode, 0o4755);
+
+        // The decoder strips the CR from a CRLF header line before parsing
+        let (header, data) = decode_first_block(b"begin 644 dos name.txt\r\n#86)C\r\n`\r\nend\r\n", &DecodeOptions::default());
+        assert_eq!((header.name.as_str(), data.as_slice()), ("dos name.txt", &b"abc"[..]));
+    }
+
+    #[test]
+    fn test_missing_mode_defaults_unless_strict() {
+        let header = parse_uudecode_header("begin  data.bin").unwrap().unwrap();
+        assert_eq!(header.mode, 0o644);
+        assert!(header.mode_defaulted);
+        assert_eq!(header.name, "data.bin");
+
+        assert!(matches!(
+            parse_uudecode_header_with("begin data.bin", true),
+            Err(DecodeError::InvalidHeader(_))
+        ));
+        assert!(matches!(parse_uudecode_header("begin 644"), Err(DecodeError::InvalidHeader(_))));
+        assert!(matches!(parse_uudecode_header("begin "), Err(DecodeError::InvalidHeader(_))));
+        assert_eq!(parse_uudecode_header("beginning of the message").unwrap(), None);
+
+        let encoded = b"begin data.bin\n#86)C\n`\nend\n";
+        let strict = DecodeOptions { strict_headers: true, ..Default::default() };
+        assert!(decode_block(&mut &encoded[..], &mut Vec::new(), &strict).is_err());
+        assert_eq!(decode_first_block(encoded, &DecodeOptions::default()).1, b"abc");
+    }
+
+    #[test]
+    fn test_prose_begin_line_is_not_a_header() {
+        assert!(matches!(
+            parse_uudecode_header("begin the meeting at noon please."),
+            Err(DecodeError::InvalidHeader(_))
+        ));
+
+        // A lone word after "begin" is only a name if a data line follows
+        let mut mail = b"Hi all,\nbegin the meeting at noon please.\nbegin meeting.\nThanks\n".to_vec();
+        mail.extend(encode_block(b"agenda", false, 0));
+        let mut decoder = Decoder::new(&mail[..], DecodeOptions::default());
+        let header = decoder.next_header().unwrap().unwrap();
+        assert_eq!((header.name.as_str(), decoder.line_number()), ("test.bin", 5));
+        let mut data = Vec::new();
+        decoder.decode_body(&header, &mut data).unwrap();
+        assert_eq!(data, b"agenda");
+
+        // A header without a mode still decodes, indented or not
+        let (header, data) = decode_first_block(b"begin meeting.txt\n#86)C\n`\nend\n", &DecodeOptions::default());
+        assert_eq!((header.name.as_str(), data.as_slice()), ("meeting.txt", &b"abc"[..]));
+        let options = DecodeOptions { strip_indent: true, ..Default::default() };
+        let (_, data) = decode_first_block(b"  begin-base64 data.b64\n  YWJj\n  ====\n", &options);
+        assert_eq!(data, b"abc");
+    }
+
+    #[test]
+    fn test_decode_data_uri() {
+        let decoded = decode_data_uri("data:application/octet-stream;base64,SGVsbG8sIFdvcmxkIQ==\n").unwrap();
+        assert_eq!(decoded, b"Hello, World!");
+        assert_eq!(decode_data_uri("DATA:;BASE64,AAEC/w==").unwrap(), [0, 1, 2, 255]);
+        assert_eq!(decode_data_uri("data:;base64,").unwrap(), b"");
+
+        assert!(matches!(decode_data_uri("SGVsbG8="), Err(DecodeError::InvalidHeader(_))));
+        assert!(matches!(decode_data_uri("data:text/plain,Hello"), Err(DecodeError::InvalidHeader(_))));
+        assert!(matches!(decode_data_uri("data:;base64"), Err(DecodeError::InvalidHeader(_))));
+        assert!(matches!(decode_data_uri("data:;base64,SGV*"), Err(DecodeError::CorruptLine { line_number: 1, .. })));
+    }
+
+    #[test]
+    fn test_allow_truncated_keeps_partial_data() {
+        let options = DecodeOptions { allow_truncated: true, ..Default::default() };
+        for use_base64 in [false, true] {
+            let data = vec![b't'; 100];
+            let encoded = encode_block(&data, use_base64, 0);
+            let text = String::from_utf8(encoded).unwrap();
+            // Keep the header and the first data line only
+            let cut: String = text.split_inclusive('\n').take(2).collect();
+
+            let result = decode_block(&mut cut.as_bytes(), &mut Vec::new(), &DecodeOptions::default());
+            assert!(matches!(result, Err(DecodeError::TruncatedData)));
+
+            let mut decoder = Decoder::new(cut.as_bytes(), options.clone());
+            let header = decoder.next_header().unwrap().unwrap();
+            let mut decoded = Vec::new();
+            decoder.decode_body(&header, &mut decoded).unwrap();
+            assert!(decoder.truncated());
+            assert_eq!(decoded, &data[..45]);
+        }
+
+        // Complete blocks are not reported as truncated
+        let mut decoder = Decoder::new(&b"begin 644 x\n#86)C\n`\nend\n"[..], options);
+        let header = decoder.next_header().unwrap().unwrap();
+        decoder.decode_body(&header, &mut Vec::new()).unwrap();
+        assert!(!decoder.truncated());
+    }
+}
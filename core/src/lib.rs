@@ -1,8 +1,36 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::ffi::{OsStr, OsString};
 use std::fmt;
 use std::path::Path;
 
+mod completion;
+mod config;
+mod crc32;
+mod decode;
+mod numbered;
+mod prefilter;
+mod progress;
+#[cfg(feature = "sha256")]
+mod sha256;
+
+pub use completion::{COMPLETION_SHELLS, generate_bash_completion};
+#[allow(deprecated)]
+pub use config::{EffectiveConfig, EffectiveOption, OptionSource, load_config_file};
+pub use crc32::Crc32;
+pub use decode::{
+    Base64Variant, DecodeError, DecodeOptions, DecodeResult, DecodedBlockMeta, DecodedHeader, Decoder, Encoding,
+    base64_decode_filename, decode, decode_all, decode_block, decode_data_uri, decode_with_sink, detect_encoding, parse_uudecode_header,
+    parse_uudecode_header_with, DEFAULT_MAX_HEADER_SCAN,
+};
+pub use numbered::NumberedLineWriter;
+pub use prefilter::{
+    PREFILTER_NAMES, Prefilter, PrefilterPipeline, PrefilterReader, Rot13, parse_prefilter_comment,
+    write_prefilter_comment,
+};
+pub use progress::{Clock, ManualClock, ProgressThrottle, SystemClock};
+#[cfg(feature = "sha256")]
+pub use sha256::{Sha256, SHA256_LINE_PREFIX, parse_sha256_line, sha256_hex, write_sha256_line};
+
 /// Validation error for option values
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct ValidationError {
@@ -23,45 +51,466 @@ impl fmt::Display for ValidationError {
 
 impl std::error::Error for ValidationError {}
 
-/// Function type for validating option values
-pub type OptionValidator = fn(&OsStr) -> Result<(), ValidationError>;
+/// Validates option values. Plain `fn` validators box as they are; closures
+/// can capture settings such as an allowed range
+pub type OptionValidator = Box<dyn Fn(&OsStr) -> Result<(), ValidationError>>;
+
+/// Post-parse fixup registered with [`Parser::with_hook`], such as filling in
+/// an argument or rewriting one option in terms of another
+pub type NormalizationHook<'a> = Box<dyn Fn(&mut ParsedCommand) -> Result<(), ParseError> + 'a>;
+
+/// Environment variable lookup registered with [`Parser::with_env`]
+pub type EnvLookup<'a> = &'a dyn Fn(&str) -> Option<OsString>;
+
+/// The argument that names standard input (or output) instead of a file; it
+/// is always parsed as a positional argument, never as a flag
+pub const STDIN_ARGUMENT: &str = "-";
+
+/// Returns true if `arg` is `STDIN_ARGUMENT`
+pub fn is_stdin_argument(arg: &OsStr) -> bool {
+    arg == STDIN_ARGUMENT
+}
+
+/// Placeholder flag for options that only have a long form (e.g. `--verbose`)
+pub const NO_SHORT_FLAG: char = '\0';
 
 /// Defines a single command-line option with validation
 pub struct OptionDefinition {
     pub flag: char,
     pub name: String,
     pub has_value: bool,
+    pub has_optional_value: bool,  // Accepts `--name=value`, but may also be given bare
     pub default_value: Option<OsString>,  // Used when option is specified but without value
     pub validator: Option<OptionValidator>,
+    pub value_from_file: bool,  // An `@PATH` value is replaced by the contents of PATH
+    pub multiple: bool,  // May be repeated, collecting every value instead of erroring
+    pub counted: bool,  // Flag that may be repeated (`-vvv`), counting each occurrence
+    pub negatable: bool,  // Flag that may also be given as `--no-NAME` to turn it off
+    pub required: bool,  // Parsing fails unless the option is given
+    pub requires: Vec<String>,  // Options that must also be given when this one is
+    pub conflicts: Vec<String>,  // Options that must not be given with this one
+    pub env_var: Option<String>,  // Environment variable supplying the value when the option isn't given
+    pub group: Option<String>,  // Heading the option is listed under in help
+    pub hidden: bool,  // Accepted, but left out of help
+    pub value_hint: ValueHint,  // What the value names, for shell completion
     pub help_text: String,
 }
 
-/// Contains the fully parsed and validated command line
+/// What an option's value names, so shell completions can offer matching words
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ValueHint {
+    /// Anything; nothing is offered
+    #[default]
+    Any,
+    /// A file path
+    FilePath,
+    /// A directory path
+    DirPath,
+}
+
+impl OptionDefinition {
+    /// Starts a definition of a valueless flag with every other setting off;
+    /// the methods below fill in the rest, e.g.
+    /// `OptionDefinition::new('o', "output").value().help("Write to FILE")`.
+    /// Pass `NO_SHORT_FLAG` for long-only options.
+    pub fn new(flag: char, name: impl Into<String>) -> Self {
+        Self {
+            flag,
+            name: name.into(),
+            has_value: false,
+            has_optional_value: false,
+            default_value: None,
+            validator: None,
+            value_from_file: false,
+            multiple: false,
+            counted: false,
+            negatable: false,
+            required: false,
+            requires: Vec::new(),
+            conflicts: Vec::new(),
+            env_var: None,
+            group: None,
+            hidden: false,
+            value_hint: ValueHint::Any,
+            help_text: String::new(),
+        }
+    }
+    
+    /// The option requires a value
+    pub fn value(mut self) -> Self {
+        self.has_value = true;
+        self
+    }
+    
+    /// The option may be given bare or as `--name=value`
+    pub fn optional_value(mut self) -> Self {
+        self.has_optional_value = true;
+        self
+    }
+    
+    /// Value used when the option is given without one.
+    ///
+    /// # Panics
+    ///
+    /// If neither [`OptionDefinition::value`] nor
+    /// [`OptionDefinition::optional_value`] was called first, as a flag has
+    /// no value to default.
+    pub fn default_value(mut self, value: impl Into<OsString>) -> Self {
+        assert!(
+            self.has_value || self.has_optional_value,
+            "option '{}' takes no value, so it can't have a default", self.name
+        );
+        self.default_value = Some(value.into());
+        self
+    }
+    
+    /// Checks each value given, failing the parse with its error
+    pub fn validator<F>(mut self, validator: F) -> Self
+    where
+        F: Fn(&OsStr) -> Result<(), ValidationError> + 'static,
+    {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+    
+    /// An `@PATH` value is replaced by the contents of PATH
+    pub fn value_from_file(mut self) -> Self {
+        self.value_from_file = true;
+        self
+    }
+    
+    /// The option may be repeated, each value being kept
+    pub fn multiple(mut self) -> Self {
+        self.multiple = true;
+        self
+    }
+    
+    /// The option may be repeated, counting how many times it was given
+    pub fn counted(mut self) -> Self {
+        self.counted = true;
+        self
+    }
+    
+    /// `--no-NAME` turns the option off
+    pub fn negatable(mut self) -> Self {
+        self.negatable = true;
+        self
+    }
+    
+    /// Parsing fails unless the option is given
+    pub fn required(mut self) -> Self {
+        self.required = true;
+        self
+    }
+    
+    /// Adds an option that must also be given when this one is
+    pub fn requires(mut self, name: impl Into<String>) -> Self {
+        self.requires.push(name.into());
+        self
+    }
+    
+    /// Adds an option that must not be given with this one
+    pub fn conflicts(mut self, name: impl Into<String>) -> Self {
+        self.conflicts.push(name.into());
+        self
+    }
+    
+    /// Environment variable read when the option is not on the command line
+    pub fn env_var(mut self, var: impl Into<String>) -> Self {
+        self.env_var = Some(var.into());
+        self
+    }
+    
+    /// Heading the option is listed under in help
+    pub fn group(mut self, group: impl Into<String>) -> Self {
+        self.group = Some(group.into());
+        self
+    }
+    
+    /// The option is accepted but left out of help
+    pub fn hidden(mut self) -> Self {
+        self.hidden = true;
+        self
+    }
+    
+    /// What the value names, for shell completion
+    pub fn value_hint(mut self, hint: ValueHint) -> Self {
+        self.value_hint = hint;
+        self
+    }
+    
+    /// Description shown in help
+    pub fn help(mut self, text: impl Into<String>) -> Self {
+        self.help_text = text.into();
+        self
+    }
+}
+
+impl fmt::Debug for OptionDefinition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OptionDefinition")
+            .field("flag", &self.flag)
+            .field("name", &self.name)
+            .field("has_value", &self.has_value)
+            .field("has_optional_value", &self.has_optional_value)
+            .field("default_value", &self.default_value)
+            .field("validator", &self.validator.as_ref().map(|_| "<validator>"))
+            .field("value_from_file", &self.value_from_file)
+            .field("multiple", &self.multiple)
+            .field("counted", &self.counted)
+            .field("negatable", &self.negatable)
+            .field("required", &self.required)
+            .field("requires", &self.requires)
+            .field("conflicts", &self.conflicts)
+            .field("env_var", &self.env_var)
+            .field("group", &self.group)
+            .field("hidden", &self.hidden)
+            .field("value_hint", &self.value_hint)
+            .field("help_text", &self.help_text)
+            .finish()
+    }
+}
+
+/// Whether and how an option was given on the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OptionState<'a> {
+    /// The option was not given
+    Absent,
+    /// The option was given without a value (a flag, or a bare optional-value option)
+    Present,
+    /// The option was given with this value
+    Value(&'a OsStr),
+}
+
+/// Where an option's value came from, as shown in the debug dump and by
+/// `--show-config`. Earlier sources take precedence over later ones.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ValueProvenance {
+    /// Given explicitly by a command line token
+    Cli,
+    /// Given without a value, so its default applies: the option's
+    /// `default_value`, or the command's own default for a bare
+    /// optional-value option such as `-v`
+    Default,
+    /// Not given on the command line, but set by the option's `env_var`
+    Env,
+    /// Set by a `--load-opts` config file (see [`EffectiveConfig`])
+    ConfigFile,
+    /// The option was not given
+    Absent,
+}
+
+impl fmt::Display for ValueProvenance {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ValueProvenance::Cli => write!(f, "cli"),
+            ValueProvenance::Default => write!(f, "default"),
+            ValueProvenance::Env => write!(f, "env"),
+            ValueProvenance::ConfigFile => write!(f, "config file"),
+            ValueProvenance::Absent => write!(f, "absent"),
+        }
+    }
+}
+
+/// Contains the fully parsed and validated command line. Two commands are
+/// equal when their executable path, options (in any order) and arguments
+/// match; where the option values came from is not compared.
+#[derive(Debug, Clone, Default)]
 pub struct ParsedCommand {
     pub executable_path: OsString,
+    /// Options that were given: `None` for present without a value
     pub options: HashMap<String, Option<OsString>>,
     pub arguments: Vec<OsString>,
+    /// Every value given to options defined with `multiple`, in command line
+    /// order; `options` holds the last one
+    pub repeated: HashMap<String, Vec<OsString>>,
+    /// How many times each option defined with `counted` was given
+    pub counts: HashMap<String, usize>,
+    /// Negatable options turned off with `--no-NAME` (and not given again)
+    pub negated: HashSet<String>,
+    /// Options given without a value whose default applies
+    pub defaulted: HashSet<String>,
+    /// Options whose value came from their environment variable
+    pub from_env: HashSet<String>,
+    /// Options whose value came from a config file
+    pub from_config: HashSet<String>,
+}
+
+impl PartialEq for ParsedCommand {
+    fn eq(&self, other: &Self) -> bool {
+        self.executable_path == other.executable_path
+            && self.options == other.options
+            && self.arguments == other.arguments
+            && self.repeated == other.repeated
+            && self.counts == other.counts
+            && self.negated == other.negated
+    }
 }
 
+impl Eq for ParsedCommand {}
+
 impl ParsedCommand {
+    /// Starts building a command directly, without parsing a command line
+    pub fn builder() -> ParsedCommandBuilder {
+        ParsedCommandBuilder::default()
+    }
+    
     /// Returns true if the named option was specified on the command line
     pub fn is_option_set(&self, name: &str) -> bool {
         self.options.contains_key(name)
     }
     
+    /// Returns whether the named option was absent, present without a value,
+    /// or given a value
+    pub fn option_state(&self, name: &str) -> OptionState<'_> {
+        match self.options.get(name) {
+            None => OptionState::Absent,
+            Some(None) => OptionState::Present,
+            Some(Some(value)) => OptionState::Value(value),
+        }
+    }
+    
+    /// Returns whether the named option's value came from the command line,
+    /// a default, the environment or a config file, or whether it was absent
+    pub fn value_provenance(&self, name: &str) -> ValueProvenance {
+        if !self.options.contains_key(name) {
+            ValueProvenance::Absent
+        } else if self.defaulted.contains(name) {
+            ValueProvenance::Default
+        } else if self.from_env.contains(name) {
+            ValueProvenance::Env
+        } else if self.from_config.contains(name) {
+            ValueProvenance::ConfigFile
+        } else {
+            ValueProvenance::Cli
+        }
+    }
+    
     /// Returns the value associated with an option, or None if not set
     pub fn option_value(&self, name: &str) -> Option<&OsStr> {
         self.options.get(name).and_then(|v| v.as_deref())
     }
     
+    /// Returns whether a flag was turned on (`--name`) or off (`--no-name`,
+    /// for options defined with `negatable`), or None if neither was given
+    pub fn option_bool(&self, name: &str) -> Option<bool> {
+        if self.options.contains_key(name) {
+            Some(true)
+        } else if self.negated.contains(name) {
+            Some(false)
+        } else {
+            None
+        }
+    }
+    
+    /// Returns how many times the named option was given: any number for
+    /// options defined with `counted`, otherwise 0 or 1
+    pub fn option_count(&self, name: &str) -> usize {
+        match self.counts.get(name) {
+            Some(&count) => count,
+            None => usize::from(self.options.contains_key(name)),
+        }
+    }
+    
+    /// Returns every value given to an option, in command line order. Only
+    /// options defined with `multiple` can have more than one.
+    pub fn option_values(&self, name: &str) -> Vec<&OsStr> {
+        match self.repeated.get(name) {
+            Some(values) => values.iter().map(OsString::as_os_str).collect(),
+            None => self.option_value(name).into_iter().collect(),
+        }
+    }
+    
     /// Returns the value for an option or its default value
     pub fn option_value_or_default<'a>(&'a self, name: &str, default: &'a OsStr) -> &'a OsStr {
         self.option_value(name).unwrap_or(default)
     }
     
+    /// Returns true if the positional argument at `index` is `-`, meaning
+    /// standard input (or output) rather than a file of that name
+    pub fn argument_is_stdin(&self, index: usize) -> bool {
+        self.arguments.get(index).is_some_and(|arg| is_stdin_argument(arg))
+    }
+    
     /// Returns true if the option has an explicit value (not just present)
     pub fn has_option_value(&self, name: &str) -> bool {
-        self.options.get(name).map_or(false, |v| v.is_some())
+        self.options.get(name).is_some_and(|v| v.is_some())
+    }
+    
+    /// Removes an option, returning its previous entry (None if it wasn't set)
+    pub fn unset_option(&mut self, name: &str) -> Option<Option<OsString>> {
+        self.defaulted.remove(name);
+        self.from_env.remove(name);
+        self.from_config.remove(name);
+        self.repeated.remove(name);
+        self.counts.remove(name);
+        self.negated.remove(name);
+        self.options.remove(name)
+    }
+    
+    /// Rebuilds a command line that parses back to this command: the
+    /// executable path, each option as `--name` or `--name=value` (sorted by
+    /// name, repeated for each value of a `multiple` option), then `--` and
+    /// the arguments
+    pub fn to_args(&self) -> Vec<OsString> {
+        let mut names: Vec<&String> = self.options.keys().collect();
+        names.sort();
+        
+        let mut args = vec![self.executable_path.clone()];
+        for name in names {
+            let values = match (self.repeated.get(name), &self.options[name]) {
+                (Some(values), _) => values.iter().map(Some).collect(),
+                (None, value) => vec![value.as_ref(); self.counts.get(name).copied().unwrap_or(1)],
+            };
+            for value in values {
+                let mut arg = OsString::from(format!("--{}", name));
+                if let Some(value) = value {
+                    arg.push("=");
+                    arg.push(value);
+                }
+                args.push(arg);
+            }
+        }
+        let mut negated: Vec<&String> = self.negated.iter().collect();
+        negated.sort();
+        args.extend(negated.into_iter().map(|name| OsString::from(format!("--no-{}", name))));
+        if !self.arguments.is_empty() {
+            args.push(OsString::from("--"));
+            args.extend(self.arguments.iter().cloned());
+        }
+        args
+    }
+}
+
+/// Builds a `ParsedCommand` option by option, for code (mostly tests) that
+/// needs one without going through argv parsing. No validation is done.
+#[derive(Debug, Clone, Default)]
+pub struct ParsedCommandBuilder {
+    command: ParsedCommand,
+}
+
+impl ParsedCommandBuilder {
+    /// Sets the executable path (empty by default)
+    pub fn executable_path(mut self, path: impl Into<OsString>) -> Self {
+        self.command.executable_path = path.into();
+        self
+    }
+    
+    /// Sets an option, with `None` meaning present without a value
+    pub fn option(mut self, name: &str, value: Option<&str>) -> Self {
+        self.command.options.insert(name.to_string(), value.map(OsString::from));
+        self
+    }
+    
+    /// Appends a positional argument
+    pub fn argument(mut self, argument: impl Into<OsString>) -> Self {
+        self.command.arguments.push(argument.into());
+        self
+    }
+    
+    /// Returns the command built so far
+    pub fn build(self) -> ParsedCommand {
+        self.command
     }
 }
 
@@ -73,6 +522,47 @@ pub enum ParseError {
     MissingValue(String),
     InvalidFlagCombination(String),
     DuplicateOption(String),
+    /// A long option prefix matching several options: the prefix as given
+    /// and the names of the candidates
+    AmbiguousOption(String, Vec<String>),
+    /// An option defined as `required` was not given
+    MissingRequiredOption(String),
+    /// An option was given without an option it `requires`
+    UnmetDependency { option: String, requires: String },
+    /// No subcommand followed the global options
+    MissingSubcommand,
+    UnknownSubcommand(String),
+    /// An `@FILE` argument was reached through more than
+    /// `MAX_RESPONSE_FILE_DEPTH` nested response files, usually because a
+    /// file names itself
+    ResponseFileDepth(String),
+    /// Another error, tied to the argument at fault: `index` counts from the
+    /// executable path (0), as in the vector given to [`Parser::parse`].
+    /// Arguments read from a response file report the `@FILE` argument.
+    AtArgument { index: usize, error: Box<ParseError> },
+}
+
+impl ParseError {
+    /// Ties the error to the argument at `index`
+    pub fn at_argument(self, index: usize) -> Self {
+        ParseError::AtArgument { index, error: Box::new(self) }
+    }
+    
+    /// Returns the index of the argument at fault, if the error is tied to one
+    pub fn argument_index(&self) -> Option<usize> {
+        match self {
+            ParseError::AtArgument { index, .. } => Some(*index),
+            _ => None,
+        }
+    }
+    
+    /// Returns the error without its argument index
+    pub fn without_argument_index(self) -> Self {
+        match self {
+            ParseError::AtArgument { error, .. } => *error,
+            error => error,
+        }
+    }
 }
 
 impl fmt::Display for ParseError {
@@ -83,31 +573,39 @@ impl fmt::Display for ParseError {
             ParseError::MissingValue(opt) => write!(f, "Option '{}' requires a value", opt),
             ParseError::InvalidFlagCombination(flags) => write!(f, "Invalid flag combination: {}", flags),
             ParseError::DuplicateOption(opt) => write!(f, "Option '{}' specified multiple times", opt),
+            ParseError::AmbiguousOption(opt, candidates) => {
+                let candidates: Vec<String> = candidates.iter().map(|name| format!("--{}", name)).collect();
+                write!(f, "Ambiguous option: {} (could be {})", opt, candidates.join(", "))
+            }
+            ParseError::MissingRequiredOption(opt) => write!(f, "Option '{}' is required", opt),
+            ParseError::UnmetDependency { option, requires } => {
+                write!(f, "Option '{}' requires option '{}'", option, requires)
+            }
+            ParseError::MissingSubcommand => write!(f, "No subcommand given"),
+            ParseError::UnknownSubcommand(name) => write!(f, "Unknown subcommand: {}", name),
+            ParseError::ResponseFileDepth(path) => {
+                write!(f, "Response file '{}' is nested too deeply (does it include itself?)", path)
+            }
+            ParseError::AtArgument { index, error } => write!(f, "argument {}: {}", index, error),
         }
     }
 }
 
 impl std::error::Error for ParseError {}
 
+/// Standard options that make a command print something and exit, so
+/// required options may be left out alongside them
+const EXIT_EARLY_OPTIONS: [&str; 3] = ["help", "version", "license"];
+
 /// Returns the standard options that all commands must support
 pub fn standard_options() -> Vec<OptionDefinition> {
     vec![
-        OptionDefinition {
-            flag: 'h',
-            name: "help".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Display this help message and exit".to_string(),
-        },
-        OptionDefinition {
-            flag: 'V',
-            name: "version".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Display version information and exit".to_string(),
-        },
+        OptionDefinition::new('h', "help")
+            .help("Display this help message and exit"),
+        OptionDefinition::new('V', "version")
+            .help("Display version information and exit"),
+        OptionDefinition::new(NO_SHORT_FLAG, "license")
+            .help("Display the full license text and exit"),
     ]
 }
 
@@ -116,163 +614,934 @@ pub fn parse_command_line(
     option_definitions: &[OptionDefinition],
     args: impl Iterator<Item = OsString>
 ) -> Result<ParsedCommand, ParseError> {
-    let mut args = args.collect::<Vec<_>>();
+    Parser::new(option_definitions)?.parse(args)
+}
+
+/// Parses command line arguments, reporting every problem instead of only
+/// the first (see [`Parser::parse_lenient`])
+pub fn parse_command_line_lenient(
+    option_definitions: &[OptionDefinition],
+    args: impl Iterator<Item = OsString>
+) -> (ParsedCommand, Vec<ParseError>) {
+    match Parser::new(option_definitions) {
+        Ok(parser) => parser.parse_lenient(args),
+        Err(error) => (ParsedCommand::builder().build(), vec![error]),
+    }
+}
+
+/// Parses a multi-tool command line of the form `tool [GLOBAL OPTIONS]
+/// SUBCOMMAND [OPTIONS] [ARGS]`. Global parsing stops at the first
+/// positional argument (as with `stop_at_first_argument`), which names the
+/// subcommand; the rest of the command
+/// line is parsed with that subcommand's definitions, with the subcommand
+/// name as its executable path (so argument indexes in its errors count from
+/// the subcommand name). Returns the global command, the subcommand name and
+/// the subcommand's command.
+pub fn parse_global_then_sub(
+    global_definitions: &[OptionDefinition],
+    subcommands: &[(&str, &[OptionDefinition])],
+    args: impl Iterator<Item = OsString>,
+) -> Result<(ParsedCommand, String, ParsedCommand), ParseError> {
+    let config = ParserConfig { stop_at_first_argument: true, ..ParserConfig::default() };
+    let mut global = Parser::new(global_definitions)?.with_config(config)?.parse(args)?;
+    if global.arguments.is_empty() {
+        return Err(ParseError::MissingSubcommand);
+    }
+    let sub_args = std::mem::take(&mut global.arguments);
+    let name = sub_args[0].to_string_lossy().into_owned();
+    let (_, sub_definitions) = subcommands.iter()
+        .find(|(sub_name, _)| *sub_name == name)
+        .ok_or_else(|| ParseError::UnknownSubcommand(name.clone()))?;
+    let sub = parse_command_line(sub_definitions, sub_args.into_iter())?;
+    Ok((global, name, sub))
+}
+
+/// Settings that change how a `Parser` reads ambiguous command lines
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ParserConfig {
+    /// When a value-taking option is given without `=value`, take the next
+    /// argument as its value even if it starts with `-` (but never `--`).
+    /// By default such an argument is treated as the next option instead.
+    pub greedy_values: bool,
+    /// Stop looking for options at the first positional argument (POSIX),
+    /// treating everything after it as arguments. By default options may
+    /// follow positional arguments (GNU) and only `--` ends them.
+    pub stop_at_first_argument: bool,
+    /// Match long option names regardless of ASCII case, so `--BASE64`
+    /// selects `--base64`. Off by default, as POSIX names are case-sensitive;
+    /// short flags always are.
+    pub case_insensitive_long_options: bool,
+}
+
+impl ParserConfig {
+    /// The form of a long option name used for lookups
+    fn long_option_key(&self, name: &str) -> String {
+        if self.case_insensitive_long_options {
+            name.to_ascii_lowercase()
+        } else {
+            name.to_string()
+        }
+    }
+}
+
+/// What the parser does after a token
+enum TokenStep {
+    /// Go on to the next token
+    Next,
+    /// Look at the same position again (a response file was expanded into it)
+    Reread,
+    /// Everything left is positional; stop looking for options
+    Stop,
+}
+
+/// A set of option definitions with their lookup tables built once, so the
+/// same definitions can be parsed against and inspected
+pub struct Parser<'a> {
+    definitions: &'a [OptionDefinition],
+    by_flag: HashMap<char, &'a OptionDefinition>,
+    /// Keyed by name, folded to lowercase with `case_insensitive_long_options`
+    by_name: HashMap<String, &'a OptionDefinition>,
+    trace: Option<&'a dyn Fn(&str)>,
+    /// Looks up `env_var` values; the process environment when None
+    env: Option<EnvLookup<'a>>,
+    hooks: Vec<NormalizationHook<'a>>,
+    config: ParserConfig,
+}
+
+impl<'a> Parser<'a> {
+    /// Builds the lookup tables, rejecting definitions that reuse a flag or name
+    pub fn new(option_definitions: &'a [OptionDefinition]) -> Result<Self, ParseError> {
+        let mut by_flag: HashMap<char, &OptionDefinition> = HashMap::new();
+        for def in option_definitions {
+            if def.flag != NO_SHORT_FLAG && by_flag.insert(def.flag, def).is_some() {
+                return Err(ParseError::DuplicateOption(format!("flag '{}'", def.flag)));
+            }
+        }
+        let config = ParserConfig::default();
+        let by_name = Self::name_table(option_definitions, &config)?;
+        // A misspelled name in `requires` or `conflicts` would otherwise
+        // never match, silently turning the rule off
+        for def in option_definitions {
+            for (rule, names) in [("requires", &def.requires), ("conflicts", &def.conflicts)] {
+                if let Some(name) = names.iter().find(|name| !option_definitions.iter().any(|other| other.name == **name)) {
+                    return Err(ParseError::UnknownOption(format!("{} (in the {} of --{})", name, rule, def.name)));
+                }
+            }
+        }
+        
+        Ok(Self { definitions: option_definitions, by_flag, by_name, trace: None, env: None, hooks: Vec::new(), config })
+    }
     
-    if args.is_empty() {
-        return Err(ParseError::UnknownOption("No executable path provided".to_string()));
+    /// Builds the long name lookup table for `config`, rejecting names that
+    /// are the same, or differ only in case when case is ignored
+    fn name_table(
+        option_definitions: &'a [OptionDefinition],
+        config: &ParserConfig,
+    ) -> Result<HashMap<String, &'a OptionDefinition>, ParseError> {
+        let mut by_name = HashMap::new();
+        for def in option_definitions {
+            if by_name.insert(config.long_option_key(&def.name), def).is_some() {
+                return Err(ParseError::DuplicateOption(def.name.clone()));
+            }
+        }
+        Ok(by_name)
     }
     
-    let executable_path = args.remove(0);
+    /// Reports every parsing decision (one line per token or flag) to `trace`,
+    /// for debugging complex invocations with `--trace`
+    pub fn with_trace(mut self, trace: &'a dyn Fn(&str)) -> Self {
+        self.trace = Some(trace);
+        self
+    }
     
-    // Build lookup tables
-    let mut by_flag: HashMap<char, &OptionDefinition> = HashMap::new();
-    let mut by_name: HashMap<&str, &OptionDefinition> = HashMap::new();
+    /// Looks up the `env_var` of each option with `env` instead of reading
+    /// the process environment, so that callers (and tests) can supply
+    /// values without changing the environment
+    pub fn with_env(mut self, env: EnvLookup<'a>) -> Self {
+        self.env = Some(env);
+        self
+    }
     
-    for def in option_definitions {
-        if by_flag.insert(def.flag, def).is_some() {
-            return Err(ParseError::DuplicateOption(format!("flag '{}'", def.flag)));
+    /// Adds a hook run on every successfully parsed command, after the
+    /// dependency checks; hooks run in the order they were added, and an
+    /// error from one fails the parse. Hooks should be idempotent, as
+    /// [`Parser::run_hooks`] may run them again.
+    pub fn with_hook(mut self, hook: impl Fn(&mut ParsedCommand) -> Result<(), ParseError> + 'a) -> Self {
+        self.hooks.push(Box::new(hook));
+        self
+    }
+    
+    /// Replaces the default `ParserConfig`, rebuilding the name table for it
+    /// (which fails if two names only differ in case and case is ignored)
+    pub fn with_config(mut self, config: ParserConfig) -> Result<Self, ParseError> {
+        self.by_name = Self::name_table(self.definitions, &config)?;
+        self.config = config;
+        Ok(self)
+    }
+    
+    /// Returns true if `next` should be taken as the value of the option
+    /// before it, rather than parsed as an option itself. A lone `-` is never
+    /// an option, so it can name stdin or stdout (`-o -`).
+    fn takes_as_value(&self, next: &OsStr) -> bool {
+        if self.config.greedy_values {
+            next != "--"
+        } else {
+            next == STDIN_ARGUMENT || !next.to_string_lossy().starts_with('-')
+        }
+    }
+    
+    /// Emits a trace event, only formatting it when tracing is enabled
+    fn trace_event(&self, event: impl FnOnce() -> String) {
+        if let Some(trace) = self.trace {
+            trace(&event());
         }
-        if by_name.insert(&def.name, def).is_some() {
-            return Err(ParseError::DuplicateOption(def.name.clone()));
+    }
+    
+    /// Finds the option named by a long option, which may be abbreviated to
+    /// any unambiguous prefix of its name. An exact name always wins.
+    fn find_long_option(&self, option_name: &str) -> Result<&'a OptionDefinition, ParseError> {
+        let key = self.config.long_option_key(option_name);
+        if let Some(def) = self.by_name.get(&key) {
+            return Ok(def);
+        }
+        let candidates: Vec<&'a OptionDefinition> = self.definitions.iter()
+            .filter(|def| !key.is_empty() && self.config.long_option_key(&def.name).starts_with(&key))
+            .collect();
+        match candidates[..] {
+            [def] => Ok(def),
+            [] => Err(ParseError::UnknownOption(format!("--{}", option_name))),
+            _ => Err(ParseError::AmbiguousOption(
+                format!("--{}", option_name),
+                candidates.iter().map(|def| def.name.clone()).collect(),
+            )),
+        }
+    }
+    
+    /// Finds the negatable option turned off by `--no-NAME`, which must be
+    /// spelled out in full. A defined option whose name starts with `no-`
+    /// takes precedence.
+    fn find_negation(&self, option_name: &str) -> Option<&'a OptionDefinition> {
+        let key = self.config.long_option_key(option_name);
+        if self.by_name.contains_key(&key) {
+            return None;
+        }
+        self.by_name.get(key.strip_prefix("no-")?).copied().filter(|def| def.negatable)
+    }
+    
+    /// Checks `required`, `requires` and `conflicts` against the options
+    /// given, reporting every problem: missing required options first, then
+    /// the others, each in definition order
+    fn check_dependencies(&self, options: &HashMap<String, Option<OsString>>) -> Vec<ParseError> {
+        let mut errors: Vec<ParseError> = self.definitions.iter()
+            .filter(|def| def.required && !options.contains_key(&def.name))
+            .map(|def| ParseError::MissingRequiredOption(def.name.clone()))
+            .collect();
+        for def in self.definitions.iter().filter(|def| options.contains_key(&def.name)) {
+            if let Some(required) = def.requires.iter().find(|name| !options.contains_key(*name)) {
+                errors.push(ParseError::UnmetDependency { option: def.name.clone(), requires: required.clone() });
+            }
+            if let Some(conflict) = def.conflicts.iter().find(|name| options.contains_key(*name)) {
+                errors.push(ParseError::InvalidFlagCombination(format!(
+                    "--{} cannot be combined with --{}", def.name, conflict
+                )));
+            }
+        }
+        errors
+    }
+    
+    /// Returns the long name of every defined option, in definition order
+    pub fn option_names(&self) -> Vec<&str> {
+        self.definitions.iter().map(|def| def.name.as_str()).collect()
+    }
+    
+    /// Returns every defined short flag, in definition order (long-only
+    /// options are skipped)
+    pub fn flags(&self) -> Vec<char> {
+        self.definitions.iter()
+            .map(|def| def.flag)
+            .filter(|&flag| flag != NO_SHORT_FLAG)
+            .collect()
+    }
+    
+    /// Parses command line arguments (including the executable path),
+    /// failing with the first problem found
+    pub fn parse(&self, args: impl Iterator<Item = OsString>) -> Result<ParsedCommand, ParseError> {
+        let (parsed, errors) = self.parse_lenient(args);
+        match errors.into_iter().next() {
+            Some(error) => Err(error),
+            None => Ok(parsed),
         }
     }
     
-    let mut options: HashMap<String, Option<OsString>> = HashMap::new();
-    let mut arguments: Vec<OsString> = Vec::new();
-    let mut i = 0;
+    /// Parses command line arguments like [`Parser::parse`], but carries on
+    /// past problems to report them all: a token that fails (an unknown
+    /// option, a missing or invalid value) is recorded and skipped, along
+    /// with any value it took. Errors are in the order found, so the first is
+    /// the one `parse` fails with. Hooks only run when there are no errors.
+    pub fn parse_lenient(&self, args: impl Iterator<Item = OsString>) -> (ParsedCommand, Vec<ParseError>) {
+        let mut args = args.collect::<Vec<_>>();
+        
+        if args.is_empty() {
+            let error = ParseError::UnknownOption("No executable path provided".to_string());
+            return (ParsedCommand::builder().build(), vec![error]);
+        }
+        
+        let executable_path = args.remove(0);
+        let by_flag = &self.by_flag;
+        
+        let mut options: HashMap<String, Option<OsString>> = HashMap::new();
+        let mut defaulted: HashSet<String> = HashSet::new();
+        let mut repeated: HashMap<String, Vec<OsString>> = HashMap::new();
+        let mut counts: HashMap<String, usize> = HashMap::new();
+        let mut negated: HashSet<String> = HashSet::new();
+        // Records an option's value, keeping every value of a `multiple` option
+        let mut record = |options: &mut HashMap<String, Option<OsString>>, def: &OptionDefinition, value: Option<OsString>| {
+            if def.multiple && let Some(value) = &value {
+                repeated.entry(def.name.clone()).or_default().push(value.clone());
+            }
+            if def.counted {
+                *counts.entry(def.name.clone()).or_default() += 1;
+            }
+            options.insert(def.name.clone(), value);
+        };
+        let mut arguments: Vec<OsString> = Vec::new();
+        // How many response files deep each token came from
+        let mut depths = vec![0; args.len()];
+        // Where in the original argument vector each token came from
+        let mut origins: Vec<usize> = (1..=args.len()).collect();
+        let mut i = 0;
+    
+        let mut errors: Vec<ParseError> = Vec::new();
     
-    while i < args.len() {
-        let arg = &args[i];
-        let arg_str = arg.to_string_lossy();
+        while i < args.len() {
+            // A failing token is recorded and skipped
+            let mut parse_token = || -> Result<TokenStep, ParseError> {
+                if let Some(path) = args[i].to_str().and_then(response_file_path) {
+                    let path = path.to_string();
+                    if depths[i] == MAX_RESPONSE_FILE_DEPTH {
+                        return Err(ParseError::ResponseFileDepth(path).at_argument(origins[i]));
+                    }
+                    let contents = read_response_file(&path).map_err(|e| e.at_argument(origins[i]))?;
+                    self.trace_event(|| format!("token '@{}' -> response file ({} arguments)", path, contents.len()));
+                    let depth = depths[i] + 1;
+                    depths.splice(i..=i, std::iter::repeat_n(depth, contents.len()));
+                    origins.splice(i..=i, std::iter::repeat_n(origins[i], contents.len()));
+                    args.splice(i..=i, contents);
+                    return Ok(TokenStep::Reread);
+                }
+            
+                let arg = &args[i];
+                let arg_str = arg.to_string_lossy();
         
-        if arg_str == "--" {
-            // Everything after -- is arguments
-            arguments.extend_from_slice(&args[i + 1..]);
-            break;
-        } else if arg_str.starts_with("--") {
-            // Long option
-            let (option_name, value) = if let Some(eq_pos) = arg_str.find('=') {
-                (&arg_str[2..eq_pos], Some(OsString::from(&arg_str[eq_pos + 1..])))
-            } else {
-                (&arg_str[2..], None)
-            };
+                if arg_str == "--" {
+                    // Everything after -- is arguments
+                    self.trace_event(|| "token '--' -> end of options".to_string());
+                    arguments.extend_from_slice(&args[i + 1..]);
+                    return Ok(TokenStep::Stop);
+                } else if let Some((long, def)) = arg_str.strip_prefix("--")
+                    .and_then(|long| Some((long, self.find_negation(long.split('=').next()?)?)))
+                {
+                    // `--no-NAME` turns off a negatable option given earlier (on
+                    // the command line, or later from a config file)
+                    if long.contains('=') {
+                        return Err(ParseError::ValidationError(ValidationError::new(
+                            format!("Option '--{}' does not accept a value", long.split('=').next().unwrap_or_default())
+                        )).at_argument(origins[i]));
+                    }
+                    self.trace_event(|| format!("token '{}' -> option '{}' negated", arg_str, def.name));
+                    options.remove(&def.name);
+                    negated.insert(def.name.clone());
+                } else if let Some(long) = arg_str.strip_prefix("--") {
+                    // Long option
+                    let (option_name, value) = if let Some(eq_pos) = long.find('=') {
+                        (&long[..eq_pos], Some(OsString::from(&long[eq_pos + 1..])))
+                    } else {
+                        (long, None)
+                    };
             
-            let def = by_name.get(option_name)
-                .ok_or_else(|| ParseError::UnknownOption(format!("--{}", option_name)))?;
+                    let def = self.find_long_option(option_name).map_err(|e| e.at_argument(origins[i]))?;
             
-            if options.contains_key(&def.name) {
-                return Err(ParseError::DuplicateOption(def.name.clone()));
-            }
+                    if options.contains_key(&def.name) && !def.multiple && !def.counted {
+                        return Err(ParseError::DuplicateOption(def.name.clone()).at_argument(origins[i]));
+                    }
             
-            let final_value = if def.has_value {
-                if let Some(v) = value {
-                    Some(v)
-                } else if i + 1 < args.len() && !args[i + 1].to_string_lossy().starts_with('-') {
-                    i += 1;
-                    Some(args[i].clone())
-                } else if let Some(default) = &def.default_value {
-                    Some(default.clone())
-                } else {
-                    return Err(ParseError::MissingValue(def.name.clone()));
-                }
-            } else {
-                if value.is_some() {
-                    return Err(ParseError::ValidationError(ValidationError::new(
-                        format!("Option '{}' does not accept a value", def.name)
-                    )));
-                }
-                None
-            };
+                    let final_value = if def.has_value {
+                        if let Some(v) = value {
+                            self.trace_event(|| format!("token '{}' -> option '{}' = {:?}", arg_str, def.name, v));
+                            Some(v)
+                        } else if i + 1 < args.len() && self.takes_as_value(&args[i + 1]) {
+                            i += 1;
+                            self.trace_event(|| format!(
+                                "token '{}' -> option '{}' = {:?} (next argument)", arg_str, def.name, args[i]
+                            ));
+                            Some(args[i].clone())
+                        } else if let Some(default) = &def.default_value {
+                            self.trace_event(|| format!(
+                                "token '{}' -> option '{}' = {:?} (default)", arg_str, def.name, default
+                            ));
+                            defaulted.insert(def.name.clone());
+                            Some(default.clone())
+                        } else {
+                            return Err(ParseError::MissingValue(def.name.clone()).at_argument(origins[i]));
+                        }
+                    } else if def.has_optional_value && value.is_some() {
+                        self.trace_event(|| format!("token '{}' -> option '{}' = {:?}", arg_str, def.name, value));
+                        value
+                    } else {
+                        if value.is_some() {
+                            return Err(ParseError::ValidationError(ValidationError::new(
+                                format!("Option '{}' does not accept a value", def.name)
+                            )).at_argument(origins[i]));
+                        }
+                        if def.has_optional_value {
+                            defaulted.insert(def.name.clone());
+                        }
+                        self.trace_event(|| format!("token '{}' -> option '{}' set", arg_str, def.name));
+                        None
+                    };
             
-            // Validate if there's a validator and a value
-            if let (Some(validator), Some(val)) = (def.validator, &final_value) {
-                validator(val).map_err(ParseError::ValidationError)?;
-            }
+                    let final_value = read_value_from_file(def, final_value).map_err(|e| e.at_argument(origins[i]))?;
+            
+                    // Validate if there's a validator and a value
+                    if let (Some(validator), Some(val)) = (&def.validator, &final_value) {
+                        validator(val).map_err(|e| ParseError::ValidationError(e).at_argument(origins[i]))?;
+                    }
             
-            options.insert(def.name.clone(), final_value);
-        } else if arg_str.starts_with('-') && arg_str.len() > 1 {
-            // Short flag(s)
-            let flags = &arg_str[1..];
-            let flag_chars: Vec<char> = flags.chars().collect();
+                    record(&mut options, def, final_value);
+                } else if arg_str.starts_with('-') && arg_str != STDIN_ARGUMENT
+                    && !is_numeric_argument(&arg_str[1..], by_flag)
+                {
+                    // Short flag(s)
+                    let flags = &arg_str[1..];
+                    let flag_chars: Vec<char> = flags.chars().collect();
             
-            for (j, &flag_char) in flag_chars.iter().enumerate() {
-                let def = by_flag.get(&flag_char)
-                    .ok_or_else(|| ParseError::UnknownOption(format!("-{}", flag_char)))?;
+                    for (j, &flag_char) in flag_chars.iter().enumerate() {
+                        let def = by_flag.get(&flag_char)
+                            .ok_or_else(|| ParseError::UnknownOption(format!("-{}", flag_char)).at_argument(origins[i]))?;
                 
-                if options.contains_key(&def.name) {
-                    return Err(ParseError::DuplicateOption(def.name.clone()));
-                }
+                        if options.contains_key(&def.name) && !def.multiple && !def.counted {
+                            return Err(ParseError::DuplicateOption(def.name.clone()).at_argument(origins[i]));
+                        }
                 
-                let is_last_flag = j == flag_chars.len() - 1;
+                        let is_last_flag = j == flag_chars.len() - 1;
                 
-                if def.has_value {
-                    if !is_last_flag {
-                        return Err(ParseError::InvalidFlagCombination(
-                            format!("Flag '{}' requires a value but is not the last in combination '{}'", flag_char, flags)
-                        ));
-                    }
+                        if def.has_value {
+                            // The rest of the cluster is the value (`-mfout.txt`,
+                            // or `-f=out.txt` like the long form), unless it is
+                            // made up of flags (`-mfe`), which is more likely a
+                            // misplaced value flag than a value
+                            let attached: String = flag_chars[j + 1..].iter().collect();
+                            if !is_last_flag && attached.chars().all(|c| by_flag.contains_key(&c)) {
+                                return Err(ParseError::InvalidFlagCombination(format!(
+                                    "Flag '{}' requires a value but is followed by other flags in combination '{}'; \
+                                     put it last or give its value separately",
+                                    flag_char, flags
+                                )).at_argument(origins[i]));
+                            }
                     
-                    let final_value = if i + 1 < args.len() && !args[i + 1].to_string_lossy().starts_with('-') {
-                        i += 1;
-                        Some(args[i].clone())
-                    } else if let Some(default) = &def.default_value {
-                        Some(default.clone())
-                    } else {
-                        return Err(ParseError::MissingValue(def.name.clone()));
-                    };
+                            let final_value = if !is_last_flag {
+                                let attached = attached.strip_prefix('=').unwrap_or(&attached);
+                                if attached.is_empty() {
+                                    return Err(ParseError::MissingValue(def.name.clone()).at_argument(origins[i]));
+                                }
+                                self.trace_event(|| format!(
+                                    "token '{}' -> flag '{}' = {:?} (attached)", arg_str, def.name, attached
+                                ));
+                                Some(OsString::from(attached))
+                            } else if i + 1 < args.len() && self.takes_as_value(&args[i + 1]) {
+                                i += 1;
+                                self.trace_event(|| format!(
+                                    "token '{}' -> flag '{}' = {:?} (next argument)", arg_str, def.name, args[i]
+                                ));
+                                Some(args[i].clone())
+                            } else if let Some(default) = &def.default_value {
+                                self.trace_event(|| format!(
+                                    "token '{}' -> flag '{}' = {:?} (default)", arg_str, def.name, default
+                                ));
+                                defaulted.insert(def.name.clone());
+                                Some(default.clone())
+                            } else {
+                                return Err(ParseError::MissingValue(def.name.clone()).at_argument(origins[i]));
+                            };
                     
-                    // Validate if there's a validator
-                    if let (Some(validator), Some(val)) = (def.validator, &final_value) {
-                        validator(val).map_err(ParseError::ValidationError)?;
-                    }
+                            let final_value = read_value_from_file(def, final_value).map_err(|e| e.at_argument(origins[i]))?;
                     
-                    options.insert(def.name.clone(), final_value);
+                            // Validate if there's a validator
+                            if let (Some(validator), Some(val)) = (&def.validator, &final_value) {
+                                validator(val).map_err(|e| ParseError::ValidationError(e).at_argument(origins[i]))?;
+                            }
+                    
+                            record(&mut options, def, final_value);
+                            break;
+                        } else {
+                            // Optional values are never taken inside a cluster, so
+                            // these flags may appear anywhere in it, set bare
+                            if def.has_optional_value {
+                                defaulted.insert(def.name.clone());
+                            }
+                            self.trace_event(|| format!("token '{}' -> flag '{}' set", arg_str, def.name));
+                            record(&mut options, def, None);
+                        }
+                    }
+                } else if self.config.stop_at_first_argument {
+                    // Regular argument - collect all remaining as arguments
+                    self.trace_event(|| format!("token '{}' -> argument (end of options)", arg_str));
+                    arguments.extend_from_slice(&args[i..]);
+                    return Ok(TokenStep::Stop);
+                } else if let Some(literal) = arg_str.strip_prefix("@@") {
+                    // An escaped `@` rather than a response file
+                    self.trace_event(|| format!("token '{}' -> argument", arg_str));
+                    arguments.push(OsString::from(format!("@{}", literal)));
                 } else {
-                    options.insert(def.name.clone(), None);
+                    // Regular argument - keep looking for options after it
+                    self.trace_event(|| format!("token '{}' -> argument", arg_str));
+                    arguments.push(arg.clone());
                 }
+                Ok(TokenStep::Next)
+            };
+            match parse_token() {
+                Ok(TokenStep::Next) => {}
+                Ok(TokenStep::Reread) => continue,
+                Ok(TokenStep::Stop) => break,
+                Err(error) => errors.push(error),
             }
-        } else {
-            // Regular argument - collect all remaining as arguments
-            arguments.extend_from_slice(&args[i..]);
-            break;
+            i += 1;
+        }
+    
+        // Options left off the command line fall back to their environment
+        // variable; default_value still only applies when an option is given
+        // without a value
+        let mut from_env: HashSet<String> = HashSet::new();
+        for def in self.definitions {
+            let Some(var) = &def.env_var else { continue };
+            if options.contains_key(&def.name) || negated.contains(&def.name) {
+                continue;
+            }
+            let env_value = match self.env {
+                Some(env) => env(var),
+                None => std::env::var_os(var),
+            };
+            let Some(env_value) = env_value else { continue };
+            self.trace_event(|| format!("environment '{}' -> option '{}' = {:?}", var, def.name, env_value));
+            // Flags are set by the variable being present, whatever its value
+            let value = if def.has_value || def.has_optional_value {
+                match read_value_from_file(def, Some(env_value)) {
+                    Ok(value) => value,
+                    Err(error) => {
+                        errors.push(error);
+                        continue;
+                    }
+                }
+            } else {
+                None
+            };
+            if let (Some(validator), Some(val)) = (&def.validator, &value)
+                && let Err(error) = validator(val)
+            {
+                errors.push(ParseError::ValidationError(error));
+                continue;
+            }
+            from_env.insert(def.name.clone());
+            record(&mut options, def, value);
         }
         
-        i += 1;
-    }
+
+        // Only the last of `--name` and `--no-name` counts
+        negated.retain(|name| !options.contains_key(name));
+        counts.retain(|name, _| options.contains_key(name));
+        repeated.retain(|name, _| options.contains_key(name));
+        
+        // Options that print something and exit don't need the others
+        if !EXIT_EARLY_OPTIONS.iter().any(|name| options.contains_key(*name)) {
+            errors.extend(self.check_dependencies(&options));
+        }
     
-    // Apply default values only for options that were explicitly specified
-    // (Don't auto-add all options with defaults to the result)
-    // The defaults are already applied above when options are processed
+        let mut parsed = ParsedCommand {
+            executable_path,
+            options,
+            arguments,
+            repeated,
+            counts,
+            negated,
+            defaulted,
+            from_env,
+            from_config: HashSet::new(),
+        };
+        if errors.is_empty()
+            && let Err(error) = self.run_hooks(&mut parsed)
+        {
+            errors.push(error);
+        }
+        (parsed, errors)
+    }
     
-    Ok(ParsedCommand {
-        executable_path,
-        options,
-        arguments,
-    })
+    /// Runs the normalization hooks on `parsed` again, for commands changed
+    /// after parsing (such as by merging options from a config file)
+    pub fn run_hooks(&self, parsed: &mut ParsedCommand) -> Result<(), ParseError> {
+        self.hooks.iter().try_for_each(|hook| hook(parsed))
+    }
 }
 
-/// Generates formatted help text for the command
+/// Applies `@PATH` indirection for options defined with `value_from_file`:
+/// the value becomes the contents of PATH, minus one trailing newline. A
+/// leading `@@` escapes a literal `@`. Other options are returned unchanged.
+fn read_value_from_file(def: &OptionDefinition, value: Option<OsString>) -> Result<Option<OsString>, ParseError> {
+    let Some(value) = value else { return Ok(None) };
+    if !def.value_from_file {
+        return Ok(Some(value));
+    }
+    let Some(text) = value.to_str() else { return Ok(Some(value)) };
+    
+    if let Some(literal) = text.strip_prefix("@@") {
+        return Ok(Some(OsString::from(format!("@{}", literal))));
+    }
+    let Some(path) = text.strip_prefix('@') else { return Ok(Some(value)) };
+    
+    let mut contents = std::fs::read_to_string(path).map_err(|e| ParseError::ValidationError(ValidationError::new(
+        format!("Cannot read value for '{}' from '{}': {}", def.name, path, e)
+    )))?;
+    if contents.ends_with('\n') {
+        contents.pop();
+        if contents.ends_with('\r') {
+            contents.pop();
+        }
+    }
+    Ok(Some(OsString::from(contents)))
+}
+
+/// How many response files may be nested inside one another
+pub const MAX_RESPONSE_FILE_DEPTH: usize = 16;
+
+/// Returns the path named by an `@FILE` argument, or None for anything else
+/// (including the `@@` escape for a literal `@`)
+fn response_file_path(arg: &str) -> Option<&str> {
+    let path = arg.strip_prefix('@')?;
+    (!path.is_empty() && !path.starts_with('@')).then_some(path)
+}
+
+/// Reads the arguments in a response file: one per line, with blank lines
+/// skipped. Single or double quotes keep what is inside them as it is,
+/// including spaces at either end and newlines, and are removed.
+fn read_response_file(path: &str) -> Result<Vec<OsString>, ParseError> {
+    let contents = std::fs::read_to_string(path).map_err(|e| ParseError::ValidationError(ValidationError::new(
+        format!("Cannot read response file '{}': {}", path, e)
+    )))?;
+    
+    let mut arguments = Vec::new();
+    let mut current = String::new();
+    let mut quoted = false;
+    let mut quote = None;
+    for c in contents.chars() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                quoted = true;
+            }
+            (None, '\n') => {
+                if quoted || !current.trim_end_matches('\r').is_empty() {
+                    arguments.push(OsString::from(current.trim_end_matches('\r')));
+                }
+                current.clear();
+                quoted = false;
+            }
+            (None, c) => current.push(c),
+        }
+    }
+    if quote.is_some() {
+        return Err(ParseError::ValidationError(ValidationError::new(
+            format!("Unterminated quote in response file '{}'", path)
+        )));
+    }
+    if quoted || !current.is_empty() {
+        arguments.push(OsString::from(current));
+    }
+    Ok(arguments)
+}
+
+/// Exit status when an input file does not exist (sysexits `EX_NOINPUT`)
+pub const EXIT_NO_INPUT: i32 = 66;
+/// Exit status when an input file cannot be read for lack of permission
+/// (sysexits `EX_NOPERM`)
+pub const EXIT_PERMISSION_DENIED: i32 = 77;
+
+/// Describes a failure to open or inspect an input file, returning the
+/// message and the exit status to use. Missing files and permission problems
+/// get their own wording and status; anything else exits with 1.
+pub fn input_error(path: &Path, error: &std::io::Error) -> (String, i32) {
+    match error.kind() {
+        std::io::ErrorKind::PermissionDenied => {
+            (format!("Permission denied reading {}", path.display()), EXIT_PERMISSION_DENIED)
+        }
+        std::io::ErrorKind::NotFound => {
+            (format!("No such input file {}", path.display()), EXIT_NO_INPUT)
+        }
+        _ => (format!("Error opening input file {}: {}", path.display(), error), 1),
+    }
+}
+
+/// Where the tools send their warnings: printed on stderr as `Warning: ...`,
+/// or with `--abort-on-warning` returned as an error for the caller to fail
+/// with instead
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Warnings {
+    pub abort: bool,
+}
+
+impl Warnings {
+    /// Reports `message`, failing with it instead when warnings are fatal
+    pub fn warn(&self, message: impl fmt::Display) -> Result<(), String> {
+        if self.abort {
+            return Err(format!("{} (warning treated as an error by --abort-on-warning)", message));
+        }
+        eprintln!("Warning: {}", message);
+        Ok(())
+    }
+}
+
+/// Gives a decoded output file the permissions from its header. Setuid,
+/// setgid and sticky bits are never restored, and elsewhere than Unix the
+/// mode is ignored.
+pub fn set_output_mode(file: &std::fs::File, mode: u32) -> std::io::Result<()> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(std::fs::Permissions::from_mode(mode & 0o777))
+    }
+    #[cfg(not(unix))]
+    {
+        let _ = (file, mode);
+        Ok(())
+    }
+}
+
+/// Handles the result of [`set_output_mode`] for `path`: a failure is an
+/// error, or only a warning when `ignore` (`--ignore-chmod`) is set
+pub fn check_chmod(result: std::io::Result<()>, path: &Path, mode: u32, ignore: bool, warnings: Warnings) -> Result<(), String> {
+    let Err(e) = result else {
+        return Ok(());
+    };
+    let message = format!("{}: cannot set mode {:o}: {}", path.display(), mode & 0o777, e);
+    if ignore {
+        warnings.warn(message)
+    } else {
+        Err(message)
+    }
+}
+
+/// Removes a `--trace` token (appearing before any `--`) from the argument
+/// list, returning whether it was present. `--trace` is handled before
+/// parsing so that the parse itself can be traced, and stays out of `--help`.
+pub fn take_trace_flag(args: &mut Vec<OsString>) -> bool {
+    let end = args.iter().position(|a| a == "--").unwrap_or(args.len());
+    match args[..end].iter().position(|a| a == "--trace") {
+        Some(pos) => {
+            args.remove(pos);
+            true
+        }
+        None => false,
+    }
+}
+
+/// Returns true if a `-` prefixed token such as `-5` or `-3.14` should be treated
+/// as a positional number rather than a cluster of short flags. Defined digit
+/// flags always win, so `-1` is only positional when no `1` flag exists.
+fn is_numeric_argument(flags: &str, by_flag: &HashMap<char, &OptionDefinition>) -> bool {
+    let looks_numeric = flags.chars().any(|c| c.is_ascii_digit())
+        && flags.chars().all(|c| c.is_ascii_digit() || c == '.')
+        && flags.matches('.').count() <= 1;
+
+    looks_numeric && !flags.chars().any(|c| by_flag.contains_key(&c))
+}
+
+/// Layout of the options section produced by `generate_help_with_config`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelpConfig {
+    /// Heading printed above the option list
+    pub heading: String,
+    /// Prefix printed before each option line
+    pub indent: String,
+}
+
+impl Default for HelpConfig {
+    fn default() -> Self {
+        Self { heading: "Options:".to_string(), indent: "  ".to_string() }
+    }
+}
+
+/// Generates formatted help text for the command
 pub fn generate_help(
     command_name: &str,
     description: &str,
     usage_pattern: &str,
     option_definitions: &[OptionDefinition]
 ) -> String {
+    generate_help_with_config(command_name, description, usage_pattern, option_definitions, &HelpConfig::default())
+}
+
+/// Generates formatted help text using a custom options heading and indent
+pub fn generate_help_with_config(
+    command_name: &str,
+    description: &str,
+    usage_pattern: &str,
+    option_definitions: &[OptionDefinition],
+    config: &HelpConfig,
+) -> String {
+    let model = help_model(command_name, description, usage_pattern, option_definitions);
     let mut help = String::new();
-    help.push_str(&format!("Usage: {} {}\n\n", command_name, usage_pattern));
-    help.push_str(&format!("{}\n\n", description));
-    help.push_str("Options:\n");
+    help.push_str(&format!("Usage: {} {}\n\n", model.command_name, model.usage));
+    help.push_str(&format!("{}\n\n", model.description));
+    help.push_str(&format!("{}\n", config.heading));
     
-    for def in option_definitions {
-        let short_flag = format!("-{}", def.flag);
-        let long_flag = format!("--{}", def.name);
-        let flags = format!("{}, {}", short_flag, long_flag);
-        help.push_str(&format!("  {:<20} {}\n", flags, def.help_text));
+    // Ungrouped options come first, then each group under a single heading,
+    // in the order the groups first appear
+    let visible: Vec<&HelpOption> = model.options.iter().filter(|option| !option.hidden).collect();
+    let mut groups: Vec<Option<&str>> = vec![None];
+    for option in &visible {
+        if !groups.contains(&option.group.as_deref()) {
+            groups.push(option.group.as_deref());
+        }
+    }
+    for group in groups {
+        if let Some(heading) = group {
+            help.push_str(&format!("\n{}:\n", heading));
+        }
+        for option in visible.iter().filter(|option| option.group.as_deref() == group) {
+            let negation = if option.negatable { "[no-]" } else { "" };
+            let long_flag = format!("--{}{}", negation, option.long_name);
+            let flags = match option.short_flag {
+                Some(flag) => format!("-{}, {}", flag, long_flag),
+                None => format!("    {}", long_flag),
+            };
+            help.push_str(&format!("{}{:<20} {}\n", config.indent, flags, option.help_text));
+        }
     }
     
     help
 }
 
+/// Help for a command as data, for renderers other than the text help (such
+/// as man pages or shell completions)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelpModel {
+    pub command_name: String,
+    pub description: String,
+    pub usage: String,
+    /// Every option, in definition order, including hidden ones
+    pub options: Vec<HelpOption>,
+}
+
+/// One option as described by a `HelpModel`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HelpOption {
+    /// None for long-only options
+    pub short_flag: Option<char>,
+    pub long_name: String,
+    /// Placeholder for the option's value (`VALUE`), None if it takes none
+    pub value_name: Option<String>,
+    /// The value may be left out (`--name[=VALUE]`)
+    pub value_optional: bool,
+    pub default_value: Option<OsString>,
+    pub help_text: String,
+    pub group: Option<String>,
+    /// Renderers should leave the option out
+    pub hidden: bool,
+    /// May also be given as `--no-NAME`
+    pub negatable: bool,
+    pub value_hint: ValueHint,
+}
+
+/// Describes a command and its options as a `HelpModel`, the source the text
+/// help (`generate_help`) is rendered from
+pub fn help_model(
+    command_name: &str,
+    description: &str,
+    usage_pattern: &str,
+    option_definitions: &[OptionDefinition],
+) -> HelpModel {
+    let options = option_definitions.iter()
+        .map(|def| HelpOption {
+            short_flag: (def.flag != NO_SHORT_FLAG).then_some(def.flag),
+            long_name: def.name.clone(),
+            value_name: (def.has_value || def.has_optional_value).then(|| "VALUE".to_string()),
+            value_optional: def.has_optional_value,
+            default_value: def.default_value.clone(),
+            help_text: def.help_text.clone(),
+            group: def.group.clone(),
+            hidden: def.hidden,
+            negatable: def.negatable,
+            value_hint: def.value_hint,
+        })
+        .collect();
+    HelpModel {
+        command_name: command_name.to_string(),
+        description: description.to_string(),
+        usage: usage_pattern.to_string(),
+        options,
+    }
+}
+
+/// Splits an option value on a byte delimiter (such as `b','`) without a
+/// lossy conversion, so non-UTF-8 parts survive intact. On Unix the raw bytes
+/// are split; elsewhere the value is split as (lossily converted) text.
+pub fn split_os(value: &OsStr, sep: u8) -> Vec<OsString> {
+    #[cfg(unix)]
+    {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        value.as_bytes()
+            .split(|&b| b == sep)
+            .map(|part| OsString::from_vec(part.to_vec()))
+            .collect()
+    }
+    #[cfg(not(unix))]
+    {
+        value.to_string_lossy()
+            .split(sep as char)
+            .map(OsString::from)
+            .collect()
+    }
+}
+
+/// Exit status for command line usage errors
+pub const EXIT_USAGE: i32 = 2;
+
+/// Why help is being shown. Requested help is the command's normal output;
+/// help shown because of a usage error is a diagnostic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HelpReason {
+    /// `--help` was given: print to stdout and exit 0
+    Requested,
+    /// The command line could not be parsed: print to stderr and exit 2
+    UsageError,
+}
+
+impl HelpReason {
+    /// Returns the exit status to use after showing help for this reason
+    pub fn exit_code(self) -> i32 {
+        match self {
+            HelpReason::Requested => 0,
+            HelpReason::UsageError => EXIT_USAGE,
+        }
+    }
+}
+
+/// Writes help text to `output`
+pub fn write_help<W: std::io::Write>(output: &mut W, help_text: &str) -> std::io::Result<()> {
+    writeln!(output, "{}", help_text)
+}
+
+/// Prints help to stdout or stderr as `reason` requires and returns the exit
+/// status the command should end with
+pub fn emit_help(help_text: &str, reason: HelpReason) -> i32 {
+    // Nothing useful can be done if the help itself cannot be written
+    let _ = match reason {
+        HelpReason::Requested => write_help(&mut std::io::stdout().lock(), help_text),
+        HelpReason::UsageError => write_help(&mut std::io::stderr().lock(), help_text),
+    };
+    reason.exit_code()
+}
+
 // Common validators
 pub fn validate_existing_file(value: &OsStr) -> Result<(), ValidationError> {
     let path = Path::new(value);
@@ -283,6 +1552,73 @@ pub fn validate_existing_file(value: &OsStr) -> Result<(), ValidationError> {
     }
 }
 
+/// Validates that an input file can actually be opened for reading, so a
+/// permission problem is reported while parsing rather than mid-encode
+pub fn validate_readable_file(value: &OsStr) -> Result<(), ValidationError> {
+    let path = Path::new(value);
+    if path.is_dir() {
+        return Err(ValidationError::new(format!("Is a directory, not a file: {}", path.display())));
+    }
+    std::fs::File::open(path)
+        .map(drop)
+        .map_err(|e| ValidationError::new(format!("Cannot read {}: {}", path.display(), e)))
+}
+
+/// Validates that an output path can be written without creating it: either
+/// the file exists and is writable, or its parent directory exists and is
+/// writable. Elsewhere than Unix only the read-only flag and the parent's
+/// existence are checked.
+pub fn validate_writable_path(value: &OsStr) -> Result<(), ValidationError> {
+    validate_file_path(value)?;
+    let path = Path::new(value);
+    if let Ok(metadata) = std::fs::metadata(path) {
+        if metadata.is_dir() {
+            return Err(ValidationError::new(format!("Is a directory: {}", path.display())));
+        }
+        if !is_writable(path, &metadata) {
+            return Err(ValidationError::new(format!("Permission denied: {}", path.display())));
+        }
+        return Ok(());
+    }
+    let parent = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    match std::fs::metadata(parent) {
+        Ok(metadata) if metadata.is_dir() => {
+            if is_writable(parent, &metadata) {
+                Ok(())
+            } else {
+                Err(ValidationError::new(format!(
+                    "Permission denied: cannot create {} in {}", path.display(), parent.display()
+                )))
+            }
+        }
+        _ => Err(ValidationError::new(format!("Parent directory does not exist: {}", parent.display()))),
+    }
+}
+
+/// Whether the process may write to `path`, asking the OS with access(2)
+#[cfg(unix)]
+fn is_writable(path: &Path, _metadata: &std::fs::Metadata) -> bool {
+    use std::ffi::{c_char, c_int, CString};
+    use std::os::unix::ffi::OsStrExt;
+    unsafe extern "C" {
+        fn access(path: *const c_char, mode: c_int) -> c_int;
+    }
+    const W_OK: c_int = 2;
+    let Ok(path) = CString::new(path.as_os_str().as_bytes()) else {
+        return false;
+    };
+    // SAFETY: `path` is a NUL-terminated string that outlives the call
+    unsafe { access(path.as_ptr(), W_OK) == 0 }
+}
+
+#[cfg(not(unix))]
+fn is_writable(_path: &Path, metadata: &std::fs::Metadata) -> bool {
+    !metadata.permissions().readonly()
+}
+
 pub fn validate_positive_integer(value: &OsStr) -> Result<(), ValidationError> {
     let s = value.to_str()
         .ok_or_else(|| ValidationError::new("Invalid UTF-8 in number".to_string()))?;
@@ -294,7 +1630,72 @@ pub fn validate_positive_integer(value: &OsStr) -> Result<(), ValidationError> {
     Ok(())
 }
 
-/// Handles version option output consistently across all commands
+/// Validates that a value is a non-negative integer (0 allowed)
+pub fn validate_non_negative_integer(value: &OsStr) -> Result<(), ValidationError> {
+    let s = value.to_str()
+        .ok_or_else(|| ValidationError::new("Invalid UTF-8 in number".to_string()))?;
+    s.parse::<u32>()
+        .map(|_| ())
+        .map_err(|_| ValidationError::new("Not a valid non-negative integer".to_string()))
+}
+
+/// Returns a validator accepting integers from `min` to `max` inclusive, e.g.
+/// `validate_integer_range(1, 64)` for a line length
+pub fn validate_integer_range(min: i64, max: i64) -> OptionValidator {
+    Box::new(move |value: &OsStr| {
+        let s = value.to_str()
+            .ok_or_else(|| ValidationError::new("Invalid UTF-8 in number".to_string()))?;
+        let n: i64 = s.parse()
+            .map_err(|_| ValidationError::new("Not a valid integer".to_string()))?;
+        if n < min || n > max {
+            return Err(ValidationError::new(format!("Value must be between {} and {}", min, max)));
+        }
+        Ok(())
+    })
+}
+
+/// Returns a validator accepting only one of `choices`, compared exactly
+/// (case-sensitive), e.g. `validate_choice(&["base64", "uu", "xx"])`
+pub fn validate_choice(choices: &'static [&'static str]) -> OptionValidator {
+    Box::new(move |value: &OsStr| {
+        match value.to_str() {
+            Some(s) if choices.contains(&s) => Ok(()),
+            _ => Err(ValidationError::new(format!(
+                "Invalid value '{}' (expected one of {})", value.to_string_lossy(), choices.join(", ")
+            ))),
+        }
+    })
+}
+
+/// Combines validators into one that requires every one of them to pass,
+/// stopping at (and reporting) the first failure
+pub fn all_of<V>(validators: Vec<V>) -> OptionValidator
+where
+    V: Fn(&OsStr) -> Result<(), ValidationError> + 'static,
+{
+    Box::new(move |value: &OsStr| validators.iter().try_for_each(|validator| validator(value)))
+}
+
+/// Combines validators into one that passes when at least one of them does,
+/// reporting every failure when none do
+pub fn any_of<V>(validators: Vec<V>) -> OptionValidator
+where
+    V: Fn(&OsStr) -> Result<(), ValidationError> + 'static,
+{
+    Box::new(move |value: &OsStr| {
+        let mut messages = Vec::new();
+        for validator in &validators {
+            match validator(value) {
+                Ok(()) => return Ok(()),
+                Err(e) => messages.push(e.to_string()),
+            }
+        }
+        Err(ValidationError::new(format!("No accepted form matched: {}", messages.join("; "))))
+    })
+}
+
+/// Handles version option output consistently across all commands. A bare
+/// `--version` (no MODE) prints the default copyright mode.
 pub fn handle_version_output(version_mode: Option<&std::ffi::OsStr>, command_name: &str) {
     let mode = version_mode
         .map(|v| v.to_string_lossy())
@@ -343,6 +1744,14 @@ pub fn handle_version_output(version_mode: Option<&std::ffi::OsStr>, command_nam
     }
 }
 
+/// Full text of the license every command is distributed under (`--license`)
+pub const LICENSE_TEXT: &str = include_str!("../LICENSE");
+
+/// Prints the full license text for `--license`
+pub fn handle_license_output() {
+    print!("{}", LICENSE_TEXT);
+}
+
 /// Handles more-help option consistently across all commands
 pub fn handle_more_help(command_name: &str, description: &str, usage: &str, options: &[OptionDefinition]) {
     let help_text = generate_help(command_name, description, usage, options);
@@ -369,33 +1778,37 @@ pub fn handle_more_help(command_name: &str, description: &str, usage: &str, opti
     }
 }
 
-/// Debug print the parsed command structure consistently
-pub fn debug_print_parsed_command(parsed: &ParsedCommand) {
-    eprintln!("DEBUG: Parsed Command Structure:");
-    eprintln!("  Executable: {:?}", parsed.executable_path);
-    eprintln!("  Options:");
-    for (name, value) in &parsed.options {
-        match value {
-            Some(v) => eprintln!("    --{} = {:?}", name, v),
-            None => eprintln!("    --{}", name),
+/// Formats the parsed command structure for the debug dump, listing every
+/// defined option (in definition order) with where its value came from
+pub fn format_parsed_command(parsed: &ParsedCommand, option_definitions: &[OptionDefinition]) -> String {
+    let mut out = String::from("DEBUG: Parsed Command Structure:\n");
+    out.push_str(&format!("  Executable: {:?}\n", parsed.executable_path));
+    out.push_str("  Options:\n");
+    for def in option_definitions {
+        let provenance = parsed.value_provenance(&def.name);
+        match parsed.options.get(&def.name) {
+            Some(Some(v)) => out.push_str(&format!("    --{} = {:?} ({})\n", def.name, v, provenance)),
+            _ => out.push_str(&format!("    --{} ({})\n", def.name, provenance)),
         }
     }
-    eprintln!("  Arguments:");
+    out.push_str("  Arguments:\n");
     for (i, arg) in parsed.arguments.iter().enumerate() {
-        eprintln!("    [{}]: {:?}", i, arg);
+        out.push_str(&format!("    [{}]: {:?}\n", i, arg));
     }
-    eprintln!();
+    out
+}
+
+/// Debug print the parsed command structure consistently
+pub fn debug_print_parsed_command(parsed: &ParsedCommand, option_definitions: &[OptionDefinition]) {
+    eprintln!("{}", format_parsed_command(parsed, option_definitions));
 }
 
-/// Print config file options if specified
+/// Print config file options if specified (--load-opts files are applied
+/// through `EffectiveConfig`)
 pub fn print_config_file_options(parsed: &ParsedCommand) {
     if let Some(save_file) = parsed.option_value("save-opts") {
         println!("Note: Would save current options to: {:?}", save_file);
     }
-    
-    if let Some(load_file) = parsed.option_value("load-opts") {
-        println!("Note: Would load options from: {:?}", load_file);
-    }
 }
 
 // Uuencoding functionality
@@ -502,7 +1915,7 @@ pub fn base64_encode_block(input: &[u8], output: &mut [u8]) -> usize {
 /// Encode filename using base64 (for --encode-file-name option)
 pub fn base64_encode_filename(filename: &str) -> String {
     let input_bytes = filename.as_bytes();
-    let output_len = ((input_bytes.len() + 2) / 3) * 4; // BASE64_LENGTH macro equivalent
+    let output_len = input_bytes.len().div_ceil(3) * 4; // BASE64_LENGTH macro equivalent
     let mut output = vec![0u8; output_len];
     
     let written = base64_encode_block(input_bytes, &mut output);
@@ -520,30 +1933,132 @@ pub fn encode<R: std::io::Read, W: std::io::Write>(
     input: &mut R,
     output: &mut W,
     use_base64: bool,
-) -> std::io::Result<()> {
-    let mut finishing = false;
+) -> Result<(), EncodeError> {
+    encode_with_checkpoints(input, output, use_base64, Checkpoint::default(), 0, |_| Ok(()))?;
+    Ok(())
+}
+
+/// Errors that can occur while encoding, identifying which side failed
+#[derive(Debug)]
+pub enum EncodeError {
+    /// Reading from the input failed
+    ReadError(std::io::Error),
+    /// Writing to the output failed
+    WriteError(std::io::Error),
+    /// Recording a checkpoint failed
+    CheckpointError(std::io::Error),
+}
+
+impl fmt::Display for EncodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EncodeError::ReadError(e) => write!(f, "failed to read input: {}", e),
+            EncodeError::WriteError(e) => write!(f, "failed to write output: {}", e),
+            EncodeError::CheckpointError(e) => write!(f, "failed to record checkpoint: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for EncodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            EncodeError::ReadError(e) | EncodeError::WriteError(e) | EncodeError::CheckpointError(e) => Some(e),
+        }
+    }
+}
+
+/// Position reached by an encode, recorded by `--checkpoint` so that an
+/// interrupted encode can be resumed. Offsets always fall on a line boundary.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Checkpoint {
+    /// Number of input bytes already encoded
+    pub input_offset: u64,
+    /// Number of output bytes already written (including the header)
+    pub output_offset: u64,
+}
+
+impl Checkpoint {
+    /// Reads a checkpoint file written by [`Checkpoint::save`]
+    pub fn load(path: &Path) -> std::io::Result<Self> {
+        let text = std::fs::read_to_string(path)?;
+        let invalid = || std::io::Error::new(
+            std::io::ErrorKind::InvalidData,
+            format!("invalid checkpoint file: {}", path.display()),
+        );
+        let mut fields = text.split_whitespace().map(|f| f.parse::<u64>());
+        match (fields.next(), fields.next(), fields.next()) {
+            (Some(Ok(input_offset)), Some(Ok(output_offset)), None) => {
+                Ok(Self { input_offset, output_offset })
+            }
+            _ => Err(invalid()),
+        }
+    }
+
+    /// Writes the checkpoint, replacing the file atomically so that an
+    /// interruption never leaves a half-written checkpoint behind
+    pub fn save(&self, path: &Path) -> std::io::Result<()> {
+        let mut tmp = path.as_os_str().to_owned();
+        tmp.push(".tmp");
+        std::fs::write(&tmp, format!("{} {}\n", self.input_offset, self.output_offset))?;
+        std::fs::rename(&tmp, path)
+    }
+}
+
+/// Fills `buf` from `input`, retrying short reads, and returns the number
+/// of bytes read (less than `buf.len()` only at EOF)
+fn read_full<R: std::io::Read>(input: &mut R, buf: &mut [u8]) -> std::io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match input.read(&mut buf[filled..]) {
+            Ok(0) => break,
+            Ok(n) => filled += n,
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+/// Encoding loop behind [`encode`] that continues from `start` and reports
+/// progress every `interval` lines (0 disables checkpoints). The output is
+/// flushed before `on_checkpoint` is called, so the recorded offsets never
+/// run ahead of what has been written. Returns the final position.
+pub fn encode_with_checkpoints<R, W, F>(
+    input: &mut R,
+    output: &mut W,
+    use_base64: bool,
+    start: Checkpoint,
+    interval: u64,
+    mut on_checkpoint: F,
+) -> Result<Checkpoint, EncodeError>
+where
+    R: std::io::Read,
+    W: std::io::Write,
+    F: FnMut(Checkpoint) -> std::io::Result<()>,
+{
+    let mut position = start;
+    let mut lines: u64 = 0;
 
     loop {
         let mut buf = [0u8; 45];
         let mut buf_out = [0u8; 64];
         
         // Read up to 45 bytes from input
-        let rdct = match input.read(&mut buf) {
-            Ok(0) => break, // EOF
-            Ok(n) => n,
-            Err(e) => return Err(e),
-        };
+        let rdct = read_full(input, &mut buf).map_err(EncodeError::ReadError)?;
+        if rdct == 0 {
+            break; // EOF
+        }
 
         // Check if this is the last chunk
-        if rdct < 45 {
-            finishing = true;
-        }
+        let finishing = rdct < 45;
 
+        let mut line_len = 0;
         let wrct = if !use_base64 {
             // Traditional uuencoding
             // First character is the encoded count
             let count_char = enc(rdct as u8);
-            output.write_all(&[count_char])?;
+            output.write_all(&[count_char]).map_err(EncodeError::WriteError)?;
+            line_len += 1;
             
             // Encode the data block
             uuencode_block(&buf[..rdct], &mut buf_out)
@@ -553,68 +2068,411 @@ pub fn encode<R: std::io::Read, W: std::io::Write>(
         };
 
         // Write encoded data
-        output.write_all(&buf_out[..wrct])?;
+        output.write_all(&buf_out[..wrct]).map_err(EncodeError::WriteError)?;
         
         // Write newline
-        output.write_all(b"\n")?;
+        output.write_all(b"\n").map_err(EncodeError::WriteError)?;
+        line_len += wrct + 1;
+
+        position.input_offset += rdct as u64;
+        position.output_offset += line_len as u64;
+        lines += 1;
 
         if finishing {
             break;
         }
+
+        if interval > 0 && lines.is_multiple_of(interval) {
+            output.flush().map_err(EncodeError::WriteError)?;
+            on_checkpoint(position).map_err(EncodeError::CheckpointError)?;
+        }
     }
 
-    Ok(())
+    Ok(position)
 }
 
-/// Generate the header line for uuencoded output
-/// Format: "begin[-base64][-encoded] mode filename\n"
-pub fn write_uuencode_header<W: std::io::Write>(
-    output: &mut W,
-    mode: u32,
-    filename: &str,
-    use_base64: bool,
-    encode_filename: bool,
-) -> std::io::Result<()> {
-    let mut header = String::from("begin");
-    
-    if use_base64 {
-        header.push_str("-base64");
+/// Base64-encodes `input` with a line break every `wrap` output columns
+/// (0 writes all data on one line), independent of the 45-byte input lines
+/// used by [`encode`]. Returns the number of input bytes encoded.
+pub fn encode_base64_wrapped<R, W>(input: &mut R, output: &mut W, wrap: usize) -> Result<u64, EncodeError>
+where
+    R: std::io::Read,
+    W: std::io::Write,
+{
+    let mut buf = [0u8; 3 * 1024];
+    let mut buf_out = [0u8; 4 * 1024];
+    let mut total = 0u64;
+    let mut column = 0;
+
+    loop {
+        let rdct = read_full(input, &mut buf).map_err(EncodeError::ReadError)?;
+        if rdct == 0 {
+            break;
+        }
+        total += rdct as u64;
+        let wrct = base64_encode_block(&buf[..rdct], &mut buf_out);
+
+        let mut encoded = &buf_out[..wrct];
+        while !encoded.is_empty() {
+            let take = if wrap == 0 { encoded.len() } else { (wrap - column).min(encoded.len()) };
+            output.write_all(&encoded[..take]).map_err(EncodeError::WriteError)?;
+            column += take;
+            encoded = &encoded[take..];
+            if wrap != 0 && column == wrap {
+                output.write_all(b"\n").map_err(EncodeError::WriteError)?;
+                column = 0;
+            }
+        }
+
+        if rdct < buf.len() {
+            break;
+        }
     }
-    
-    if encode_filename {
-        header.push_str("-encoded");
+
+    if column > 0 {
+        output.write_all(b"\n").map_err(EncodeError::WriteError)?;
     }
-    
-    let display_filename = if encode_filename {
-        base64_encode_filename(filename)
-    } else {
-        filename.to_string()
-    };
-    
-    writeln!(output, "{} {:o} {}", header, mode, display_filename)?;
-    Ok(())
+    Ok(total)
 }
 
-/// Generate the trailer line for uuencoded output  
-/// Traditional uuencoding: "end\n"
-/// Base64 encoding: "====\n"
-pub fn write_uuencode_trailer<W: std::io::Write>(
-    output: &mut W,
-    use_base64: bool,
-) -> std::io::Result<()> {
-    if use_base64 {
-        writeln!(output, "====")?;
-    } else {
-        // Traditional uuencoding writes a zero-length line first
-        let zero_char = enc(0);
-        writeln!(output, "{}", zero_char as char)?;
-        writeln!(output, "end")?;
-    }
-    Ok(())
+/// Encodes `data` in memory as a complete block (header, body and trailer)
+pub fn encode_to_bytes(data: &[u8], name: &str, mode: u32, use_base64: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    // Writing to a Vec cannot fail
+    write_uuencode_header(&mut out, mode, name, use_base64, false)
+        .and_then(|_| encode(&mut &data[..], &mut out, use_base64).map_err(std::io::Error::other))
+        .and_then(|_| write_uuencode_trailer(&mut out, use_base64))
+        .expect("in-memory encode failed");
+    out
 }
-pub fn validate_version_mode(value: &OsStr) -> Result<(), ValidationError> {
-    let s = value.to_str()
-        .ok_or_else(|| ValidationError::new("Invalid UTF-8 in version mode".to_string()))?;
+
+/// Name written in the header by [`encode_to_string`]
+pub const ENCODE_TO_STRING_NAME: &str = "data";
+
+/// Encodes `input` in memory as a complete block (named
+/// [`ENCODE_TO_STRING_NAME`], mode 644) for embedding in text such as logs
+/// or JSON. Encoded output is always ASCII.
+pub fn encode_to_string(input: &[u8], use_base64: bool) -> String {
+    let encoded = encode_to_bytes(input, ENCODE_TO_STRING_NAME, 0o644, use_base64);
+    assert!(encoded.is_ascii(), "encoder produced non-ASCII output");
+    // ASCII is valid UTF-8
+    String::from_utf8(encoded).unwrap()
+}
+
+/// Decodes the first block found in `encoded`, returning its header and data
+pub fn decode_bytes(encoded: &[u8]) -> Result<(DecodedHeader, Vec<u8>), DecodeError> {
+    let mut data = Vec::new();
+    match decode_block(&mut &encoded[..], &mut data, &DecodeOptions::default())? {
+        Some(header) => Ok((header, data)),
+        None => Err(DecodeError::NoBeginLine),
+    }
+}
+
+/// Runs an in-process encode/decode roundtrip over fixed buffers in both
+/// encodings, so a deployed binary can verify its codec (`--self-test`)
+pub fn run_self_test() -> Result<(), String> {
+    let all_bytes: Vec<u8> = (0..=255u8).collect();
+    // Lengths around the 3-byte group and 45-byte line boundaries
+    let lengths = [0, 1, 2, 3, 4, 5, 44, 45, 46, 90, 256];
+
+    for use_base64 in [false, true] {
+        let encoding = if use_base64 { "base64" } else { "uuencode" };
+        for &len in &lengths {
+            let data: Vec<u8> = all_bytes.iter().copied().cycle().take(len).collect();
+            let encoded = encode_to_bytes(&data, "self-test", 0o644, use_base64);
+            let (header, decoded) = decode_bytes(&encoded)
+                .map_err(|e| format!("{} roundtrip of {} bytes failed: {}", encoding, len, e))?;
+            if header.name != "self-test" || header.mode != 0o644 || decoded != data {
+                return Err(format!("{} roundtrip of {} bytes produced different data", encoding, len));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Position of a block within a multi-part transmission (`--part`/`--total`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartNumber {
+    pub part: u32,
+    pub total: u32,
+}
+
+impl PartNumber {
+    /// Creates a part number, checking that `1 <= part <= total`
+    pub fn new(part: u32, total: u32) -> Result<Self, ValidationError> {
+        if part == 0 || total == 0 {
+            return Err(ValidationError::new("Part and total must be greater than zero".to_string()));
+        }
+        if part > total {
+            return Err(ValidationError::new(format!("Part {} is greater than total {}", part, total)));
+        }
+        Ok(Self { part, total })
+    }
+
+    /// Parses a `# part N of M` comment line, returning None for any other line
+    pub fn parse_comment(line: &str) -> Option<Self> {
+        let rest = line.trim().strip_prefix("# part ")?;
+        let (part, total) = rest.split_once(" of ")?;
+        Self::new(part.trim().parse().ok()?, total.trim().parse().ok()?).ok()
+    }
+}
+
+/// Write the `# part N of M` comment that precedes a numbered block
+pub fn write_part_comment<W: std::io::Write>(output: &mut W, part: PartNumber) -> std::io::Result<()> {
+    writeln!(output, "# part {} of {}", part.part, part.total)
+}
+
+/// Write the `# mtime: SECONDS` comment that carries the source file's
+/// modification time (seconds since the Unix epoch) for `--preserve-time`
+pub fn write_mtime_comment<W: std::io::Write>(output: &mut W, seconds: u64) -> std::io::Result<()> {
+    writeln!(output, "# mtime: {}", seconds)
+}
+
+/// Parses a `# mtime: SECONDS` comment line, returning None for any other line
+pub fn parse_mtime_comment(line: &str) -> Option<u64> {
+    line.trim().strip_prefix("# mtime:")?.trim().parse().ok()
+}
+
+/// Checks that numbered parts arrive in order (1, 2, ... M) during reassembly
+#[derive(Debug, Default)]
+pub struct PartSequence {
+    last: Option<PartNumber>,
+}
+
+impl PartSequence {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the next part, failing if it isn't the one expected
+    pub fn check(&mut self, part: PartNumber) -> Result<(), String> {
+        let expected = match self.last {
+            Some(last) if last.part < last.total => {
+                if part.total != last.total {
+                    return Err(format!(
+                        "part {} of {} does not match the earlier total of {}",
+                        part.part, part.total, last.total
+                    ));
+                }
+                last.part + 1
+            }
+            _ => 1,
+        };
+        if part.part != expected {
+            return Err(format!(
+                "part {} of {} arrived out of order (expected part {})",
+                part.part, part.total, expected
+            ));
+        }
+        self.last = Some(part);
+        Ok(())
+    }
+
+    /// Returns true if a sequence was started but its last part never arrived
+    pub fn is_incomplete(&self) -> bool {
+        self.last.is_some_and(|last| last.part < last.total)
+    }
+}
+
+/// Generate the header line for uuencoded output
+/// Format: "begin[-base64][-encoded] mode filename\n"
+pub fn write_uuencode_header<W: std::io::Write>(
+    output: &mut W,
+    mode: u32,
+    filename: &str,
+    use_base64: bool,
+    encode_filename: bool,
+) -> std::io::Result<()> {
+    let mut header = String::from("begin");
+    
+    if use_base64 {
+        header.push_str("-base64");
+    }
+    
+    if encode_filename {
+        header.push_str("-encoded");
+    }
+    
+    let display_filename = if encode_filename {
+        base64_encode_filename(filename)
+    } else {
+        filename.to_string()
+    };
+    
+    writeln!(output, "{} {:o} {}", header, mode, display_filename)?;
+    Ok(())
+}
+
+/// Generate the trailer line for uuencoded output  
+/// Traditional uuencoding: "end\n"
+/// Base64 encoding: "====\n"
+pub fn write_uuencode_trailer<W: std::io::Write>(
+    output: &mut W,
+    use_base64: bool,
+) -> std::io::Result<()> {
+    if use_base64 {
+        writeln!(output, "====")?;
+    } else {
+        // Traditional uuencoding writes a zero-length line first
+        let zero_char = enc(0);
+        writeln!(output, "{}", zero_char as char)?;
+        writeln!(output, "end")?;
+    }
+    Ok(())
+}
+
+/// Line writer that prefixes every output line with a fixed indent, so that
+/// encoded blocks can be embedded inside indented text (see `--indent`)
+pub struct IndentWriter<W: std::io::Write> {
+    inner: W,
+    indent: Vec<u8>,
+    at_line_start: bool,
+}
+
+impl<W: std::io::Write> IndentWriter<W> {
+    /// Wraps `inner`, prefixing each line with `indent` spaces
+    pub fn new(inner: W, indent: usize) -> Self {
+        Self { inner, indent: vec![b' '; indent], at_line_start: true }
+    }
+
+    /// Returns the wrapped writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for IndentWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        for line in buf.split_inclusive(|&b| b == b'\n') {
+            if self.at_line_start {
+                self.inner.write_all(&self.indent)?;
+            }
+            self.inner.write_all(line)?;
+            self.at_line_start = line.last() == Some(&b'\n');
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Writer that counts the bytes and lines (newlines) written through it, for
+/// summaries such as `--footer-summary`
+pub struct CountingWriter<W: std::io::Write> {
+    inner: W,
+    bytes: u64,
+    lines: u64,
+}
+
+impl<W: std::io::Write> CountingWriter<W> {
+    /// Wraps `inner` with both counts at zero
+    pub fn new(inner: W) -> Self {
+        Self { inner, bytes: 0, lines: 0 }
+    }
+
+    /// Returns the number of bytes written so far
+    pub fn bytes(&self) -> u64 {
+        self.bytes
+    }
+
+    /// Returns the number of newlines written so far
+    pub fn lines(&self) -> u64 {
+        self.lines
+    }
+
+    /// Borrows the wrapped writer; writing to it directly bypasses the counts
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Unwraps the writer, discarding the counts
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: std::io::Write> std::io::Write for CountingWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.bytes += n as u64;
+        self.lines += buf[..n].iter().filter(|&&b| b == b'\n').count() as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Output file that only appears under its final name once complete: data is
+/// written to `<path>.tmp`, then [`AtomicFile::commit`] fsyncs it and renames
+/// it into place. Dropping it uncommitted, or a failed commit, removes the
+/// temporary file. Destructors don't run on `process::exit`, so drop it
+/// before exiting.
+pub struct AtomicFile {
+    file: Option<std::fs::File>,
+    tmp_path: std::path::PathBuf,
+    path: std::path::PathBuf,
+    /// The temporary file has been renamed to `path`
+    renamed: bool,
+}
+
+impl AtomicFile {
+    /// Creates the temporary file that will become `path`
+    pub fn create(path: &Path) -> std::io::Result<Self> {
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = std::path::PathBuf::from(tmp_path);
+        let file = std::fs::File::create(&tmp_path)?;
+        Ok(Self { file: Some(file), tmp_path, path: path.to_path_buf(), renamed: false })
+    }
+
+    /// Returns the path of the temporary file being written
+    pub fn tmp_path(&self) -> &Path {
+        &self.tmp_path
+    }
+
+    /// Flushes the data to disk and renames the file to its final name
+    pub fn commit(mut self) -> std::io::Result<()> {
+        let file = self.file.take().expect("file is only taken on commit");
+        file.sync_all()?;
+        drop(file);
+        std::fs::rename(&self.tmp_path, &self.path)?;
+        self.renamed = true;
+
+        // Make the rename itself durable
+        #[cfg(unix)]
+        if let Some(dir) = self.path.parent() {
+            let dir = if dir.as_os_str().is_empty() { Path::new(".") } else { dir };
+            std::fs::File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl std::io::Write for AtomicFile {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.file.as_mut().expect("file is only taken on commit").write(buf)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.file.as_mut().expect("file is only taken on commit").flush()
+    }
+}
+
+impl Drop for AtomicFile {
+    fn drop(&mut self) {
+        if !self.renamed {
+            let _ = std::fs::remove_file(&self.tmp_path);
+        }
+    }
+}
+
+pub fn validate_version_mode(value: &OsStr) -> Result<(), ValidationError> {
+    let s = value.to_str()
+        .ok_or_else(|| ValidationError::new("Invalid UTF-8 in version mode".to_string()))?;
     
     match s.to_lowercase().as_str() {
         "version" | "v" | "copyright" | "c" | "verbose" => Ok(()),
@@ -624,6 +2482,95 @@ pub fn validate_version_mode(value: &OsStr) -> Result<(), ValidationError> {
     }
 }
 
+/// Parses a symbolic mode such as `u=rw,g=r,o=` into a numeric mode,
+/// starting from 0. Each comma-separated clause is `[ugoa]*` (none means
+/// `a`) followed by one or more `[=+-][rwx]*` operations.
+pub fn parse_symbolic_mode(s: &str) -> Result<u32, String> {
+    let mut mode = 0;
+    for clause in s.split(',') {
+        let ops_start = clause.find(['=', '+', '-'])
+            .ok_or_else(|| format!("Invalid mode clause '{}': missing =, + or -", clause))?;
+        let (who, mut ops) = clause.split_at(ops_start);
+        
+        let mut who_mask = 0;
+        for c in who.chars() {
+            who_mask |= match c {
+                'u' => 0o700,
+                'g' => 0o070,
+                'o' => 0o007,
+                'a' => 0o777,
+                _ => return Err(format!("Invalid mode clause '{}': unknown class '{}'", clause, c)),
+            };
+        }
+        if who_mask == 0 {
+            who_mask = 0o777;
+        }
+        
+        while let Some(op) = ops.chars().next() {
+            let perms_end = ops[1..].find(['=', '+', '-']).map_or(ops.len(), |i| i + 1);
+            let mut bits = 0;
+            for c in ops[1..perms_end].chars() {
+                bits |= match c {
+                    'r' => 0o444,
+                    'w' => 0o222,
+                    'x' => 0o111,
+                    _ => return Err(format!("Invalid mode clause '{}': unknown permission '{}'", clause, c)),
+                };
+            }
+            let bits = bits & who_mask;
+            match op {
+                '=' => mode = (mode & !who_mask) | bits,
+                '+' => mode |= bits,
+                _ => mode &= !bits,
+            }
+            ops = &ops[perms_end..];
+        }
+    }
+    Ok(mode)
+}
+
+/// Parses a file mode given in octal (`644`) or symbolic (`u=rw,g=r,o=`) form
+pub fn parse_mode(s: &str) -> Result<u32, String> {
+    if !s.is_empty() && s.bytes().all(|b| b.is_ascii_digit()) {
+        return u32::from_str_radix(s, 8)
+            .ok()
+            .filter(|mode| *mode <= 0o7777)
+            .ok_or_else(|| format!("Invalid octal mode '{}'", s));
+    }
+    parse_symbolic_mode(s)
+}
+
+/// Validates a file mode option value (octal or symbolic)
+pub fn validate_mode(value: &OsStr) -> Result<(), ValidationError> {
+    value.to_str()
+        .ok_or_else(|| "Invalid UTF-8 in mode".to_string())
+        .and_then(parse_mode)
+        .map(|_| ())
+        .map_err(ValidationError::new)
+}
+
+/// Validates an octal permission mode from `0` to `0777` (a leading `0` is
+/// allowed), for options that don't take setuid bits or symbolic modes
+pub fn validate_octal_mode(value: &OsStr) -> Result<(), ValidationError> {
+    let s = value.to_str()
+        .ok_or_else(|| ValidationError::new("Invalid UTF-8 in mode".to_string()))?;
+    if s.is_empty() || !s.bytes().all(|b| (b'0'..=b'7').contains(&b)) {
+        return Err(ValidationError::new(format!("Invalid octal mode '{}'", s)));
+    }
+    match u32::from_str_radix(s, 8) {
+        Ok(mode) if mode <= 0o777 => Ok(()),
+        _ => Err(ValidationError::new(format!("Mode '{}' is out of range (0 to 0777)", s))),
+    }
+}
+
+/// Validates that an option value is valid UTF-8, for options whose value is
+/// text rather than a path (file path validators tolerate raw bytes)
+pub fn validate_utf8(value: &OsStr) -> Result<(), ValidationError> {
+    value.to_str()
+        .map(|_| ())
+        .ok_or_else(|| ValidationError::new(format!("Invalid UTF-8 in value: {}", value.to_string_lossy())))
+}
+
 /// Validates that a file path is valid (basic check for reasonable characters)
 /// Used for config files and other file path options across all sharutils commands
 pub fn validate_file_path(value: &OsStr) -> Result<(), ValidationError> {
@@ -645,12 +2592,31 @@ pub fn validate_file_path(value: &OsStr) -> Result<(), ValidationError> {
 mod tests {
     use super::*;
 
+    /// Returns the standard options followed by `extra`
+    fn options_with(extra: impl IntoIterator<Item = OptionDefinition>) -> Vec<OptionDefinition> {
+        let mut options = standard_options();
+        options.extend(extra);
+        options
+    }
+
+    /// Parses `args` as the arguments following the command name
+    fn parse(options: &[OptionDefinition], args: &[&str]) -> Result<ParsedCommand, ParseError> {
+        parse_command_line(options, std::iter::once("test-cmd").chain(args.iter().copied()).map(OsString::from))
+    }
+
+    /// Returns a path in the temporary directory unique to `name` and this
+    /// test process; nothing is created there
+    pub(crate) fn scratch_path(name: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("sharutils-{}-{}", name, std::process::id()))
+    }
+
     #[test]
     fn test_standard_options() {
         let options = standard_options();
-        assert_eq!(options.len(), 2);
+        assert_eq!(options.len(), 3);
         assert_eq!(options[0].name, "help");
         assert_eq!(options[1].name, "version");
+        assert_eq!(options[2].name, "license");
     }
 
     #[test]
@@ -691,20 +2657,13 @@ mod tests {
         ];
         
         let result = parse_command_line(&options, args.into_iter());
-        assert!(matches!(result, Err(ParseError::UnknownOption(_))));
+        assert_eq!(result, Err(ParseError::UnknownOption("--unknown".to_string()).at_argument(1)));
     }
 
     #[test]
     fn test_combined_short_flags() {
         let mut options = standard_options();
-        options.push(OptionDefinition {
-            flag: 'm',
-            name: "mode".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Test mode".to_string(),
-        });
+        options.push(OptionDefinition::new('m', "mode").help("Test mode"));
         
         let args = vec![
             OsString::from("test-cmd"),
@@ -719,14 +2678,7 @@ mod tests {
     #[test]
     fn test_long_option_with_value() {
         let mut options = standard_options();
-        options.push(OptionDefinition {
-            flag: 'f',
-            name: "file".to_string(),
-            has_value: true,
-            default_value: None,
-            validator: None,
-            help_text: "File path".to_string(),
-        });
+        options.push(OptionDefinition::new('f', "file").value().help("File path"));
         
         let args = vec![
             OsString::from("test-cmd"),
@@ -741,14 +2693,7 @@ mod tests {
     #[test]
     fn test_short_flag_with_value() {
         let mut options = standard_options();
-        options.push(OptionDefinition {
-            flag: 'f',
-            name: "file".to_string(),
-            has_value: true,
-            default_value: None,
-            validator: None,
-            help_text: "File path".to_string(),
-        });
+        options.push(OptionDefinition::new('f', "file").value().help("File path"));
         
         let args = vec![
             OsString::from("test-cmd"),
@@ -764,14 +2709,7 @@ mod tests {
     #[test]
     fn test_missing_required_value() {
         let mut options = standard_options();
-        options.push(OptionDefinition {
-            flag: 'f',
-            name: "file".to_string(),
-            has_value: true,
-            default_value: None,
-            validator: None,
-            help_text: "File path".to_string(),
-        });
+        options.push(OptionDefinition::new('f', "file").value().help("File path"));
         
         let args = vec![
             OsString::from("test-cmd"),
@@ -779,20 +2717,18 @@ mod tests {
         ];
         
         let result = parse_command_line(&options, args.into_iter());
-        assert!(matches!(result, Err(ParseError::MissingValue(_))));
+        assert_eq!(result, Err(ParseError::MissingValue("file".to_string()).at_argument(1)));
     }
 
     #[test]
     fn test_default_value_when_no_value_provided() {
         let mut options = standard_options();
-        options.push(OptionDefinition {
-            flag: 'o',
-            name: "output".to_string(),
-            has_value: true,
-            default_value: Some(OsString::from("default.txt")),
-            validator: None,
-            help_text: "Output file".to_string(),
-        });
+        options.push(
+            OptionDefinition::new('o', "output")
+                .value()
+                .default_value("default.txt")
+                .help("Output file"),
+        );
         
         let args = vec![
             OsString::from("test-cmd"),
@@ -814,7 +2750,7 @@ mod tests {
         ];
         
         let result = parse_command_line(&options, args.into_iter());
-        assert!(matches!(result, Err(ParseError::DuplicateOption(_))));
+        assert_eq!(result, Err(ParseError::DuplicateOption("help".to_string()).at_argument(2)));
     }
 
     #[test]
@@ -839,8 +2775,7 @@ mod tests {
     fn test_option_value_or_default() {
         let mut cmd = ParsedCommand {
             executable_path: OsString::from("test"),
-            options: HashMap::new(),
-            arguments: Vec::new(),
+            ..ParsedCommand::default()
         };
         
         cmd.options.insert("test".to_string(), Some(OsString::from("value")));
@@ -855,6 +2790,138 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_unset_option() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('f', "file").value().help("File path"));
+
+        let args = vec![
+            OsString::from("test-cmd"),
+            OsString::from("--help"),
+            OsString::from("--file=test.txt"),
+        ];
+
+        let mut cmd = parse_command_line(&options, args.into_iter()).unwrap();
+        assert!(cmd.is_option_set("file"));
+
+        assert_eq!(cmd.unset_option("file"), Some(Some(OsString::from("test.txt"))));
+        assert!(!cmd.is_option_set("file"));
+        assert_eq!(cmd.unset_option("help"), Some(None));
+        assert_eq!(cmd.unset_option("help"), None);
+    }
+
+    #[test]
+    fn test_parsed_command_roundtrips_through_args() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('o', "output").value().help("Output file"));
+        options.push(OptionDefinition::new('m', "base64").help("Use base64"));
+        let args = ["test-cmd", "-m", "-o", "out.b64", "input.bin", "--", "--looks-like-an-option"];
+        let parsed = parse_command_line(&options, args.iter().map(OsString::from)).unwrap();
+
+        let saved = parsed.to_args();
+        let loaded = parse_command_line(&options, saved.into_iter()).unwrap();
+        assert_eq!(loaded, parsed);
+
+        let mut changed = loaded.clone();
+        changed.unset_option("base64");
+        assert_ne!(changed, parsed);
+    }
+
+    #[test]
+    fn test_parsed_command_builder() {
+        let cmd = ParsedCommand::builder()
+            .executable_path("uuencode")
+            .option("base64", None)
+            .option("output", Some("out.b64"))
+            .argument("file")
+            .build();
+
+        assert_eq!(cmd.executable_path, OsString::from("uuencode"));
+        assert!(cmd.is_option_set("base64"));
+        assert_eq!(cmd.option_state("base64"), OptionState::Present);
+        assert_eq!(cmd.option_value("output"), Some(OsStr::new("out.b64")));
+        assert!(!cmd.is_option_set("verbose"));
+        assert_eq!(cmd.arguments, vec![OsString::from("file")]);
+
+        let options = options_with([OptionDefinition::new('m', "base64"), OptionDefinition::new('o', "output").value()]);
+        let parsed = parse_command_line(&options, cmd.to_args().into_iter()).unwrap();
+        assert_eq!(parsed, cmd);
+    }
+
+    #[test]
+    fn test_greedy_values() {
+        let options = options_with([OptionDefinition::new('m', "base64"), OptionDefinition::new('o', "output").value()]);
+        let args = || ["test-cmd", "--output", "--base64"].iter().map(OsString::from);
+
+        // By default a following option is not taken as the value
+        let result = Parser::new(&options).unwrap().parse(args());
+        assert_eq!(result, Err(ParseError::MissingValue("output".to_string()).at_argument(1)));
+
+        let greedy = Parser::new(&options).unwrap()
+            .with_config(ParserConfig { greedy_values: true, ..ParserConfig::default() }).unwrap();
+        let parsed = greedy.parse(args()).unwrap();
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("--base64")));
+        assert!(!parsed.is_option_set("base64"));
+
+        let parsed = greedy.parse(["test-cmd", "-o", "-m"].iter().map(OsString::from)).unwrap();
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("-m")));
+
+        // Even greedy parsing never takes `--` as a value
+        let result = greedy.parse(["test-cmd", "--output", "--", "file"].iter().map(OsString::from));
+        assert_eq!(result, Err(ParseError::MissingValue("output".to_string()).at_argument(1)));
+    }
+
+    #[test]
+    fn test_parse_symbolic_mode() {
+        assert_eq!(parse_symbolic_mode("u=rw,g=r,o="), Ok(0o640));
+        assert_eq!(parse_symbolic_mode("a=rx,u+w"), Ok(0o755));
+        assert_eq!(parse_symbolic_mode("=rw,go-w"), Ok(0o644));
+        assert_eq!(parse_symbolic_mode("u=rwx-x"), Ok(0o600));
+        assert!(parse_symbolic_mode("u=rwz").is_err());
+        assert!(parse_symbolic_mode("q=r").is_err());
+        assert!(parse_symbolic_mode("urw").is_err());
+
+        assert_eq!(parse_mode("644"), Ok(0o644));
+        assert_eq!(parse_mode("u=rw,g=r,o="), Ok(0o640));
+        assert!(parse_mode("8").is_err());
+        assert!(validate_mode(OsStr::new("u=rw,g=r")).is_ok());
+        assert!(validate_mode(OsStr::new("u=rwq")).is_err());
+    }
+
+    #[test]
+    fn test_encode_base64_wrapped() {
+        let data: Vec<u8> = (0..200u8).collect();
+        let mut expected = Vec::new();
+        encode(&mut &data[..], &mut expected, true).unwrap();
+        let expected: Vec<u8> = expected.into_iter().filter(|&b| b != b'\n').collect();
+
+        for wrap in [0, 60, 76, 7] {
+            let mut out = Vec::new();
+            assert_eq!(encode_base64_wrapped(&mut &data[..], &mut out, wrap).unwrap(), 200);
+            let text = String::from_utf8(out).unwrap();
+            assert!(text.ends_with('\n'));
+            let lines: Vec<&str> = text.lines().collect();
+            if wrap == 0 {
+                assert_eq!(lines.len(), 1);
+            } else {
+                assert!(lines.iter().all(|l| l.len() <= wrap));
+                assert!(lines[..lines.len() - 1].iter().all(|l| l.len() == wrap));
+            }
+            assert_eq!(lines.concat().into_bytes(), expected);
+        }
+    }
+
+    #[test]
+    fn test_input_error_messages() {
+        let path = Path::new("secret.bin");
+        let denied = std::io::Error::from(std::io::ErrorKind::PermissionDenied);
+        assert_eq!(input_error(path, &denied), ("Permission denied reading secret.bin".to_string(), EXIT_PERMISSION_DENIED));
+        let missing = std::io::Error::from(std::io::ErrorKind::NotFound);
+        assert_eq!(input_error(path, &missing), ("No such input file secret.bin".to_string(), EXIT_NO_INPUT));
+        let other = std::io::Error::other("disk on fire");
+        assert_eq!(input_error(path, &other).1, 1);
+    }
+
     #[test]
     fn test_generate_help() {
         let options = standard_options();
@@ -867,26 +2934,1360 @@ mod tests {
     }
 
     #[test]
-    fn test_validate_positive_integer() {
-        assert!(validate_positive_integer(OsStr::new("42")).is_ok());
-        assert!(validate_positive_integer(OsStr::new("1")).is_ok());
-        assert!(validate_positive_integer(OsStr::new("0")).is_err());
-        assert!(validate_positive_integer(OsStr::new("-1")).is_err());
-        assert!(validate_positive_integer(OsStr::new("abc")).is_err());
+    fn test_split_os() {
+        assert_eq!(split_os(OsStr::new("mode,time"), b','), vec![OsString::from("mode"), OsString::from("time")]);
+        assert_eq!(split_os(OsStr::new(""), b','), vec![OsString::new()]);
     }
 
+    #[cfg(unix)]
     #[test]
-    fn test_validate_existing_file() {
-        // Test with a file that should exist (current dir)
-        assert!(validate_existing_file(OsStr::new(".")).is_ok());
-        
-        // Test with a file that shouldn't exist
-        assert!(validate_existing_file(OsStr::new("/nonexistent/file/path")).is_err());
+    fn test_split_os_keeps_non_utf8_bytes() {
+        use std::os::unix::ffi::{OsStrExt, OsStringExt};
+        let value = OsStr::from_bytes(b"caf\xe9,na\xefve,plain");
+        let parts = split_os(value, b',');
+        assert_eq!(parts, vec![
+            OsString::from_vec(b"caf\xe9".to_vec()),
+            OsString::from_vec(b"na\xefve".to_vec()),
+            OsString::from("plain"),
+        ]);
     }
 
     #[test]
-    fn test_validate_version_mode() {
-        // Test valid modes
+    fn test_help_reason_exit_codes() {
+        assert_eq!(HelpReason::Requested.exit_code(), 0);
+        assert_eq!(HelpReason::UsageError.exit_code(), EXIT_USAGE);
+
+        let mut out = Vec::new();
+        write_help(&mut out, "Usage: test-cmd").unwrap();
+        assert_eq!(out, b"Usage: test-cmd\n");
+    }
+
+    #[test]
+    fn test_generate_help_with_custom_heading() {
+        let options = standard_options();
+        let config = HelpConfig { heading: "Optionen:".to_string(), indent: "\t".to_string() };
+        let help = generate_help_with_config("testcmd", "Test command", "[OPTIONS]", &options, &config);
+
+        assert!(help.contains("\nOptionen:\n"));
+        assert!(!help.contains("Options:"));
+        assert!(help.contains("\n\t-h, --help"));
+
+        // The defaults reproduce generate_help exactly
+        assert_eq!(
+            generate_help_with_config("testcmd", "Test command", "[OPTIONS]", &options, &HelpConfig::default()),
+            generate_help("testcmd", "Test command", "[OPTIONS]", &options)
+        );
+    }
+
+    #[test]
+    fn test_defined_digit_flag_is_parsed_as_flag() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('1', "fast").help("Compress faster"));
+
+        let args = vec![
+            OsString::from("test-cmd"),
+            OsString::from("-1"),
+            OsString::from("file.txt"),
+        ];
+
+        let result = parse_command_line(&options, args.into_iter()).unwrap();
+        assert!(result.is_option_set("fast"));
+        assert_eq!(result.arguments, vec![OsString::from("file.txt")]);
+    }
+
+    #[test]
+    fn test_level_style_digit_flags() {
+        let options: Vec<OptionDefinition> = ('1'..='9')
+            .map(|level| OptionDefinition::new(level, format!("level-{}", level)))
+            .chain([OptionDefinition::new('v', "verbose")])
+            .collect();
+
+        let result = parse(&options, &["-5", "file.txt"]).unwrap();
+        assert!(result.is_option_set("level-5"));
+        assert_eq!(result.options.len(), 1);
+        assert_eq!(result.arguments, vec![OsString::from("file.txt")]);
+
+        // A level combines with other flags like any short flag
+        let result = parse(&options, &["-v9"]).unwrap();
+        assert!(result.is_option_set("verbose") && result.is_option_set("level-9"));
+        // No `0` level is defined, so `-0` stays a positional number
+        assert_eq!(parse(&options, &["-0"]).unwrap().arguments, vec![OsString::from("-0")]);
+    }
+
+    #[test]
+    fn test_undefined_digit_flag_is_argument() {
+        let options = standard_options();
+        let args = vec![
+            OsString::from("test-cmd"),
+            OsString::from("-1"),
+            OsString::from("file.txt"),
+        ];
+
+        let result = parse_command_line(&options, args.into_iter()).unwrap();
+        assert_eq!(result.arguments, vec![OsString::from("-1"), OsString::from("file.txt")]);
+    }
+
+    #[test]
+    fn test_negative_numbers_are_arguments() {
+        let options = options_with([OptionDefinition::new('m', "base64"), OptionDefinition::new('5', "five")]);
+
+        let result = parse(&options, &["-m", "-3.14", "name"]).unwrap();
+        assert!(result.is_option_set("base64"));
+        assert_eq!(result.arguments, vec![OsString::from("-3.14"), OsString::from("name")]);
+
+        // `-5` is claimed by the defined digit flag, but `-7` is not
+        let result = parse(&options, &["-m", "-5", "name"]).unwrap();
+        assert!(result.is_option_set("five"));
+        assert_eq!(result.arguments, vec![OsString::from("name")]);
+        assert_eq!(parse(&options, &["-m", "-7", "name"]).unwrap().arguments[0], OsString::from("-7"));
+        // Mixing the flag's digit into a number still parses as flags
+        assert_eq!(parse(&options, &["-57"]), Err(ParseError::UnknownOption("-7".to_string()).at_argument(1)));
+
+        // Not a number: a second decimal point
+        assert_eq!(parse(&options, &["-1.2.3"]), Err(ParseError::UnknownOption("-1".to_string()).at_argument(1)));
+    }
+
+    /// Reader that fails with an error after yielding `limit` bytes
+    struct InterruptedReader<'a> {
+        data: &'a [u8],
+        limit: usize,
+    }
+
+    impl std::io::Read for InterruptedReader<'_> {
+        fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+            if self.limit == 0 {
+                return Err(std::io::Error::other("simulated interruption"));
+            }
+            let n = buf.len().min(self.limit).min(self.data.len());
+            buf[..n].copy_from_slice(&self.data[..n]);
+            self.data = &self.data[n..];
+            self.limit -= n;
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_resume_from_checkpoint_matches_uninterrupted_encode() {
+        use std::io::{Cursor, Seek, SeekFrom};
+
+        let data: Vec<u8> = (0..10_000u32).map(|i| (i * 7 % 251) as u8).collect();
+
+        let mut expected = Vec::new();
+        write_uuencode_header(&mut expected, 0o644, "big.bin", false, false).unwrap();
+        encode(&mut &data[..], &mut expected, false).unwrap();
+        write_uuencode_trailer(&mut expected, false).unwrap();
+
+        // First run: checkpoint every 10 lines, interrupted part way through
+        let mut partial = Vec::new();
+        write_uuencode_header(&mut partial, 0o644, "big.bin", false, false).unwrap();
+        let start = Checkpoint { input_offset: 0, output_offset: partial.len() as u64 };
+        let mut last = None;
+        let mut reader = InterruptedReader { data: &data, limit: 5_000 };
+        let result = encode_with_checkpoints(&mut reader, &mut partial, false, start, 10, |cp| {
+            last = Some(cp);
+            Ok(())
+        });
+        assert!(result.is_err());
+        let checkpoint = last.expect("no checkpoint recorded");
+        assert_eq!(checkpoint.input_offset % 45, 0);
+
+        // Resume: drop output written after the checkpoint, seek the input
+        partial.truncate(checkpoint.output_offset as usize);
+        let mut input = Cursor::new(&data);
+        input.seek(SeekFrom::Start(checkpoint.input_offset)).unwrap();
+        encode_with_checkpoints(&mut input, &mut partial, false, checkpoint, 10, |_| Ok(())).unwrap();
+        write_uuencode_trailer(&mut partial, false).unwrap();
+
+        assert_eq!(partial, expected);
+    }
+
+    /// Writer that fails every write
+    struct FailingWriter;
+
+    impl std::io::Write for FailingWriter {
+        fn write(&mut self, _buf: &[u8]) -> std::io::Result<usize> {
+            Err(std::io::Error::other("disk full"))
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_encode_reports_read_error() {
+        let mut reader = InterruptedReader { data: b"some data", limit: 0 };
+        let result = encode(&mut reader, &mut Vec::new(), false);
+        assert!(matches!(result, Err(EncodeError::ReadError(_))));
+        assert!(result.unwrap_err().to_string().starts_with("failed to read input"));
+    }
+
+    #[test]
+    fn test_encode_reports_write_error() {
+        let data = b"some data";
+        for use_base64 in [false, true] {
+            let result = encode(&mut &data[..], &mut FailingWriter, use_base64);
+            assert!(matches!(result, Err(EncodeError::WriteError(_))));
+        }
+    }
+
+    #[test]
+    fn test_final_partial_group_padding() {
+        // Expected lines match GNU uuencode: the length character counts only
+        // real bytes, and the partial group is padded with zero bits
+        let cases: [(&[u8], &str); 4] = [
+            (b"a", "!80``\n"),
+            (b"ab", "\"86(`\n"),
+            (b"abcd", "$86)C9```\n"),
+            (b"abcde", "%86)C9&4`\n"),
+        ];
+
+        for (data, expected_line) in cases {
+            let mut out = Vec::new();
+            encode(&mut &data[..], &mut out, false).unwrap();
+            assert_eq!(String::from_utf8(out).unwrap(), expected_line);
+
+            let encoded = encode_to_bytes(data, "x", 0o644, false);
+            let (_, decoded) = decode_bytes(&encoded).unwrap();
+            assert_eq!(decoded, data, "roundtrip of {} bytes", data.len());
+        }
+    }
+
+    #[test]
+    fn test_in_memory_roundtrip() {
+        let encoded = encode_to_bytes(b"in memory", "mem.txt", 0o600, false);
+        let (header, data) = decode_bytes(&encoded).unwrap();
+        assert_eq!(header.name, "mem.txt");
+        assert_eq!(header.mode, 0o600);
+        assert_eq!(data, b"in memory");
+
+        assert!(matches!(decode_bytes(b"no header here\n"), Err(DecodeError::NoBeginLine)));
+    }
+
+    #[test]
+    fn test_run_self_test() {
+        assert_eq!(run_self_test(), Ok(()));
+    }
+
+    #[test]
+    fn test_part_comment_roundtrip() {
+        let part = PartNumber::new(2, 5).unwrap();
+        let mut out = Vec::new();
+        write_part_comment(&mut out, part).unwrap();
+        assert_eq!(out, b"# part 2 of 5\n");
+        assert_eq!(PartNumber::parse_comment(std::str::from_utf8(&out).unwrap()), Some(part));
+
+        assert_eq!(PartNumber::parse_comment("# a plain comment"), None);
+        assert_eq!(PartNumber::parse_comment("# part 6 of 5"), None);
+        assert!(PartNumber::new(0, 5).is_err());
+        assert!(PartNumber::new(3, 2).is_err());
+    }
+
+    #[test]
+    fn test_mtime_comment_roundtrip() {
+        let mut out = Vec::new();
+        write_mtime_comment(&mut out, 1_700_000_000).unwrap();
+        assert_eq!(out, b"# mtime: 1700000000\n");
+        assert_eq!(parse_mtime_comment(std::str::from_utf8(&out).unwrap()), Some(1_700_000_000));
+        assert_eq!(parse_mtime_comment("# mtime: soon"), None);
+        assert_eq!(parse_mtime_comment("# part 1 of 2"), None);
+    }
+
+    #[test]
+    fn test_part_sequence_order() {
+        let mut sequence = PartSequence::new();
+        sequence.check(PartNumber::new(1, 3).unwrap()).unwrap();
+        sequence.check(PartNumber::new(2, 3).unwrap()).unwrap();
+        assert!(sequence.is_incomplete());
+        let err = sequence.check(PartNumber::new(1, 3).unwrap()).unwrap_err();
+        assert!(err.contains("out of order (expected part 3)"));
+        sequence.check(PartNumber::new(3, 3).unwrap()).unwrap();
+        assert!(!sequence.is_incomplete());
+    }
+
+    #[test]
+    fn test_checkpoint_save_and_load() {
+        let path = scratch_path("checkpoint");
+        let checkpoint = Checkpoint { input_offset: 4500, output_offset: 6180 };
+        checkpoint.save(&path).unwrap();
+        assert_eq!(Checkpoint::load(&path).unwrap(), checkpoint);
+
+        std::fs::write(&path, "garbage").unwrap();
+        assert!(Checkpoint::load(&path).is_err());
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_atomic_file_renames_on_commit() {
+        use std::io::Write;
+        let path = scratch_path("atomic");
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"complete contents").unwrap();
+        let tmp_path = file.tmp_path().to_path_buf();
+
+        // Nothing is visible under the final name until the commit
+        assert!(!path.exists());
+        assert!(tmp_path.exists());
+        file.commit().unwrap();
+        assert!(!tmp_path.exists());
+        assert_eq!(std::fs::read(&path).unwrap(), b"complete contents");
+        std::fs::remove_file(&path).unwrap();
+
+        // An abandoned file leaves nothing behind
+        let mut file = AtomicFile::create(&path).unwrap();
+        file.write_all(b"partial").unwrap();
+        drop(file);
+        assert!(!path.exists());
+        assert!(!tmp_path.exists());
+    }
+
+    #[test]
+    fn test_parser_introspection() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new(NO_SHORT_FLAG, "verbose").help("Verbose output"));
+
+        let parser = Parser::new(&options).unwrap();
+        assert_eq!(parser.option_names(), vec!["help", "version", "license", "verbose"]);
+        assert_eq!(parser.flags(), vec!['h', 'V']);
+    }
+
+    #[test]
+    fn test_parser_rejects_duplicate_definitions() {
+        let mut options = standard_options();
+        options.extend(standard_options());
+        assert!(matches!(Parser::new(&options), Err(ParseError::DuplicateOption(_))));
+    }
+
+    #[test]
+    fn test_trace_reports_each_decision() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('m', "base64").help("Use base64"));
+        options.push(OptionDefinition::new('o', "output").value().help("Output file"));
+
+        let lines = std::cell::RefCell::new(Vec::new());
+        let record = |line: &str| lines.borrow_mut().push(line.to_string());
+        let parser = Parser::new(&options).unwrap().with_trace(&record);
+
+        let args = vec![
+            OsString::from("test-cmd"),
+            OsString::from("-m"),
+            OsString::from("-o"),
+            OsString::from("out.txt"),
+            OsString::from("input.bin"),
+        ];
+        parser.parse(args.into_iter()).unwrap();
+
+        assert_eq!(*lines.borrow(), vec![
+            "token '-m' -> flag 'base64' set",
+            "token '-o' -> flag 'output' = \"out.txt\" (next argument)",
+            "token 'input.bin' -> argument",
+        ]);
+    }
+
+    #[test]
+    fn test_value_from_file() {
+        let mut options = standard_options();
+        options.push(
+            OptionDefinition::new('c', "comment")
+                .value()
+                .value_from_file()
+                .help("Comment text"),
+        );
+        let path = scratch_path("comment");
+        std::fs::write(&path, "from a file\n").unwrap();
+        let parse = |value: String| parse_command_line(&options, vec![
+            OsString::from("test-cmd"),
+            OsString::from("--comment"),
+            OsString::from(value),
+        ].into_iter());
+
+        let parsed = parse(format!("@{}", path.display())).unwrap();
+        assert_eq!(parsed.option_value("comment"), Some(OsStr::new("from a file")));
+
+        let parsed = parse("@@literal".to_string()).unwrap();
+        assert_eq!(parsed.option_value("comment"), Some(OsStr::new("@literal")));
+
+        std::fs::remove_file(&path).unwrap();
+        let error = parse(format!("@{}", path.display())).unwrap_err();
+        assert!(matches!(error.without_argument_index(), ParseError::ValidationError(_)));
+    }
+
+    #[test]
+    fn test_response_files() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('o', "output").value());
+        let dir = scratch_path("response");
+        std::fs::create_dir_all(&dir).unwrap();
+        let outer = dir.join("outer");
+        let inner = dir.join("inner");
+        let looping = dir.join("loop");
+        std::fs::write(&outer, format!("-o\n'out file.txt'\n\n@{}\nlast\n", inner.display())).unwrap();
+        std::fs::write(&inner, "\"  spaced  \"\r\n\"two\nlines\"\n@@literal\n").unwrap();
+        std::fs::write(&looping, format!("first\n@{}\n", looping.display())).unwrap();
+        let parse = |args: &[String]| parse_command_line(
+            &options,
+            std::iter::once("test-cmd".to_string()).chain(args.iter().cloned()).map(OsString::from),
+        );
+
+        // Nested files are spliced in place, quotes keep spaces and newlines
+        let parsed = parse(&["before".to_string(), format!("@{}", outer.display()), "after".to_string()]).unwrap();
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("out file.txt")));
+        let expected: Vec<OsString> = ["before", "  spaced  ", "two\nlines", "@literal", "last", "after"]
+            .into_iter().map(OsString::from).collect();
+        assert_eq!(parsed.arguments, expected);
+
+        // `@@` escapes a literal `@`, and nothing after `--` is expanded
+        let parsed = parse(&["@@outer".to_string(), "--".to_string(), format!("@{}", outer.display())]).unwrap();
+        assert_eq!(parsed.arguments, vec![OsString::from("@outer"), OsString::from(format!("@{}", outer.display()))]);
+
+        assert_eq!(
+            parse(&[format!("@{}", looping.display())]),
+            Err(ParseError::ResponseFileDepth(looping.display().to_string()).at_argument(1))
+        );
+        assert!(matches!(
+            parse(&[format!("@{}", dir.join("missing").display())]).map_err(ParseError::without_argument_index),
+            Err(ParseError::ValidationError(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_optional_value_states() {
+        let mut options = standard_options();
+        options.retain(|opt| opt.name != "version");
+        options.push(
+            OptionDefinition::new('v', "version")
+                .optional_value()
+                .validator(validate_version_mode)
+                .help("Output version information and exit [=MODE]"),
+        );
+
+        assert_eq!(parse(&options, &["-v"]).unwrap().option_state("version"), OptionState::Present);
+        assert_eq!(parse(&options, &["--version"]).unwrap().option_state("version"), OptionState::Present);
+        assert_eq!(
+            parse(&options, &["--version=verbose"]).unwrap().option_state("version"),
+            OptionState::Value(OsStr::new("verbose"))
+        );
+        assert_eq!(parse(&options, &[]).unwrap().option_state("version"), OptionState::Absent);
+        assert!(parse(&options, &["--version=bogus"]).is_err());
+
+        // A bare optional-value option does not swallow the next argument
+        let parsed = parse(&options, &["--version", "file"]).unwrap();
+        assert_eq!(parsed.option_state("version"), OptionState::Present);
+        assert_eq!(parsed.arguments, vec![OsString::from("file")]);
+    }
+
+    #[test]
+    fn test_optional_value_flag_inside_cluster() {
+        let mut options = standard_options();
+        options.retain(|opt| opt.name != "version");
+        options.extend([
+            OptionDefinition::new('v', "version").optional_value(),
+            OptionDefinition::new('m', "base64"),
+            OptionDefinition::new('o', "output").value(),
+        ]);
+
+        let parsed = parse(&options, &["-vm"]).unwrap();
+        assert_eq!(parsed.option_state("version"), OptionState::Present);
+        assert_eq!(parsed.option_state("base64"), OptionState::Present);
+
+        let parsed = parse(&options, &["-mv"]).unwrap();
+        assert_eq!(parsed.option_state("version"), OptionState::Present);
+
+        // Required values still have to come last
+        assert!(matches!(parse(&options, &["-om", "out.txt"]).map_err(ParseError::without_argument_index), Err(ParseError::InvalidFlagCombination(_))));
+        let parsed = parse(&options, &["-vo", "out.txt"]).unwrap();
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("out.txt")));
+    }
+
+    #[test]
+    fn test_value_flag_after_boolean_flags() {
+        let options = options_with([
+            OptionDefinition::new('m', "base64"),
+            OptionDefinition::new('e', "encode-file-name"),
+            OptionDefinition::new('f', "file").value(),
+        ]);
+
+        let parsed = parse(&options, &["-mef", "out.txt"]).unwrap();
+        assert!(parsed.is_option_set("base64"));
+        assert!(parsed.is_option_set("encode-file-name"));
+        assert_eq!(parsed.option_value("file"), Some(OsStr::new("out.txt")));
+        assert!(parsed.arguments.is_empty());
+
+        let parsed = parse(&options, &["-mefout.txt", "input"]).unwrap();
+        assert!(parsed.is_option_set("base64"));
+        assert!(parsed.is_option_set("encode-file-name"));
+        assert_eq!(parsed.option_value("file"), Some(OsStr::new("out.txt")));
+        assert_eq!(parsed.arguments, vec![OsString::from("input")]);
+
+        match parse(&options, &["-mfe", "out.txt"]).map_err(ParseError::without_argument_index) {
+            Err(ParseError::InvalidFlagCombination(message)) => {
+                assert!(message.contains("Flag 'f' requires a value"), "{}", message);
+            }
+            other => panic!("expected an invalid combination, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_attached_short_values() {
+        let mut options = standard_options();
+        options.retain(|opt| opt.name != "version");
+        options.extend([OptionDefinition::new('v', "version").optional_value(), OptionDefinition::new('f', "file").value()]);
+        let file = |args: &[&str]| parse(&options, args).unwrap().option_value("file").map(OsStr::to_os_string);
+
+        assert_eq!(file(&["-ffile.txt"]), Some(OsString::from("file.txt")));
+        // `=` is accepted the same way as for long options
+        assert_eq!(file(&["-f=file.txt"]), Some(OsString::from("file.txt")));
+        assert_eq!(file(&["-f==x"]), Some(OsString::from("=x")));
+        assert_eq!(parse(&options, &["-f="]), Err(ParseError::MissingValue("file".to_string()).at_argument(1)));
+
+        let parsed = parse(&options, &["-vffile.txt", "input"]).unwrap();
+        assert_eq!(parsed.option_state("version"), OptionState::Present);
+        assert_eq!(parsed.option_value("file"), Some(OsStr::new("file.txt")));
+        assert_eq!(parsed.arguments, vec![OsString::from("input")]);
+
+        // Without an attached value the next argument is used
+        assert_eq!(file(&["-f", "next.txt"]), Some(OsString::from("next.txt")));
+        assert_eq!(parse(&options, &["-f"]), Err(ParseError::MissingValue("file".to_string()).at_argument(1)));
+    }
+
+    #[test]
+    fn test_debug_dump_shows_value_provenance() {
+        let mut options = standard_options();
+        options.retain(|opt| opt.name != "version");
+        options.extend([
+            OptionDefinition::new('v', "version").optional_value(),
+            OptionDefinition::new('o', "output").value().default_value("out.txt"),
+        ]);
+        let dump = |args: &[&str]| format_parsed_command(&parse(&options, args).unwrap(), &options);
+
+        let bare = dump(&["-v"]);
+        assert!(bare.contains("    --version (default)\n"), "{}", bare);
+        assert!(bare.contains("    --output (absent)\n"), "{}", bare);
+        assert!(bare.contains("    --help (absent)\n"), "{}", bare);
+
+        let explicit = dump(&["--version=short", "-h"]);
+        assert!(explicit.contains("    --version = \"short\" (cli)\n"), "{}", explicit);
+        assert!(explicit.contains("    --help (cli)\n"), "{}", explicit);
+
+        // A value option given without its value falls back to default_value
+        assert!(dump(&["-o"]).contains("    --output = \"out.txt\" (default)\n"));
+        assert!(dump(&["-o", "out.txt"]).contains("    --output = \"out.txt\" (cli)\n"));
+    }
+
+    #[test]
+    fn test_long_option_prefixes() {
+        let options = options_with([
+            OptionDefinition::new(NO_SHORT_FLAG, "base64"),
+            OptionDefinition::new(NO_SHORT_FLAG, "base64-variant").value(),
+            OptionDefinition::new(NO_SHORT_FLAG, "save-opts"),
+            OptionDefinition::new(NO_SHORT_FLAG, "sort"),
+        ]);
+
+        // Unambiguous prefixes, with and without a value
+        assert!(parse(&options, &["--he"]).unwrap().is_option_set("help"));
+        assert!(parse(&options, &["--so"]).unwrap().is_option_set("sort"));
+        let parsed = parse(&options, &["--base64-v=url"]).unwrap();
+        assert_eq!(parsed.option_value("base64-variant"), Some(OsStr::new("url")));
+
+        // An exact name wins even though `base64-variant` extends it
+        let parsed = parse(&options, &["--base64"]).unwrap();
+        assert!(parsed.is_option_set("base64"));
+        assert!(!parsed.is_option_set("base64-variant"));
+
+        assert_eq!(
+            parse(&options, &["--s"]),
+            Err(ParseError::AmbiguousOption("--s".to_string(), vec!["save-opts".to_string(), "sort".to_string()]).at_argument(1))
+        );
+        assert_eq!(
+            parse(&options, &["--bas"]).unwrap_err().to_string(),
+            "argument 1: Ambiguous option: --bas (could be --base64, --base64-variant)"
+        );
+        assert_eq!(parse(&options, &["--sorted"]), Err(ParseError::UnknownOption("--sorted".to_string()).at_argument(1)));
+        // An abbreviation names the same option as its full spelling
+        assert_eq!(parse(&options, &["--sort", "--so"]), Err(ParseError::DuplicateOption("sort".to_string()).at_argument(2)));
+    }
+
+    #[test]
+    fn test_multiple_option_collects_values() {
+        let options = options_with([OptionDefinition::new('o', "output").value().multiple(), OptionDefinition::new('f', "file").value()]);
+
+        let parsed = parse(&options, &["-o", "a", "--output=b", "-oc", "input"]).unwrap();
+        assert_eq!(parsed.option_values("output"), vec![OsStr::new("a"), OsStr::new("b"), OsStr::new("c")]);
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("c")));
+        assert_eq!(parsed.arguments, vec![OsString::from("input")]);
+        assert_eq!(parse_command_line(&options, parsed.to_args().into_iter()), Ok(parsed));
+
+        let parsed = parse(&options, &["-f", "a"]).unwrap();
+        assert_eq!(parsed.option_values("file"), vec![OsStr::new("a")]);
+        assert!(parsed.option_values("output").is_empty());
+        assert_eq!(parse(&options, &["-f", "a", "-f", "b"]), Err(ParseError::DuplicateOption("file".to_string()).at_argument(3)));
+    }
+
+    #[test]
+    fn test_parse_global_then_sub() {
+        let definition = |flag, name: &str| OptionDefinition::new(flag, name);
+        let global = vec![definition('v', "verbose")];
+        let encode = vec![definition('m', "base64")];
+        let decode = vec![definition('i', "ignore-chmod")];
+        let subcommands: [(&str, &[OptionDefinition]); 2] = [("encode", &encode), ("decode", &decode)];
+        let parse = |args: &[&str]| parse_global_then_sub(
+            &global,
+            &subcommands,
+            std::iter::once("tool").chain(args.iter().copied()).map(OsString::from),
+        );
+
+        let (global_cmd, name, sub) = parse(&["-v", "encode", "--base64", "file", "out"]).unwrap();
+        assert_eq!(global_cmd.executable_path, OsString::from("tool"));
+        assert!(global_cmd.is_option_set("verbose"));
+        assert!(global_cmd.arguments.is_empty());
+        assert_eq!(name, "encode");
+        assert_eq!(sub.executable_path, OsString::from("encode"));
+        assert!(sub.is_option_set("base64"));
+        assert_eq!(sub.arguments, vec![OsString::from("file"), OsString::from("out")]);
+
+        // Options belong to the parser of the side they appear on
+        assert_eq!(parse(&["--base64", "encode"]), Err(ParseError::UnknownOption("--base64".to_string()).at_argument(1)));
+        // The subcommand's arguments count from its name
+        assert_eq!(parse(&["decode", "-v"]), Err(ParseError::UnknownOption("-v".to_string()).at_argument(1)));
+        assert_eq!(parse(&["-v"]), Err(ParseError::MissingSubcommand));
+        assert_eq!(parse(&["shar"]), Err(ParseError::UnknownSubcommand("shar".to_string())));
+    }
+
+    #[test]
+    fn test_options_after_arguments() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('i', "ignore-chmod"));
+        let args = || ["uudecode", "file1", "--ignore-chmod", "file2", "--", "-i"].into_iter().map(OsString::from);
+
+        let parsed = parse_command_line(&options, args()).unwrap();
+        assert!(parsed.is_option_set("ignore-chmod"));
+        assert_eq!(parsed.arguments, vec![OsString::from("file1"), OsString::from("file2"), OsString::from("-i")]);
+
+        let strict = ParserConfig { stop_at_first_argument: true, ..ParserConfig::default() };
+        let parsed = Parser::new(&options).unwrap().with_config(strict).unwrap().parse(args()).unwrap();
+        assert!(!parsed.is_option_set("ignore-chmod"));
+        assert_eq!(parsed.arguments, ["file1", "--ignore-chmod", "file2", "--", "-i"].map(OsString::from));
+    }
+
+    #[test]
+    fn test_help_model_describes_options() {
+        let options = options_with([
+            OptionDefinition::new('o', "output").value().default_value("out.txt").group("Output").help("Help for output"),
+            OptionDefinition::new(NO_SHORT_FLAG, "debug-dump").hidden().help("Help for debug-dump"),
+        ]);
+
+        let model = help_model("testcmd", "Test command", "[OPTIONS]", &options);
+        assert_eq!(model.command_name, "testcmd");
+        assert_eq!(model.usage, "[OPTIONS]");
+        let visible: Vec<&HelpOption> = model.options.iter().filter(|option| !option.hidden).collect();
+        assert_eq!(
+            visible.iter().map(|option| option.long_name.as_str()).collect::<Vec<_>>(),
+            vec!["help", "version", "license", "output"]
+        );
+        assert_eq!(*visible[3], HelpOption {
+            short_flag: Some('o'),
+            long_name: "output".to_string(),
+            value_name: Some("VALUE".to_string()),
+            value_optional: false,
+            default_value: Some(OsString::from("out.txt")),
+            help_text: "Help for output".to_string(),
+            group: Some("Output".to_string()),
+            hidden: false,
+            negatable: false,
+            value_hint: ValueHint::Any,
+        });
+        assert_eq!(visible[2].short_flag, None);
+        assert_eq!(visible[2].value_name, None);
+
+        // The text help is rendered from the model: hidden options are left
+        // out and grouped ones get a heading
+        let help = generate_help("testcmd", "Test command", "[OPTIONS]", &options);
+        assert!(help.contains("\nOutput:\n  -o, --output         Help for output\n"), "{}", help);
+        assert!(!help.contains("debug-dump"));
+    }
+
+    #[test]
+    fn test_help_lists_each_group_once() {
+        let options = options_with([
+            OptionDefinition::new(NO_SHORT_FLAG, "alpha").group("A"),
+            OptionDefinition::new(NO_SHORT_FLAG, "plain"),
+            OptionDefinition::new(NO_SHORT_FLAG, "beta").group("B"),
+            OptionDefinition::new(NO_SHORT_FLAG, "again").group("A"),
+        ]);
+
+        let help = generate_help("testcmd", "Test command", "[OPTIONS]", &options);
+        assert_eq!(help.matches("\nA:\n").count(), 1, "{}", help);
+        assert_eq!(help.matches("\nB:\n").count(), 1, "{}", help);
+        let position = |text: &str| help.find(text).unwrap();
+        assert!(position("--plain") < position("\nA:\n"), "{}", help);
+        assert!(position("\nA:\n") < position("--alpha"));
+        assert!(position("--again") < position("\nB:\n"), "{}", help);
+        assert!(position("\nB:\n") < position("--beta"));
+    }
+
+    #[test]
+    fn test_required_options() {
+        let options = options_with([
+            OptionDefinition::new(NO_SHORT_FLAG, "output").value().required(),
+            OptionDefinition::new(NO_SHORT_FLAG, "mode").value().required(),
+        ]);
+
+        assert!(parse(&options, &["--mode=644", "--output", "out.txt"]).is_ok());
+        assert_eq!(parse(&options, &["--mode=644"]), Err(ParseError::MissingRequiredOption("output".to_string())));
+        // The first missing option in definition order is reported
+        assert_eq!(parse(&options, &["file"]), Err(ParseError::MissingRequiredOption("output".to_string())));
+        assert_eq!(parse(&options, &["--output=x"]).unwrap_err().to_string(), "Option 'mode' is required");
+
+        // Help (or version) can be asked for without the required options
+        assert!(parse(&options, &["--help"]).unwrap().is_option_set("help"));
+        assert!(parse(&options, &["-V"]).is_ok());
+    }
+
+    #[test]
+    fn test_encode_to_string_roundtrip() {
+        let data: Vec<u8> = (0..=255u8).collect();
+        for use_base64 in [false, true] {
+            let encoded = encode_to_string(&data, use_base64);
+            assert!(encoded.starts_with("begin"));
+            let (header, decoded) = decode_bytes(encoded.as_bytes()).unwrap();
+            assert_eq!(header.name, ENCODE_TO_STRING_NAME);
+            assert_eq!(decoded, data);
+        }
+    }
+
+    #[test]
+    fn test_option_requires_and_conflicts() {
+        let options = options_with([
+            OptionDefinition::new(NO_SHORT_FLAG, "resume").requires("checkpoint"),
+            OptionDefinition::new(NO_SHORT_FLAG, "checkpoint").requires("output").conflicts("indent"),
+            OptionDefinition::new(NO_SHORT_FLAG, "output"),
+            OptionDefinition::new(NO_SHORT_FLAG, "indent"),
+        ]);
+
+        // Dependencies are only checked for options that were given
+        assert!(parse(&options, &["--indent"]).is_ok());
+        assert!(parse(&options, &["--resume", "--checkpoint", "--output"]).is_ok());
+        assert_eq!(
+            parse(&options, &["--resume", "--checkpoint"]),
+            Err(ParseError::UnmetDependency { option: "checkpoint".to_string(), requires: "output".to_string() })
+        );
+        assert_eq!(
+            parse(&options, &["--resume"]).unwrap_err().to_string(),
+            "Option 'resume' requires option 'checkpoint'"
+        );
+        assert_eq!(
+            parse(&options, &["--checkpoint", "--output", "--indent"]),
+            Err(ParseError::InvalidFlagCombination("--checkpoint cannot be combined with --indent".to_string()))
+        );
+
+        // Names that aren't defined are caught when the parser is built
+        let misspelled = options_with([OptionDefinition::new(NO_SHORT_FLAG, "resume").requires("checkpiont")]);
+        assert_eq!(
+            Parser::new(&misspelled).err(),
+            Some(ParseError::UnknownOption("checkpiont (in the requires of --resume)".to_string()))
+        );
+        let misspelled = options_with([OptionDefinition::new(NO_SHORT_FLAG, "quiet").conflicts("verbos")]);
+        assert!(matches!(Parser::new(&misspelled), Err(ParseError::UnknownOption(_))));
+    }
+
+    // Builds a non-UTF-8 value, which only Unix can do portably
+    #[cfg(unix)]
+    #[test]
+    fn test_env_var_fallback() {
+        use std::os::unix::ffi::OsStringExt;
+        const VAR: &str = "SHARUTILS_TEST_ENV_VAR_FALLBACK_OUTPUT";
+        let mut options = standard_options();
+        options.push(
+            OptionDefinition::new('o', "output")
+                .value()
+                .default_value("default.txt")
+                .env_var(VAR),
+        );
+        // A stand-in environment, as changing the real one races with
+        // every other test thread that reads it
+        let environment = std::cell::RefCell::new(None);
+        let lookup = |var: &str| {
+            assert_eq!(var, VAR);
+            environment.borrow().clone()
+        };
+        let parser = Parser::new(&options).unwrap().with_env(&lookup);
+        let parse = |args: &[&str]| parser.parse(
+            std::iter::once("test-cmd").chain(args.iter().copied()).map(OsString::from),
+        ).unwrap();
+
+        // Unset: the option stays absent, and a bare option gets default_value
+        assert_eq!(parse(&[]).value_provenance("output"), ValueProvenance::Absent);
+        assert_eq!(parse(&["-o"]).option_value("output"), Some(OsStr::new("default.txt")));
+
+        // Set: the variable fills in for a missing option, ahead of
+        // default_value, but never over a command line value
+        let env_value = OsString::from_vec(b"env-\xff.txt".to_vec());
+        *environment.borrow_mut() = Some(env_value.clone());
+        let parsed = parse(&[]);
+        assert_eq!(parsed.option_value("output"), Some(env_value.as_os_str()));
+        assert_eq!(parsed.value_provenance("output"), ValueProvenance::Env);
+        let parsed = parse(&["-o", "cli.txt"]);
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("cli.txt")));
+        assert_eq!(parsed.value_provenance("output"), ValueProvenance::Cli);
+        assert_eq!(parse(&["-o"]).value_provenance("output"), ValueProvenance::Default);
+
+        *environment.borrow_mut() = None;
+        assert!(!parse(&[]).is_option_set("output"));
+    }
+
+    #[test]
+    fn test_case_insensitive_long_options() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('m', "base64"));
+        let args = |arg: &str| vec![OsString::from("test-cmd"), OsString::from(arg)].into_iter();
+        let folding = ParserConfig { case_insensitive_long_options: true, ..ParserConfig::default() };
+
+        // Off by default
+        assert_eq!(
+            Parser::new(&options).unwrap().parse(args("--BASE64")),
+            Err(ParseError::UnknownOption("--BASE64".to_string()).at_argument(1))
+        );
+
+        let parser = Parser::new(&options).unwrap().with_config(folding).unwrap();
+        assert!(parser.parse(args("--BASE64")).unwrap().is_option_set("base64"));
+        assert!(parser.parse(args("--Lic")).unwrap().is_option_set("license"));
+        // Short flags keep their case
+        assert_eq!(parser.parse(args("-M")), Err(ParseError::UnknownOption("-M".to_string()).at_argument(1)));
+
+        // Names that only differ in case can't be told apart once folded
+        drop(parser);
+        let mut colliding = standard_options();
+        colliding.push(OptionDefinition { name: "HELP".to_string(), flag: NO_SHORT_FLAG, ..options.pop().unwrap() });
+        assert!(Parser::new(&colliding).is_ok());
+        assert_eq!(
+            Parser::new(&colliding).unwrap().with_config(folding).err(),
+            Some(ParseError::DuplicateOption("HELP".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_dash_is_stdin_argument() {
+        let options = standard_options();
+        let args = |args: &[&str]| std::iter::once("test-cmd")
+            .chain(args.iter().copied())
+            .map(OsString::from)
+            .collect::<Vec<_>>()
+            .into_iter();
+
+        let parsed = parse_command_line(&options, args(&["in.txt", "-", "-h"])).unwrap();
+        assert_eq!(parsed.arguments, vec![OsString::from("in.txt"), OsString::from("-")]);
+        assert!(parsed.is_option_set("help"));
+        assert!(!parsed.argument_is_stdin(0));
+        assert!(parsed.argument_is_stdin(1));
+        assert!(!parsed.argument_is_stdin(2));
+
+        // Also after `--`, and ahead of a POSIX-style end of options
+        assert!(parse_command_line(&options, args(&["--", "-"])).unwrap().argument_is_stdin(0));
+        let strict = ParserConfig { stop_at_first_argument: true, ..ParserConfig::default() };
+        let parsed = Parser::new(&options).unwrap().with_config(strict).unwrap().parse(args(&["-", "-h"])).unwrap();
+        assert_eq!(parsed.arguments, vec![OsString::from("-"), OsString::from("-h")]);
+
+        // A value-taking option takes it as its value
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('o', "output").value().help("Output file"));
+        let parsed = parse_command_line(&options, args(&["-o", "-", "in.txt"])).unwrap();
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("-")));
+        assert_eq!(parsed.arguments, vec![OsString::from("in.txt")]);
+        let parsed = parse_command_line(&options, args(&["--output", "-"])).unwrap();
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("-")));
+    }
+
+    #[test]
+    fn test_normalization_hooks() {
+        let options = standard_options();
+        let args = |args: &[&str]| std::iter::once("test-cmd")
+            .chain(args.iter().copied())
+            .map(OsString::from)
+            .collect::<Vec<_>>()
+            .into_iter();
+        let parser = Parser::new(&options).unwrap()
+            .with_hook(|parsed| {
+                if parsed.arguments.is_empty() {
+                    parsed.arguments.push(OsString::from(STDIN_ARGUMENT));
+                }
+                Ok(())
+            })
+            .with_hook(|parsed| match parsed.arguments.len() {
+                1 => Ok(()),
+                _ => Err(ParseError::ValidationError(ValidationError::new("expected one argument".to_string()))),
+            });
+
+        assert!(parser.parse(args(&[])).unwrap().argument_is_stdin(0));
+        assert_eq!(parser.parse(args(&["in.txt"])).unwrap().arguments, vec![OsString::from("in.txt")]);
+        assert_eq!(
+            parser.parse(args(&["a", "b"])),
+            Err(ParseError::ValidationError(ValidationError::new("expected one argument".to_string())))
+        );
+        // Hooks only see commands that parsed
+        assert_eq!(parser.parse(args(&["--bogus"])).unwrap_err().argument_index(), Some(1));
+    }
+
+    #[test]
+    fn test_counted_flags() {
+        let options = options_with([OptionDefinition::new('v', "verbose").counted(), OptionDefinition::new('q', "quiet")]);
+
+        assert_eq!(parse(&options, &[]).unwrap().option_count("verbose"), 0);
+        assert_eq!(parse(&options, &["-vvv"]).unwrap().option_count("verbose"), 3);
+        assert_eq!(parse(&options, &["-v", "-v", "-v"]).unwrap().option_count("verbose"), 3);
+        assert_eq!(parse(&options, &["-vv", "--verbose", "--verb"]).unwrap().option_count("verbose"), 4);
+
+        // Counted and normal flags mix in one combination
+        let parsed = parse(&options, &["-vqvh"]).unwrap();
+        assert_eq!(parsed.option_count("verbose"), 2);
+        assert_eq!(parsed.option_count("quiet"), 1);
+        assert!(parsed.is_option_set("help"));
+        assert_eq!(parse(&options, &["-vqvq"]), Err(ParseError::DuplicateOption("quiet".to_string()).at_argument(1)));
+
+        // The count survives a roundtrip through to_args
+        let parsed = parse(&options, &["-vvv"]).unwrap();
+        assert_eq!(parse_command_line(&options, parsed.to_args().into_iter()).unwrap(), parsed);
+    }
+
+    #[test]
+    fn test_negatable_flags() {
+        let options = options_with([
+            OptionDefinition::new('m', "base64").negatable(),
+            OptionDefinition::new('q', "quiet"),
+            OptionDefinition::new(NO_SHORT_FLAG, "no-header"),
+        ]);
+
+        assert_eq!(parse(&options, &["--no-base64"]).unwrap().option_bool("base64"), Some(false));
+        assert_eq!(parse(&options, &["--base64"]).unwrap().option_bool("base64"), Some(true));
+        assert_eq!(parse(&options, &[]).unwrap().option_bool("base64"), None);
+
+        // The last of the two forms wins
+        let parsed = parse(&options, &["-m", "--no-base64"]).unwrap();
+        assert!(!parsed.is_option_set("base64"));
+        assert_eq!(parsed.option_bool("base64"), Some(false));
+        assert_eq!(parse(&options, &["--no-base64", "-m"]).unwrap().option_bool("base64"), Some(true));
+        assert_eq!(parse_command_line(&options, parsed.to_args().into_iter()).unwrap(), parsed);
+
+        // Only negatable options have a --no- form, and it takes no value
+        assert_eq!(parse(&options, &["--no-quiet"]), Err(ParseError::UnknownOption("--no-quiet".to_string()).at_argument(1)));
+        let error = parse(&options, &["--no-base64=yes"]).unwrap_err();
+        assert!(matches!(error.without_argument_index(), ParseError::ValidationError(_)));
+        // An option really named no-header is not a negation of anything
+        assert!(parse(&options, &["--no-header"]).unwrap().is_option_set("no-header"));
+
+        let help = generate_help("testcmd", "Test command", "[OPTIONS]", &options);
+        assert!(help.contains("-m, --[no-]base64"), "{}", help);
+    }
+
+    #[test]
+    fn test_builder_matches_literal_definition() {
+        let built = OptionDefinition::new('o', "output")
+            .value()
+            .default_value("out.txt")
+            .validator(validate_file_path)
+            .multiple()
+            .requires("mode")
+            .conflicts("stdout")
+            .conflicts("quiet")
+            .env_var("TEST_OUTPUT")
+            .group("Output")
+            .value_hint(ValueHint::FilePath)
+            .help("Write to FILE");
+        let literal = OptionDefinition {
+            flag: 'o',
+            name: "output".to_string(),
+            has_value: true,
+            has_optional_value: false,
+            default_value: Some(OsString::from("out.txt")),
+            validator: Some(Box::new(validate_file_path)),
+            value_from_file: false,
+            multiple: true,
+            counted: false,
+            negatable: false,
+            required: false,
+            requires: vec!["mode".to_string()],
+            conflicts: vec!["stdout".to_string(), "quiet".to_string()],
+            env_var: Some("TEST_OUTPUT".to_string()),
+            group: Some("Output".to_string()),
+            hidden: false,
+            value_hint: ValueHint::FilePath,
+            help_text: "Write to FILE".to_string(),
+        };
+        assert_eq!(format!("{:?}", built), format!("{:?}", literal));
+        let path = OptionDefinition::new('f', "file").value().validator(validate_file_path);
+        assert_eq!(path.value_hint, ValueHint::Any);
+
+        let flag = OptionDefinition::new(NO_SHORT_FLAG, "verbose").counted().negatable().hidden();
+        assert_eq!((flag.flag, flag.has_value, flag.counted, flag.negatable, flag.hidden), (NO_SHORT_FLAG, false, true, true, true));
+        assert!(flag.help_text.is_empty() && flag.validator.is_none() && flag.conflicts.is_empty());
+
+        let parsed = parse_command_line(&[flag], ["testcmd", "--verbose", "--verbose"].map(OsString::from).into_iter()).unwrap();
+        assert_eq!(parsed.option_count("verbose"), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "option 'quiet' takes no value, so it can't have a default")]
+    fn test_builder_default_value_on_flag_panics() {
+        let _ = OptionDefinition::new('q', "quiet").default_value("yes");
+    }
+
+    #[test]
+    fn test_closure_validator_captures_settings() {
+        let limit = 64u32;
+        let options = [OptionDefinition::new(NO_SHORT_FLAG, "width")
+            .value()
+            .validator(move |value: &OsStr| match value.to_str().and_then(|s| s.parse::<u32>().ok()) {
+                Some(n) if n <= limit => Ok(()),
+                _ => Err(ValidationError::new(format!("Width must be at most {}", limit))),
+            })];
+        let parse = |width: &str| parse_command_line(&options, ["testcmd", "--width", width].map(OsString::from).into_iter());
+
+        assert_eq!(parse("64").unwrap().option_value("width"), Some(OsStr::new("64")));
+        match parse("65").map_err(ParseError::without_argument_index) {
+            Err(ParseError::ValidationError(e)) => assert_eq!(e.to_string(), "Width must be at most 64"),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+        assert!(format!("{:?}", options[0]).contains("validator: Some(\"<validator>\")"));
+    }
+
+    #[test]
+    fn test_errors_report_argument_index() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('o', "output").value().help("Output file"));
+        options.push(OptionDefinition::new('m', "base64").help("Use base64"));
+
+        let error = parse(&options, &["-m", "--output", "out.txt", "input", "--bogus", "more"]).unwrap_err();
+        assert_eq!(error.argument_index(), Some(5));
+        assert_eq!(error.to_string(), "argument 5: Unknown option: --bogus");
+        assert_eq!(error.without_argument_index(), ParseError::UnknownOption("--bogus".to_string()));
+
+        assert_eq!(parse(&options, &["-m", "input", "-o"]), Err(ParseError::MissingValue("output".to_string()).at_argument(3)));
+        assert_eq!(parse(&options, &["-m", "-hx"]).unwrap_err().argument_index(), Some(2));
+
+        // Arguments from a response file report the `@FILE` argument
+        let path = scratch_path("argindex").with_extension("rsp");
+        std::fs::write(&path, "-m\n--bogus\n").unwrap();
+        let error = parse(&options, &["input", &format!("@{}", path.display()), "more"]).unwrap_err();
+        assert_eq!(error, ParseError::UnknownOption("--bogus".to_string()).at_argument(2));
+        std::fs::remove_file(&path).unwrap();
+
+        // Errors found once every argument is read have no index
+        let options = [OptionDefinition::new('o', "output").value().required()];
+        let error = parse_command_line(&options, [OsString::from("test-cmd")].into_iter()).unwrap_err();
+        assert_eq!(error.argument_index(), None);
+    }
+
+    #[test]
+    fn test_counting_writer_counts_bytes_and_lines() {
+        use std::io::Write;
+
+        let mut output = CountingWriter::new(Vec::new());
+        assert_eq!((output.bytes(), output.lines()), (0, 0));
+
+        output.write_all(b"begin 644 a.txt\n").unwrap();
+        output.write_all(b"partial").unwrap();
+        output.write_all(b" line\n`\nend\n").unwrap();
+        assert_eq!(output.bytes(), 35);
+        assert_eq!(output.lines(), 4);
+        assert_eq!(output.get_ref().len(), 35);
+
+        // Counts what the wrapped writer took, through any nesting
+        let mut indented = CountingWriter::new(IndentWriter::new(Vec::new(), 2));
+        write_uuencode_trailer(&mut indented, false).unwrap();
+        assert_eq!((indented.bytes(), indented.lines()), (6, 2));
+        assert_eq!(indented.into_inner().into_inner(), b"  `\n  end\n");
+    }
+
+    #[test]
+    fn test_warnings_abort_when_fatal() {
+        assert_eq!(Warnings::default().warn("odd input"), Ok(()));
+        assert_eq!(
+            Warnings { abort: true }.warn("odd input"),
+            Err("odd input (warning treated as an error by --abort-on-warning)".to_string())
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_set_output_mode() {
+        use std::os::unix::fs::PermissionsExt;
+        let path = scratch_path("mode");
+        let file = std::fs::File::create(&path).unwrap();
+        set_output_mode(&file, 0o755).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o7777, 0o755);
+        // Setuid and friends are dropped
+        set_output_mode(&file, 0o4640).unwrap();
+        assert_eq!(std::fs::metadata(&path).unwrap().permissions().mode() & 0o7777, 0o640);
+        std::fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_check_chmod_failure_is_fatal_unless_ignored() {
+        let failed = || Err(std::io::Error::from(std::io::ErrorKind::PermissionDenied));
+        let path = Path::new("out.bin");
+        assert_eq!(check_chmod(Ok(()), path, 0o755, false, Warnings::default()), Ok(()));
+        let error = check_chmod(failed(), path, 0o4755, false, Warnings::default()).unwrap_err();
+        assert!(error.starts_with("out.bin: cannot set mode 755: "), "{}", error);
+        // --ignore-chmod turns it into a warning, unless warnings are fatal
+        assert_eq!(check_chmod(failed(), path, 0o755, true, Warnings::default()), Ok(()));
+        assert!(check_chmod(failed(), path, 0o755, true, Warnings { abort: true }).is_err());
+    }
+
+    #[test]
+    fn test_lenient_parse_reports_every_problem() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new('o', "output").value().validator(validate_positive_integer).help("Count"));
+        options.push(OptionDefinition::new('m', "base64").help("Use base64"));
+        options.push(OptionDefinition::new('q', "quiet").conflicts("base64").help("Say nothing"));
+        let args = |args: &[&str]| std::iter::once("test-cmd").chain(args.iter().copied()).map(OsString::from).collect::<Vec<_>>();
+        let bad = args(&["--bogus", "-m", "--output", "zero", "in.txt", "-q", "-x", "out.txt"]);
+
+        let (parsed, errors) = parse_command_line_lenient(&options, bad.clone().into_iter());
+        assert_eq!(errors, vec![
+            ParseError::UnknownOption("--bogus".to_string()).at_argument(1),
+            ParseError::ValidationError(ValidationError::new("Not a valid positive integer".to_string())).at_argument(4),
+            ParseError::UnknownOption("-x".to_string()).at_argument(7),
+            ParseError::InvalidFlagCombination("--quiet cannot be combined with --base64".to_string()),
+        ]);
+        // Everything else was still parsed, minus the skipped tokens
+        assert!(parsed.is_option_set("base64") && parsed.is_option_set("quiet"));
+        assert!(!parsed.is_option_set("output"));
+        assert_eq!(parsed.arguments, vec![OsString::from("in.txt"), OsString::from("out.txt")]);
+
+        // The fail-fast parse stops at the first of them
+        assert_eq!(parse_command_line(&options, bad.into_iter()), Err(errors[0].clone()));
+
+        let (parsed, errors) = parse_command_line_lenient(&options, args(&["-m", "-o", "3"]).into_iter());
+        assert!(errors.is_empty());
+        assert_eq!(parsed.option_value("output"), Some(OsStr::new("3")));
+    }
+
+    #[test]
+    fn test_take_trace_flag() {
+        let mut args = vec![
+            OsString::from("test-cmd"),
+            OsString::from("--trace"),
+            OsString::from("file"),
+        ];
+        assert!(take_trace_flag(&mut args));
+        assert_eq!(args, vec![OsString::from("test-cmd"), OsString::from("file")]);
+
+        let mut args = vec![
+            OsString::from("test-cmd"),
+            OsString::from("--"),
+            OsString::from("--trace"),
+        ];
+        assert!(!take_trace_flag(&mut args));
+        assert_eq!(args.len(), 3);
+    }
+
+    #[test]
+    fn test_long_only_option() {
+        let mut options = standard_options();
+        options.push(OptionDefinition::new(NO_SHORT_FLAG, "verbose").help("Verbose output"));
+        options.push(OptionDefinition::new(NO_SHORT_FLAG, "quiet").help("Quiet output"));
+
+        let args = vec![
+            OsString::from("test-cmd"),
+            OsString::from("--verbose"),
+        ];
+
+        let result = parse_command_line(&options, args.into_iter()).unwrap();
+        assert!(result.is_option_set("verbose"));
+        assert!(!result.is_option_set("quiet"));
+
+        let help = generate_help("testcmd", "Test command", "[OPTIONS]", &options);
+        assert!(help.contains("      --verbose"));
+        assert!(!help.contains("-\0"));
+    }
+
+    #[test]
+    fn test_validate_positive_integer() {
+        assert!(validate_positive_integer(OsStr::new("42")).is_ok());
+        assert!(validate_positive_integer(OsStr::new("1")).is_ok());
+        assert!(validate_positive_integer(OsStr::new("0")).is_err());
+        assert!(validate_positive_integer(OsStr::new("-1")).is_err());
+        assert!(validate_positive_integer(OsStr::new("abc")).is_err());
+    }
+
+    #[test]
+    fn test_validate_integer_range() {
+        let validate = validate_integer_range(1, 64);
+        assert!(validate(OsStr::new("1")).is_ok());
+        assert!(validate(OsStr::new("45")).is_ok());
+        assert!(validate(OsStr::new("64")).is_ok());
+        assert_eq!(validate(OsStr::new("0")).unwrap_err().to_string(), "Value must be between 1 and 64");
+        assert_eq!(validate(OsStr::new("65")).unwrap_err().to_string(), "Value must be between 1 and 64");
+        assert_eq!(validate(OsStr::new("abc")).unwrap_err().to_string(), "Not a valid integer");
+        assert_eq!(validate(OsStr::new("")).unwrap_err().to_string(), "Not a valid integer");
+        assert!(validate_integer_range(-10, -1)(OsStr::new("-5")).is_ok());
+
+        // Used as an option's validator
+        let options = [OptionDefinition::new(NO_SHORT_FLAG, "width").value().validator(validate_integer_range(1, 64)).help("Width")];
+        let args = |width: &str| ["test-cmd", "--width", width].into_iter().map(OsString::from).collect::<Vec<_>>().into_iter();
+        assert!(parse_command_line(&options, args("64")).is_ok());
+        assert_eq!(
+            parse_command_line(&options, args("100")).map_err(ParseError::without_argument_index),
+            Err(ParseError::ValidationError(ValidationError::new("Value must be between 1 and 64".to_string())))
+        );
+    }
+
+    #[test]
+    fn test_validate_choice() {
+        let validate = validate_choice(&["base64", "uu", "xx"]);
+        assert!(validate(OsStr::new("base64")).is_ok());
+        assert!(validate(OsStr::new("xx")).is_ok());
+        assert_eq!(
+            validate(OsStr::new("yenc")).unwrap_err().to_string(),
+            "Invalid value 'yenc' (expected one of base64, uu, xx)"
+        );
+        // Exact and case-sensitive
+        assert!(validate(OsStr::new("UU")).is_err());
+        assert!(validate(OsStr::new("uu ")).is_err());
+        assert_eq!(
+            validate(OsStr::new("")).unwrap_err().to_string(),
+            "Invalid value '' (expected one of base64, uu, xx)"
+        );
+    }
+
+    #[test]
+    fn test_all_of_stops_at_first_failure() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+        let calls = Rc::new(Cell::new(0));
+        let counted = |result: Result<(), &'static str>| {
+            let calls = Rc::clone(&calls);
+            Box::new(move |_: &OsStr| {
+                calls.set(calls.get() + 1);
+                result.map_err(|message| ValidationError::new(message.to_string()))
+            }) as OptionValidator
+        };
+
+        let validate = all_of(vec![counted(Ok(())), counted(Err("second failed")), counted(Err("third failed"))]);
+        assert_eq!(validate(OsStr::new("x")).unwrap_err().to_string(), "second failed");
+        assert_eq!(calls.get(), 2, "the third validator must not run");
+
+        let validate = all_of(vec![validate_positive_integer, validate_octal_mode]);
+        assert!(validate(OsStr::new("644")).is_ok());
+        assert_eq!(validate(OsStr::new("0")).unwrap_err().to_string(), "Value must be greater than zero");
+        assert_eq!(validate(OsStr::new("9")).unwrap_err().to_string(), "Invalid octal mode '9'");
+        assert!(all_of::<OptionValidator>(Vec::new())(OsStr::new("anything")).is_ok());
+    }
+
+    #[test]
+    fn test_any_of_reports_every_failure() {
+        let validate = any_of(vec![validate_choice(&["auto"]), validate_integer_range(1, 64)]);
+        assert!(validate(OsStr::new("auto")).is_ok());
+        assert!(validate(OsStr::new("32")).is_ok());
+        assert_eq!(
+            validate(OsStr::new("100")).unwrap_err().to_string(),
+            "No accepted form matched: Invalid value '100' (expected one of auto); Value must be between 1 and 64"
+        );
+        assert!(any_of::<OptionValidator>(Vec::new())(OsStr::new("anything")).is_err());
+
+        // A combined validator is still a single option validator
+        let options = [OptionDefinition::new(NO_SHORT_FLAG, "width").value().validator(validate).help("Width")];
+        let parse = |width: &str| parse_command_line(&options, ["test-cmd", "--width", width].map(OsString::from).into_iter());
+        assert!(parse("auto").is_ok());
+        assert!(parse("0").is_err());
+    }
+
+    #[test]
+    fn test_validate_octal_mode() {
+        for mode in ["644", "0755", "777", "0", "0000"] {
+            assert!(validate_octal_mode(OsStr::new(mode)).is_ok(), "{}", mode);
+        }
+        assert_eq!(validate_octal_mode(OsStr::new("800")).unwrap_err().to_string(), "Invalid octal mode '800'");
+        assert_eq!(validate_octal_mode(OsStr::new("649")).unwrap_err().to_string(), "Invalid octal mode '649'");
+        assert_eq!(
+            validate_octal_mode(OsStr::new("1000")).unwrap_err().to_string(),
+            "Mode '1000' is out of range (0 to 0777)"
+        );
+        assert!(validate_octal_mode(OsStr::new("")).is_err());
+        assert!(validate_octal_mode(OsStr::new("u=rw")).is_err());
+        assert!(validate_octal_mode(OsStr::new("-644")).is_err());
+    }
+
+    #[test]
+    fn test_validate_readable_file() {
+        let dir = scratch_path("readable");
+        std::fs::create_dir_all(&dir).unwrap();
+        let file = dir.join("input.txt");
+        std::fs::write(&file, b"data").unwrap();
+
+        assert!(validate_readable_file(file.as_os_str()).is_ok());
+        assert_eq!(
+            validate_readable_file(dir.as_os_str()).unwrap_err().to_string(),
+            format!("Is a directory, not a file: {}", dir.display())
+        );
+        let missing = dir.join("missing.txt");
+        let message = validate_readable_file(missing.as_os_str()).unwrap_err().to_string();
+        assert!(message.starts_with(&format!("Cannot read {}: ", missing.display())), "{}", message);
+        assert!(message.contains("No such file or directory") || message.contains("cannot find"), "{}", message);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_writable_path() {
+        let dir = scratch_path("writable");
+        std::fs::create_dir_all(&dir).unwrap();
+        let existing = dir.join("existing.uue");
+        std::fs::write(&existing, b"").unwrap();
+
+        assert!(validate_writable_path(existing.as_os_str()).is_ok());
+        assert!(validate_writable_path(dir.join("new.uue").as_os_str()).is_ok());
+        assert!(!dir.join("new.uue").exists(), "the check must not create the file");
+        assert!(validate_writable_path(OsStr::new("relative-new.uue")).is_ok());
+        assert_eq!(
+            validate_writable_path(dir.join("missing/new.uue").as_os_str()).unwrap_err().to_string(),
+            format!("Parent directory does not exist: {}", dir.join("missing").display())
+        );
+        assert_eq!(
+            validate_writable_path(dir.as_os_str()).unwrap_err().to_string(),
+            format!("Is a directory: {}", dir.display())
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    #[ignore = "root bypasses file permissions; run with --ignored as an unprivileged user"]
+    fn test_validate_writable_path_reports_permission_denied() {
+        use std::os::unix::fs::PermissionsExt;
+        let locked = scratch_path("locked");
+        std::fs::create_dir_all(&locked).unwrap();
+        std::fs::write(locked.join("existing.uue"), b"").unwrap();
+        std::fs::set_permissions(locked.join("existing.uue"), std::fs::Permissions::from_mode(0o444)).unwrap();
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o555)).unwrap();
+        let denied = std::fs::File::create(locked.join("probe")).is_err();
+        let new_file = validate_writable_path(locked.join("new.uue").as_os_str());
+        let existing = validate_writable_path(locked.join("existing.uue").as_os_str());
+        std::fs::set_permissions(&locked, std::fs::Permissions::from_mode(0o755)).unwrap();
+        std::fs::remove_dir_all(&locked).unwrap();
+
+        assert!(denied, "file permissions are not enforced for this user");
+        assert_eq!(
+            new_file.unwrap_err().to_string(),
+            format!("Permission denied: cannot create {} in {}", locked.join("new.uue").display(), locked.display())
+        );
+        assert_eq!(
+            existing.unwrap_err().to_string(),
+            format!("Permission denied: {}", locked.join("existing.uue").display())
+        );
+    }
+
+    #[test]
+    fn test_validate_existing_file() {
+        // Test with a file that should exist (current dir)
+        assert!(validate_existing_file(OsStr::new(".")).is_ok());
+        
+        // Test with a file that shouldn't exist
+        assert!(validate_existing_file(OsStr::new("/nonexistent/file/path")).is_err());
+    }
+
+    #[test]
+    fn test_validate_version_mode() {
+        // Test valid modes
         assert!(validate_version_mode(OsStr::new("version")).is_ok());
         assert!(validate_version_mode(OsStr::new("v")).is_ok());
         assert!(validate_version_mode(OsStr::new("copyright")).is_ok());
@@ -900,6 +4301,22 @@ mod tests {
         assert!(validate_version_mode(OsStr::new("help")).is_err());
     }
 
+    #[test]
+    fn test_validate_utf8() {
+        assert!(validate_utf8(OsStr::new("plain ascii")).is_ok());
+        assert!(validate_utf8(OsStr::new("ünïcødé ✓")).is_ok());
+        assert!(validate_utf8(OsStr::new("")).is_ok());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_validate_utf8_rejects_invalid_bytes() {
+        use std::os::unix::ffi::OsStrExt;
+        let invalid = OsStr::from_bytes(&[b'n', b'a', 0xff, b'm', b'e']);
+        let err = validate_utf8(invalid).unwrap_err();
+        assert!(err.to_string().contains("Invalid UTF-8"));
+    }
+
     #[test]
     fn test_validate_file_path() {
         // Test valid paths
@@ -920,4 +4337,11 @@ mod tests {
         let ok_path = "a".repeat(100);
         assert!(validate_file_path(OsStr::new(&ok_path)).is_ok());
     }
+
+    #[test]
+    fn test_license_copy_matches_workspace_license() {
+        // The crate carries its own copy so that it can be packaged on its own
+        let workspace = std::fs::read_to_string(concat!(env!("CARGO_MANIFEST_DIR"), "/../LICENSE")).unwrap();
+        assert_eq!(LICENSE_TEXT, workspace);
+    }
 }
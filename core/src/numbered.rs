@@ -0,0 +1,135 @@
+// Numbered data lines (--numbered-lines) for lossy transports
+// Each line after the begin line, trailer included, is written as
+// `NNNNNN CCCC <line>`: a 1-based sequence number within the block (at least
+// six digits, more once a block passes 999999 lines) and a Fletcher-16
+// checksum of the line, so a dropped, reordered or damaged line is detected
+// when decoding. As the trailer is numbered too, losing the last data lines
+// shows up as a gap before it.
+
+use std::io::{self, Write};
+
+/// Fletcher-16 checksum of one data line (without its prefix or newline)
+fn line_checksum(line: &[u8]) -> u16 {
+    let (mut sum1, mut sum2) = (0u16, 0u16);
+    for &b in line {
+        sum1 = (sum1 + b as u16) % 255;
+        sum2 = (sum2 + sum1) % 255;
+    }
+    (sum2 << 8) | sum1
+}
+
+/// Writer that prefixes every line with its sequence number and checksum.
+/// Wrap the data lines and trailer of a block; the begin line (and any
+/// comments before it) stays unnumbered.
+pub struct NumberedLineWriter<W: Write> {
+    inner: W,
+    sequence: u64,
+    line: Vec<u8>,
+}
+
+impl<W: Write> NumberedLineWriter<W> {
+    /// Wraps `inner`, numbering lines from 1
+    pub fn new(inner: W) -> Self {
+        Self { inner, sequence: 0, line: Vec::new() }
+    }
+
+    /// Returns the wrapped writer. A trailing partial line is discarded, but
+    /// the encoders always end their output with a newline.
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for NumberedLineWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        // The checksum goes before the data, so each line is held until complete
+        for part in buf.split_inclusive(|&b| b == b'\n') {
+            let Some(data) = part.strip_suffix(b"\n") else {
+                self.line.extend_from_slice(part);
+                continue;
+            };
+            self.line.extend_from_slice(data);
+            self.sequence += 1;
+            write!(self.inner, "{:06} {:04x} ", self.sequence, line_checksum(&self.line))?;
+            self.inner.write_all(&self.line)?;
+            self.inner.write_all(b"\n")?;
+            self.line.clear();
+        }
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Checks the `NNNNNN CCCC ` prefix of a numbered line against the
+/// `expected` sequence number and the checksum of the data after it. Returns
+/// the length of the prefix.
+pub(crate) fn check_line_number(line: &[u8], expected: u64) -> Result<usize, String> {
+    let malformed = || "missing or malformed line number prefix".to_string();
+    let digits = line.iter().position(|&b| b == b' ').ok_or_else(malformed)?;
+    let prefix_len = digits + 6;
+    if digits < 6 || line.len() < prefix_len || line[prefix_len - 1] != b' ' {
+        return Err(malformed());
+    }
+    let field = |range: std::ops::Range<usize>| std::str::from_utf8(&line[range]).ok();
+    let sequence = field(0..digits)
+        .filter(|s| s.bytes().all(|b| b.is_ascii_digit()))
+        .and_then(|s| s.parse::<u64>().ok())
+        .ok_or_else(malformed)?;
+    let checksum = field(digits + 1..digits + 5)
+        .and_then(|s| u16::from_str_radix(s, 16).ok())
+        .ok_or_else(malformed)?;
+
+    if sequence != expected {
+        return Err(format!(
+            "expected line number {}, found {} (a line was dropped or reordered)", expected, sequence
+        ));
+    }
+    if line_checksum(&line[prefix_len..]) != checksum {
+        return Err(format!("checksum mismatch on numbered line {}", sequence));
+    }
+    Ok(prefix_len)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_lines_are_numbered_and_checked() {
+        let mut out = NumberedLineWriter::new(Vec::new());
+        out.write_all(b"first li").unwrap();
+        out.write_all(b"ne\nsecond line\n").unwrap();
+        let text = out.into_inner();
+
+        let lines: Vec<&[u8]> = text.split(|&b| b == b'\n').filter(|l| !l.is_empty()).collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[0].starts_with(b"000001 "));
+        assert_eq!(&lines[0][12..], b"first line");
+        assert_eq!(check_line_number(lines[0], 1), Ok(12));
+        assert_eq!(check_line_number(lines[1], 2), Ok(12));
+
+        assert!(check_line_number(lines[1], 1).unwrap_err().contains("dropped or reordered"));
+        let mut damaged = lines[0].to_vec();
+        *damaged.last_mut().unwrap() = b'X';
+        assert!(check_line_number(&damaged, 1).unwrap_err().contains("checksum mismatch"));
+        assert!(check_line_number(b"M86%C", 1).is_err());
+        assert!(check_line_number(b"00001 0000 ", 1).is_err());
+    }
+
+    #[test]
+    fn test_sequence_numbers_widen_past_six_digits() {
+        let mut out = NumberedLineWriter::new(Vec::new());
+        out.sequence = 999_998;
+        out.write_all(b"last six\nfirst seven\n").unwrap();
+        let text = out.into_inner();
+
+        let lines: Vec<&[u8]> = text.split(|&b| b == b'\n').filter(|l| !l.is_empty()).collect();
+        assert!(lines[0].starts_with(b"999999 ") && lines[1].starts_with(b"1000000 "));
+        assert_eq!(check_line_number(lines[0], 999_999), Ok(12));
+        assert_eq!(check_line_number(lines[1], 1_000_000), Ok(13));
+        assert_eq!(&lines[1][13..], b"first seven");
+    }
+}
@@ -0,0 +1,121 @@
+// Progress reporting support
+// Time is read through the `Clock` trait so throttling can be tested without sleeping
+
+use std::cell::Cell;
+use std::time::{Duration, Instant};
+
+/// Source of monotonic time for time-dependent output such as progress reports
+pub trait Clock {
+    /// Returns the time elapsed since an arbitrary, fixed starting point
+    fn now(&self) -> Duration;
+}
+
+/// Clock backed by the system's monotonic clock
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Default for SystemClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+}
+
+/// Clock that only moves when told to, for deterministic tests
+#[derive(Default)]
+pub struct ManualClock {
+    now: Cell<Duration>,
+}
+
+impl ManualClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Moves the clock forward by `step`
+    pub fn advance(&self, step: Duration) {
+        self.now.set(self.now.get() + step);
+    }
+}
+
+impl Clock for ManualClock {
+    fn now(&self) -> Duration {
+        self.now.get()
+    }
+}
+
+impl<C: Clock + ?Sized> Clock for &C {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// Limits how often progress is reported: at most once per `interval`
+pub struct ProgressThrottle<C: Clock> {
+    clock: C,
+    interval: Duration,
+    last_emit: Option<Duration>,
+}
+
+impl<C: Clock> ProgressThrottle<C> {
+    pub fn new(clock: C, interval: Duration) -> Self {
+        Self { clock, interval, last_emit: None }
+    }
+
+    /// Returns true if a progress report should be emitted now. The first
+    /// call always emits; later calls emit once `interval` has passed.
+    pub fn should_emit(&mut self) -> bool {
+        let now = self.clock.now();
+        match self.last_emit {
+            Some(last) if now.saturating_sub(last) < self.interval => false,
+            _ => {
+                self.last_emit = Some(now);
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_throttle_with_manual_clock() {
+        let clock = ManualClock::new();
+        let mut throttle = ProgressThrottle::new(&clock, Duration::from_secs(1));
+
+        // Ten updates 300ms apart: emits at 0ms, 1200ms and 2400ms
+        let mut emitted = 0;
+        for _ in 0..10 {
+            if throttle.should_emit() {
+                emitted += 1;
+            }
+            clock.advance(Duration::from_millis(300));
+        }
+        assert_eq!(emitted, 3);
+    }
+
+    #[test]
+    fn test_throttle_without_time_passing() {
+        let clock = ManualClock::new();
+        let mut throttle = ProgressThrottle::new(&clock, Duration::from_millis(500));
+
+        assert!(throttle.should_emit());
+        assert!(!throttle.should_emit());
+        clock.advance(Duration::from_millis(500));
+        assert!(throttle.should_emit());
+    }
+}
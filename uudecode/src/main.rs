@@ -1,64 +1,882 @@
-use std::ffi::OsString;
+use std::cell::Cell;
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Read, Write};
+use std::path::{Component, Path, PathBuf};
 use std::process;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use sharutils_core::{
-    OptionDefinition, standard_options, parse_command_line, 
-    generate_help, validate_version_mode, validate_file_path,
-    handle_version_output, handle_more_help, debug_print_parsed_command,
-    print_config_file_options
+    COMPLETION_SHELLS, generate_bash_completion, validate_choice, ValueHint,
+    OptionDefinition, standard_options, take_trace_flag, Parser, ParseError, ParsedCommand,
+    generate_help, emit_help, HelpReason, validate_version_mode, validate_file_path, validate_writable_path,
+    handle_version_output, handle_license_output, handle_more_help, print_config_file_options,
+    load_config_file, EffectiveConfig, input_error,
+    Base64Variant, DecodeError, DecodeOptions, decode_data_uri, DecodedBlockMeta, Decoder, Encoding, PartSequence, ValidationError,
+    PrefilterPipeline, Crc32, run_self_test, split_os, validate_positive_integer, DEFAULT_MAX_HEADER_SCAN, NO_SHORT_FLAG,
+    STDIN_ARGUMENT, Warnings, AtomicFile, is_stdin_argument, set_output_mode, check_chmod,
 };
+#[cfg(debug_assertions)]
+use sharutils_core::debug_print_parsed_command;
+
+/// Validates a --base64-variant value
+fn validate_base64_variant(value: &OsStr) -> Result<(), ValidationError> {
+    value.to_str()
+        .ok_or_else(|| "Invalid UTF-8 in base64 variant".to_string())
+        .and_then(|v| v.parse::<Base64Variant>())
+        .map(|_| ())
+        .map_err(ValidationError::new)
+}
+
+/// Validates an --input-format value
+fn validate_input_format(value: &OsStr) -> Result<(), ValidationError> {
+    value.to_str()
+        .ok_or_else(|| "Invalid UTF-8 in input format".to_string())
+        .and_then(|v| v.parse::<Encoding>())
+        .map(|_| ())
+        .map_err(ValidationError::new)
+}
+
+/// Validates a --rename template: `%n` and `%%` may appear, `%i` must
+fn validate_rename_template(value: &OsStr) -> Result<(), ValidationError> {
+    let template = value.to_str()
+        .ok_or_else(|| ValidationError::new("Invalid UTF-8 in rename template".to_string()))?;
+    let mut has_index = false;
+    let mut chars = template.chars();
+    while let Some(c) = chars.next() {
+        if c != '%' {
+            continue;
+        }
+        match chars.next() {
+            Some('i') => has_index = true,
+            Some('n' | '%') => {}
+            other => {
+                let directive = other.map_or("%".to_string(), |c| format!("%{}", c));
+                return Err(ValidationError::new(format!(
+                    "Unknown directive '{}' in rename template (expected %n, %i or %%)", directive
+                )));
+            }
+        }
+    }
+    if !has_index {
+        return Err(ValidationError::new("Rename template must contain %i".to_string()));
+    }
+    Ok(())
+}
+
+/// Fills in a (validated) --rename template for the `index`th renaming of
+/// the file name `name`
+fn expand_rename_template(template: &str, name: &OsStr, index: usize) -> OsString {
+    let mut expanded = OsString::new();
+    let mut rest = template;
+    while let Some(pos) = rest.find('%') {
+        expanded.push(&rest[..pos]);
+        match rest[pos + 1..].chars().next() {
+            Some('n') => expanded.push(name),
+            Some('i') => expanded.push(index.to_string()),
+            _ => expanded.push("%"),
+        }
+        rest = &rest[pos + 2..];
+    }
+    expanded.push(rest);
+    expanded
+}
+
+/// Non-fatal conditions that `--exit-zero-on` can exempt from a failing exit status
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum ExitCondition {
+    /// An input contained no encoded block
+    NoData,
+    /// An output file was left alone rather than overwritten
+    ClobberSkipped,
+}
+
+impl std::str::FromStr for ExitCondition {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "no-data" => Ok(ExitCondition::NoData),
+            "clobber-skipped" => Ok(ExitCondition::ClobberSkipped),
+            _ => Err(format!("Unknown exit condition '{}' (expected no-data or clobber-skipped)", s)),
+        }
+    }
+}
+
+/// Parses a comma-separated `--exit-zero-on` list
+fn parse_exit_conditions(value: &OsStr) -> Result<HashSet<ExitCondition>, String> {
+    split_os(value, b',')
+        .iter()
+        .map(|condition| condition.to_str()
+            .ok_or_else(|| format!("Invalid UTF-8 in exit condition {:?}", condition))?
+            .parse())
+        .collect()
+}
+
+/// Validates an --exit-zero-on value
+fn validate_exit_conditions(value: &OsStr) -> Result<(), ValidationError> {
+    parse_exit_conditions(value).map(|_| ()).map_err(ValidationError::new)
+}
+
+/// Returns the `--exit-zero-on` condition an input's decode error falls
+/// under, if any
+fn exit_condition(e: &(dyn std::error::Error + 'static)) -> Option<ExitCondition> {
+    match e.downcast_ref::<DecodeError>() {
+        Some(DecodeError::NoBeginLine) => Some(ExitCondition::NoData),
+        _ => None,
+    }
+}
 
 /// Returns uudecode-specific command line options
 fn uudecode_options() -> Vec<OptionDefinition> {
-    vec![
-        OptionDefinition {
-            flag: 'o',
-            name: "output-file".to_string(),
-            has_value: true,
-            default_value: None,
-            validator: Some(validate_file_path),
-            help_text: "Direct output to file".to_string(),
-        },
-        OptionDefinition {
-            flag: 'c',
-            name: "ignore-chmod".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Ignore fchmod(3P) errors".to_string(),
-        },
-        OptionDefinition {
-            flag: 'v',
-            name: "version".to_string(),
-            has_value: true,
-            default_value: Some(OsString::from("copyright")),  // Only when -v is specified without value
-            validator: Some(validate_version_mode),
-            help_text: "Output version information and exit [=MODE]".to_string(),
-        },
-        OptionDefinition {
-            flag: '!',
-            name: "more-help".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Extended usage information passed through pager".to_string(),
-        },
-        OptionDefinition {
-            flag: 'R',
-            name: "save-opts".to_string(),
-            has_value: true,
-            default_value: None,  // No automatic default - only when explicitly specified
-            validator: Some(validate_file_path),
-            help_text: "Save the option state to a config file [=FILE]".to_string(),
-        },
-        OptionDefinition {
-            flag: 'r',
-            name: "load-opts".to_string(),
-            has_value: true,
-            default_value: None,
-            validator: Some(validate_file_path),
-            help_text: "Load options from the config file FILE".to_string(),
-        },
-    ]
+    #[allow(unused_mut)]
+    let mut options = vec![
+        OptionDefinition::new('o', "output-file")
+            .value()
+            .validator(validate_writable_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Direct output to file"),
+        OptionDefinition::new('c', "ignore-chmod")
+            .help("Ignore fchmod(3P) errors"),
+        OptionDefinition::new('v', "version")
+            .optional_value()  // Bare -v means the default (copyright) mode
+            .validator(validate_version_mode)
+            .help("Output version information and exit [=MODE]"),
+        OptionDefinition::new('!', "more-help")
+            .help("Extended usage information passed through pager"),
+        OptionDefinition::new('R', "save-opts")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Save the option state to a config file [=FILE]"),
+        OptionDefinition::new('r', "load-opts")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Load options from the config file FILE"),
+        OptionDefinition::new(NO_SHORT_FLAG, "strip-indent")
+            .help("Strip the begin line's indentation from every line of the block"),
+        OptionDefinition::new(NO_SHORT_FLAG, "strict")
+            .help("Treat questionable input (e.g. duplicate output names, bad line lengths) as an error"),
+        OptionDefinition::new(NO_SHORT_FLAG, "strict-headers")
+            .help("Reject begin lines without a valid octal mode instead of assuming 644"),
+        OptionDefinition::new(NO_SHORT_FLAG, "allow-unsafe-names")
+            .help("Write to header names that are absolute or contain '..' components"),
+        OptionDefinition::new(NO_SHORT_FLAG, "rename-duplicates")
+            .help("Append .1, .2, ... to output names already written in this run"),
+        OptionDefinition::new(NO_SHORT_FLAG, "rename")
+            .value()
+            .validator(validate_rename_template)
+            .conflicts("rename-duplicates")
+            .help("Rename outputs already written in this run by TEMPLATE (%n name, %i index), skipping existing files"),
+        OptionDefinition::new(NO_SHORT_FLAG, "base64-variant")
+            .value()
+            .validator(validate_base64_variant)
+            .help("Base64 alphabet to accept: standard, url or any (default)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "input-format")
+            .value()
+            .validator(validate_input_format)
+            .help("Require every block to be uu or base64 and reject other headers (a check, not a speedup)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "no-header")
+            .requires("input-format")
+            .help("With --input-format, ignore the format announced by each header"),
+        OptionDefinition::new(NO_SHORT_FLAG, "data-uri")
+            .conflicts("input-format")
+            .conflicts("no-header")
+            .conflicts("numbered-lines")
+            .conflicts("update")
+            .help("Decode each input as a 'data:...;base64,' URI, written to --output-file or 'data'"),
+        OptionDefinition::new(NO_SHORT_FLAG, "probe")
+            .conflicts("output-file")
+            .conflicts("output-dir")
+            .conflicts("concat")
+            .conflicts("update")
+            .conflicts("data-uri")
+            .help("Print each block's name, mode, encoding, size, line count and CRC-32 as JSON instead of decoding"),
+        OptionDefinition::new(NO_SHORT_FLAG, "max-header-scan")
+            .value()
+            .validator(validate_positive_integer)
+            .help("Give up if no begin line is found within the first N bytes (default 1 GiB)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "no-trailer-check")
+            .help("Keep the data of a block cut off before its end line, with a warning"),
+        OptionDefinition::new(NO_SHORT_FLAG, "numbered-lines")
+            .help("Expect and check the sequence numbers written by uuencode --numbered-lines"),
+        OptionDefinition::new(NO_SHORT_FLAG, "exit-zero-on")
+            .value()
+            .validator(validate_exit_conditions)
+            .help("Exit 0 despite the listed conditions (no-data, clobber-skipped)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "preserve-time")
+            .help("Set each output file's modification time from its '# mtime:' comment"),
+        OptionDefinition::new('u', "update")
+            .help("Only overwrite an existing output file if the decoded content differs"),
+        OptionDefinition::new('n', "no-clobber")
+            .help("Never overwrite an existing output file; skip the block and exit nonzero"),
+        OptionDefinition::new('k', "continue-on-error")
+            .help("Report a block that fails to decode and carry on with the next one"),
+        OptionDefinition::new(NO_SHORT_FLAG, "dir")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::DirPath)
+            .conflicts("stdin-list")
+            .help("Decode every .uue, .uu, .b64 and .base64 file in directory DIR"),
+        OptionDefinition::new(NO_SHORT_FLAG, "output-dir")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::DirPath)
+            .help("Write decoded files named by their headers into directory DIR"),
+        OptionDefinition::new(NO_SHORT_FLAG, "concat")
+            .requires("output-file")
+            .conflicts("output-dir")
+            .conflicts("update")
+            .help("Write every block, in order, to the --output-file, ignoring header names"),
+        OptionDefinition::new(NO_SHORT_FLAG, "stdin-list")
+            .help("Read the names of the files to decode from stdin, one per line"),
+        OptionDefinition::new(NO_SHORT_FLAG, "show-config")
+            .help("Print every option's effective value and where it came from, then exit"),
+        OptionDefinition::new(NO_SHORT_FLAG, "self-test")
+            .help("Run an internal encode/decode roundtrip and exit"),
+        OptionDefinition::new(NO_SHORT_FLAG, "abort-on-warning")
+            .help("Treat every warning as an error and exit nonzero"),
+        OptionDefinition::new(NO_SHORT_FLAG, "dump-completion")
+            .value()
+            .validator(validate_choice(&COMPLETION_SHELLS))
+            .hidden()
+            .help("Print a completion script for SHELL (bash) and exit"),
+    ];
+    #[cfg(feature = "sha256")]
+    options.push(OptionDefinition::new(NO_SHORT_FLAG, "verify-sha256")
+        .help("Require each block's '# sha256:' line and check the decoded data against it"));
+    options
+}
+
+/// Post-parse hook: `--rename-duplicates` is shorthand for `--rename=%n.%i`.
+/// The two conflict, and a config file's one is dropped for the command
+/// line's, so any `--rename` here is the one this hook set.
+fn expand_rename_duplicates(parsed: &mut ParsedCommand) -> Result<(), ParseError> {
+    if parsed.is_option_set("rename-duplicates") {
+        parsed.options.insert("rename".to_string(), Some(OsString::from("%n.%i")));
+    }
+    Ok(())
+}
+
+/// Tracks the output files written during one run, so that a block reusing
+/// an earlier block's name doesn't silently overwrite its output
+struct OutputNames {
+    seen: HashSet<PathBuf>,
+    strict: bool,
+    /// Template for renaming a name already used (`%n.%i` for
+    /// `--rename-duplicates`)
+    rename: Option<String>,
+}
+
+impl OutputNames {
+    /// Returns the path the next block should be written to, reporting
+    /// (or renaming around) any name already used earlier in this run, and
+    /// whether it was renamed. A renamed block never replaces a file that is
+    /// already on disk (a `same.txt.1` from an earlier run), so names taken
+    /// there are skipped too, and the caller creates it with `create_new`.
+    fn resolve(&mut self, name: PathBuf, warnings: Warnings) -> Result<(PathBuf, bool), String> {
+        if self.seen.insert(name.clone()) {
+            return Ok((name, false));
+        }
+
+        if let Some(template) = &self.rename {
+            let file_name = name.file_name().unwrap_or(name.as_os_str());
+            let renamed = (1..)
+                .map(|i| name.with_file_name(expand_rename_template(template, file_name, i)))
+                .find(|candidate| !self.seen.contains(candidate) && candidate.symlink_metadata().is_err())
+                .unwrap();
+            self.seen.insert(renamed.clone());
+            warnings.warn(format!("duplicate output name {:?}, writing to {:?}", name, renamed))?;
+            return Ok((renamed, true));
+        }
+
+        if self.strict {
+            return Err(format!("duplicate output name {:?}", name));
+        }
+
+        warnings.warn(format!("duplicate output name {:?} overwrites an earlier block", name))?;
+        Ok((name, false))
+    }
+}
+
+/// Where the data of the block being decoded goes
+enum BlockOutput {
+    /// A file created for this block, removed again if the block fails
+    File { output: BufWriter<File>, path: PathBuf },
+    /// `--concat`: the shared output file, cut back to its length at the
+    /// `start` of the block if the block fails
+    Appended { output: BufWriter<File>, start: u64 },
+    /// `-o -`, `-o /dev/stdout` or a `/dev/stdout` header name; never
+    /// chmod'ed or timestamped
+    Stdout(BufWriter<io::Stdout>),
+    /// `-n` with an existing target: the block is decoded and thrown away
+    Skipped,
+    /// `-u` with an existing target: written to a temporary file beside it,
+    /// which only replaces the target (given `mode`) once the block is
+    /// complete, and only if its content differs
+    Pending { target: PathBuf, output: BufWriter<AtomicFile>, mode: OutputMode },
+}
+
+impl BlockOutput {
+    fn write_all(&mut self, chunk: &[u8]) -> io::Result<()> {
+        match self {
+            BlockOutput::File { output, .. } | BlockOutput::Appended { output, .. } => output.write_all(chunk),
+            BlockOutput::Stdout(output) => output.write_all(chunk),
+            BlockOutput::Skipped => Ok(()),
+            BlockOutput::Pending { output, .. } => output.write_all(chunk),
+        }
+    }
+
+    /// Completes the output file, setting its modification time to `mtime`
+    /// if given
+    fn finish(self, mtime: Option<SystemTime>) -> io::Result<()> {
+        let file = match self {
+            BlockOutput::File { output, .. } | BlockOutput::Appended { output, .. } => {
+                output.into_inner().map_err(|e| e.into_error())?
+            }
+            BlockOutput::Stdout(mut output) => return output.flush(),
+            BlockOutput::Skipped => return Ok(()),
+            BlockOutput::Pending { target, output, mode } => {
+                let replacement = output.into_inner().map_err(|e| e.into_error())?;
+                if same_content(replacement.tmp_path(), &target).unwrap_or(false) {
+                    // Dropping the replacement removes its temporary file
+                    eprintln!("uudecode: {}: unchanged", target.display());
+                    return Ok(());
+                }
+                let in_target = |e: io::Error| io::Error::other(format!("{}: {}", target.display(), e));
+                let file = File::options().write(true).open(replacement.tmp_path()).map_err(in_target)?;
+                mode.apply(&file)?;
+                if let Some(mtime) = mtime {
+                    file.set_modified(mtime)?;
+                }
+                return replacement.commit().map_err(in_target);
+            }
+        };
+        match mtime {
+            Some(mtime) => file.set_modified(mtime),
+            None => Ok(()),
+        }
+    }
+
+    /// Throws away what a failed block wrote, so that it leaves no partial
+    /// output behind. Best effort: the block's own error is what gets
+    /// reported.
+    fn abort(self) {
+        match self {
+            BlockOutput::File { output, path } => {
+                drop(output.into_parts());
+                let _ = fs::remove_file(path);
+            }
+            BlockOutput::Appended { output, start } => {
+                let (file, _) = output.into_parts();
+                let _ = file.set_len(start);
+            }
+            BlockOutput::Stdout(mut output) => {
+                let _ = output.flush();
+            }
+            // Dropping the replacement removes its temporary file and leaves
+            // the target as it was
+            BlockOutput::Skipped | BlockOutput::Pending { .. } => {}
+        }
+    }
+}
+
+/// Whether the files at `a` and `b` hold the same bytes, compared a buffer
+/// at a time
+fn same_content(a: &Path, b: &Path) -> io::Result<bool> {
+    if fs::metadata(a)?.len() != fs::metadata(b)?.len() {
+        return Ok(false);
+    }
+    let (mut a, mut b) = (BufReader::new(File::open(a)?), BufReader::new(File::open(b)?));
+    loop {
+        let (chunk_a, chunk_b) = (a.fill_buf()?, b.fill_buf()?);
+        let len = chunk_a.len().min(chunk_b.len());
+        if len == 0 {
+            return Ok(chunk_a.len() == chunk_b.len());
+        }
+        if chunk_a[..len] != chunk_b[..len] {
+            return Ok(false);
+        }
+        a.consume(len);
+        b.consume(len);
+    }
+}
+
+/// The permissions from a block's header, for the file it is written to
+struct OutputMode {
+    path: PathBuf,
+    mode: u32,
+    /// `-c` (or `POSIXLY_CORRECT`): only warn if the mode cannot be set
+    ignore_errors: bool,
+    warnings: Warnings,
+}
+
+impl OutputMode {
+    fn apply(&self, file: &File) -> io::Result<()> {
+        check_chmod(set_output_mode(file, self.mode), &self.path, self.mode, self.ignore_errors, self.warnings)
+            .map_err(io::Error::other)
+    }
+}
+
+/// The block currently being decoded
+struct OpenBlock {
+    output: BlockOutput,
+    /// Modification time to give the output once it is complete
+    mtime: Option<SystemTime>,
+    /// Stages named by the block's `# prefilter:` comment, undone on the way out
+    prefilter: PrefilterPipeline,
+}
+
+impl OpenBlock {
+    fn write_all(&mut self, chunk: &[u8]) -> io::Result<()> {
+        if self.prefilter.is_empty() {
+            return self.output.write_all(chunk);
+        }
+        let restored = self.prefilter.revert(chunk)?;
+        self.output.write_all(&restored)
+    }
+
+    fn finish(mut self) -> io::Result<()> {
+        let tail = self.prefilter.finish_revert()?;
+        self.output.write_all(&tail)?;
+        self.output.finish(self.mtime)
+    }
+
+    fn abort(self) {
+        self.output.abort();
+    }
+}
+
+/// Settings and state shared by every input decoded in one run
+struct DecodeRun<'a> {
+    output_file: Option<&'a OsStr>,
+    /// Directory that header-named outputs are written into
+    output_dir: Option<&'a OsStr>,
+    /// `--concat`: append every block to `output_file` (created empty
+    /// before decoding starts)
+    concat: bool,
+    options: DecodeOptions,
+    names: OutputNames,
+    parts: PartSequence,
+    /// `-u`: leave existing output files alone when their content is unchanged
+    update: bool,
+    /// `-n`: never overwrite an existing output file
+    no_clobber: bool,
+    /// Blocks not written because of `no_clobber`
+    clobber_skipped: usize,
+    /// Apply the `# mtime:` comment of each block to its output file
+    preserve_time: bool,
+    /// `-k`: report a failed block and carry on with the next one
+    continue_on_error: bool,
+    /// Blocks skipped because of errors under `continue_on_error`
+    failures: usize,
+    /// `--data-uri`: each input is a `data:` URI rather than encoded blocks
+    data_uri: bool,
+    /// `-c` (or `POSIXLY_CORRECT`): warn instead of failing when an output's
+    /// mode cannot be set
+    ignore_chmod: bool,
+    /// Write to absolute header names and names with `..` components
+    allow_unsafe_names: bool,
+    warnings: Warnings,
+}
+
+/// Decodes every block found in `input`, writing each one to `output_file`
+/// if given or otherwise to the name in its header
+fn decode_input<R: BufRead>(
+    input: R,
+    source: &str,
+    run: &mut DecodeRun,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if run.data_uri {
+        return decode_data_uri_input(input, run);
+    }
+    let warnings = run.warnings;
+    let DecodeRun {
+        output_file, output_dir, concat, names, parts, update, no_clobber, clobber_skipped, preserve_time, ignore_chmod,
+        allow_unsafe_names, ..
+    } = run;
+    let mut decoder = Decoder::new(input, run.options.clone());
+    let mut blocks = 0;
+
+    // Each block goes to its own file, opened when the block starts. A block
+    // that failed under -k is set aside with `abort` rather than finished.
+    let mut current: Option<OpenBlock> = None;
+    let failed = Cell::new(false);
+    let mut sink = |meta: &DecodedBlockMeta, chunk: &[u8]| {
+        if chunk.is_empty() {
+            let previous_failed = failed.replace(false);
+            if let Some(finished) = current.take() {
+                if previous_failed { finished.abort() } else { finished.finish()? }
+            }
+            // The comment comes from the input, so it may name a time the
+            // system can't represent
+            let mtime = match meta.header.mtime.filter(|_| *preserve_time) {
+                Some(seconds) => {
+                    let mtime = UNIX_EPOCH.checked_add(Duration::from_secs(seconds));
+                    if mtime.is_none() {
+                        warnings.warn(format!("{}: mtime {} is out of range, not applied", meta.header.name, seconds))
+                            .map_err(io::Error::other)?;
+                    }
+                    mtime
+                }
+                None => None,
+            };
+            let prefilter = match &meta.header.prefilter {
+                Some(names) => names.parse::<PrefilterPipeline>()
+                    .map_err(|e| io::Error::other(format!("{}: {}", meta.header.name, e)))?,
+                None => PrefilterPipeline::default(),
+            };
+            if meta.header.mode_defaulted {
+                warnings.warn(format!("{}: no valid mode in header, assuming 644", meta.header.name))
+                    .map_err(io::Error::other)?;
+            }
+            if let Some(part) = meta.header.part {
+                parts.check(part).map_err(io::Error::other)?;
+            }
+            if writes_to_stdout((*output_file, *output_dir), &meta.header.name) {
+                let output = BlockOutput::Stdout(BufWriter::new(io::stdout()));
+                current = Some(OpenBlock { output, mtime: None, prefilter });
+                return Ok(());
+            }
+            if let (true, Some(path)) = (*concat, *output_file) {
+                let (start, file) = File::options().append(true).open(path)
+                    .and_then(|file| Ok((file.metadata()?.len(), file)))
+                    .map_err(|e| io::Error::other(format!("{}: {}", Path::new(path).display(), e)))?;
+                let output = BlockOutput::Appended { output: BufWriter::new(file), start };
+                current = Some(OpenBlock { output, mtime, prefilter });
+                return Ok(());
+            }
+            // A later block would overwrite the first, unless renamed
+            if let Some(path) = *output_file
+                && meta.block_index > 0
+                && names.rename.is_none()
+            {
+                return Err(io::Error::other(format!(
+                    "{}: the input has more than one block; use --concat to write them all to one file",
+                    Path::new(path).display()
+                )));
+            }
+            let name = match (*output_file, *output_dir) {
+                (Some(path), _) => PathBuf::from(path),
+                (None, Some(dir)) => {
+                    // An absolute name would replace `dir` and `..` would climb out of it
+                    Path::new(dir).join(header_path(&meta.header.name, *allow_unsafe_names).map_err(io::Error::other)?)
+                }
+                (None, None) => header_path(&meta.header.name, *allow_unsafe_names).map_err(io::Error::other)?,
+            };
+            let (target, renamed) = names.resolve(name, warnings).map_err(io::Error::other)?;
+            let mode = OutputMode { path: target.clone(), mode: meta.header.mode, ignore_errors: *ignore_chmod, warnings };
+            if *update && !*no_clobber && target.exists() {
+                let replacement = AtomicFile::create(&target)
+                    .map_err(|e| io::Error::other(format!("{}: {}", target.display(), e)))?;
+                let output = BlockOutput::Pending { target, output: BufWriter::new(replacement), mode };
+                current = Some(OpenBlock { output, mtime, prefilter });
+                return Ok(());
+            }
+            let Some(file) = create_output(&target, *no_clobber || renamed)
+                .map_err(|e| io::Error::other(format!("{}: {}", target.display(), e)))?
+            else {
+                report_clobber_skip(&target);
+                *clobber_skipped += 1;
+                current = Some(OpenBlock { output: BlockOutput::Skipped, mtime, prefilter });
+                return Ok(());
+            };
+            // Like fchmod in GNU uudecode: a file whose mode can't be set
+            // gets no data
+            mode.apply(&file)?;
+            let output = BlockOutput::File { output: BufWriter::new(file), path: target };
+            current = Some(OpenBlock { output, mtime, prefilter });
+            return Ok(());
+        }
+        match current.as_mut() {
+            Some(block) => block.write_all(chunk),
+            None => Ok(()),
+        }
+    };
+
+    let result = loop {
+        match decoder.decode_next_block(&mut sink) {
+            Ok(Some(meta)) => {
+                if decoder.truncated()
+                    && let Err(e) = warnings.warn(format!(
+                        "{}: {} ends before its end line; the output may be incomplete", source, meta.header.name
+                    ))
+                {
+                    break Err(DecodeError::Io(io::Error::other(e)));
+                }
+                blocks += 1;
+            }
+            Ok(None) => break Ok(()),
+            Err(e) if run.continue_on_error && can_skip_block(&e) => {
+                eprintln!("Error decoding {} (line {}): {}", source, decoder.line_number(), sink_error(e));
+                failed.set(true);
+                run.failures += 1;
+                blocks += 1;
+            }
+            Err(e) => break Err(e),
+        }
+    };
+    // Only a block that decoded cleanly replaces or creates its output
+    if let Some(block) = current {
+        match result {
+            Ok(()) if !failed.get() => block.finish()?,
+            _ => block.abort(),
+        }
+    }
+
+    match result {
+        Ok(()) if blocks == 0 => Err(DecodeError::NoBeginLine.into()),
+        Ok(()) => Ok(()),
+        Err(e) => Err(sink_error(e)),
+    }
+}
+
+/// Returns the path a header name is written to. Like BSD uudecode, names
+/// that could reach outside the current or `--output-dir` directory
+/// (absolute, or with a `..` component) are refused unless `allow_unsafe` is set, as the name comes
+/// from untrusted input.
+fn header_path(name: &str, allow_unsafe: bool) -> Result<PathBuf, String> {
+    let path = PathBuf::from(name);
+    let escapes = path.components().any(|c| !matches!(c, Component::Normal(_) | Component::CurDir));
+    if escapes && !allow_unsafe {
+        return Err(format!("{}: refusing an absolute or '..' output name (use --allow-unsafe-names)", name));
+    }
+    Ok(path)
+}
+
+/// Whether `-k` can carry on with the next block of an input after `e`
+fn can_skip_block(e: &DecodeError) -> bool {
+    match e {
+        // Errors reading the input itself cannot be skipped; sink errors
+        // (about one block's output) carry an inner error
+        DecodeError::Io(e) => e.get_ref().is_some(),
+        // No begin line was found, so there is no later block: scanning on
+        // would only hit the same error again on every following line
+        DecodeError::HeaderScanLimit { .. } | DecodeError::NoBeginLine => false,
+        _ => true,
+    }
+}
+
+/// Creates (or truncates) the output file at `path`. With `no_clobber` the
+/// file is only created if nothing is there yet, not even a dangling
+/// symlink, in the same step that opens it; Ok(None) means something was.
+fn create_output(path: &Path, no_clobber: bool) -> io::Result<Option<File>> {
+    if !no_clobber {
+        return File::create(path).map(Some);
+    }
+    match File::options().write(true).create_new(true).open(path) {
+        Ok(file) => Ok(Some(file)),
+        Err(e) if e.kind() == io::ErrorKind::AlreadyExists => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Reports an output left alone by `--no-clobber`
+fn report_clobber_skip(path: &Path) {
+    eprintln!("uudecode: {}: exists, not overwritten (--no-clobber)", path.display());
+}
+
+/// Whether an output name means standard output rather than a file
+fn is_stdout_name(name: &OsStr) -> bool {
+    name == "-" || name == "/dev/stdout"
+}
+
+/// Whether a block named `header_name` is written to standard output
+fn writes_to_stdout(run_output: (Option<&OsStr>, Option<&OsStr>), header_name: &str) -> bool {
+    match run_output {
+        (Some(path), _) => is_stdout_name(path),
+        (None, None) => is_stdout_name(OsStr::new(header_name)),
+        (None, Some(_)) => false,
+    }
+}
+
+/// Output name for `--data-uri` without `--output-file`, as a URI carries no
+/// file name of its own
+const DATA_URI_OUTPUT_NAME: &str = "data";
+
+/// Decodes `input` as a single `data:` URI, writing the payload where a
+/// block's data would go
+fn decode_data_uri_input<R: Read>(mut input: R, run: &mut DecodeRun) -> Result<(), Box<dyn std::error::Error>> {
+    let mut uri = String::new();
+    input.read_to_string(&mut uri)?;
+    let data = decode_data_uri(&uri)?;
+    if writes_to_stdout((run.output_file, run.output_dir), DATA_URI_OUTPUT_NAME) {
+        return Ok(io::stdout().write_all(&data)?);
+    }
+    if let (true, Some(path)) = (run.concat, run.output_file) {
+        let mut file = File::options().append(true).open(path)
+            .map_err(|e| format!("{}: {}", Path::new(path).display(), e))?;
+        return Ok(file.write_all(&data)?);
+    }
+    let name = match (run.output_file, run.output_dir) {
+        (Some(path), _) => PathBuf::from(path),
+        (None, Some(dir)) => Path::new(dir).join(DATA_URI_OUTPUT_NAME),
+        (None, None) => PathBuf::from(DATA_URI_OUTPUT_NAME),
+    };
+    let (target, renamed) = run.names.resolve(name, run.warnings)?;
+    let Some(mut file) = create_output(&target, run.no_clobber || renamed)
+        .map_err(|e| format!("{}: {}", target.display(), e))?
+    else {
+        report_clobber_skip(&target);
+        run.clobber_skipped += 1;
+        return Ok(());
+    };
+    file.write_all(&data).map_err(|e| format!("{}: {}", target.display(), e))?;
+    Ok(())
+}
+
+/// Unwraps errors raised by the output sink so they are reported without
+/// the I/O prefix
+fn sink_error(e: DecodeError) -> Box<dyn std::error::Error> {
+    match e {
+        DecodeError::Io(e) if e.get_ref().is_some() => e.into_inner().unwrap(),
+        e => e.into(),
+    }
+}
+
+/// What `--probe` reports about one block
+struct BlockProbe {
+    /// The input the block was found in
+    source: String,
+    name: String,
+    mode: u32,
+    encoding: Encoding,
+    /// Decoded size in bytes
+    size: u64,
+    /// Lines from the begin line through the end line
+    lines: usize,
+    crc32: u32,
+}
+
+/// Counts and checksums the decoded data of a probed block, discarding it
+#[derive(Default)]
+struct ProbeSink {
+    size: u64,
+    crc32: Crc32,
+}
+
+impl Write for ProbeSink {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.size += buf.len() as u64;
+        self.crc32.update(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+/// Scans every block in `input` for `--probe`, without writing any output
+fn probe_input<R: BufRead>(input: R, source: &str, options: &DecodeOptions) -> Result<Vec<BlockProbe>, DecodeError> {
+    let mut decoder = Decoder::new(input, options.clone());
+    let mut probes = Vec::new();
+    while let Some(header) = decoder.next_header()? {
+        let begin_line = decoder.line_number();
+        let mut sink = ProbeSink::default();
+        decoder.decode_body(&header, &mut sink)?;
+        probes.push(BlockProbe {
+            source: source.to_string(),
+            name: header.name,
+            mode: header.mode,
+            encoding: header.encoding,
+            size: sink.size,
+            lines: decoder.line_number() - begin_line + 1,
+            crc32: sink.crc32.finalize(),
+        });
+    }
+    if probes.is_empty() {
+        return Err(DecodeError::NoBeginLine);
+    }
+    Ok(probes)
+}
+
+/// Quotes `s` as a JSON string
+fn json_string(s: &str) -> String {
+    let mut quoted = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c.is_control() => quoted.push_str(&format!("\\u{:04x}", c as u32)),
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+/// Writes the `--probe` report: a JSON object whose `blocks` array has one
+/// entry per block, in input order
+fn write_probe_json<W: Write>(output: &mut W, probes: &[BlockProbe]) -> io::Result<()> {
+    writeln!(output, "{{")?;
+    write!(output, "  \"blocks\": [")?;
+    for (i, probe) in probes.iter().enumerate() {
+        let encoding = match probe.encoding {
+            Encoding::Uu => "uu",
+            Encoding::Base64 => "base64",
+            Encoding::Xx => "xx",
+            Encoding::Unknown => "unknown",
+        };
+        write!(
+            output,
+            "{}\n    {{\"input\": {}, \"name\": {}, \"mode\": \"{:03o}\", \"encoding\": \"{}\", \"size\": {}, \"lines\": {}, \"crc32\": \"{:08x}\"}}",
+            if i == 0 { "" } else { "," },
+            json_string(&probe.source), json_string(&probe.name), probe.mode, encoding, probe.size, probe.lines, probe.crc32
+        )?;
+    }
+    if !probes.is_empty() {
+        write!(output, "\n  ")?;
+    }
+    writeln!(output, "]")?;
+    writeln!(output, "}}")
+}
+
+/// Extensions of the files `--dir` picks up
+const ENCODED_EXTENSIONS: [&str; 4] = ["uue", "uu", "b64", "base64"];
+
+/// Lists the encoded files (by extension) directly inside `dir`, sorted by name
+fn list_encoded_files(dir: &Path) -> io::Result<Vec<OsString>> {
+    let mut inputs = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let encoded = path.extension()
+            .and_then(|e| e.to_str())
+            .is_some_and(|e| ENCODED_EXTENSIONS.contains(&e.to_ascii_lowercase().as_str()));
+        if encoded && path.is_file() {
+            inputs.push(path.into_os_string());
+        }
+    }
+    inputs.sort();
+    Ok(inputs)
+}
+
+/// Reads a newline-separated list of file names (as produced by `ls`),
+/// skipping blank lines
+fn read_input_list<R: BufRead>(input: R) -> io::Result<Vec<OsString>> {
+    let mut inputs = Vec::new();
+    for line in input.lines() {
+        let line = line?;
+        let name = line.strip_suffix('\r').unwrap_or(&line);
+        if !name.is_empty() {
+            inputs.push(OsString::from(name));
+        }
+    }
+    Ok(inputs)
+}
+
+/// Returns the `--help` text
+fn help_text(options: &[OptionDefinition]) -> String {
+    generate_help(
+        "uudecode",
+        "Decode an encoded file",
+        "[OPTIONS] [input-file...]",
+        options
+    )
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -71,29 +889,66 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Add uudecode-specific options including custom version option
     options.extend(uudecode_options());
     
-    // Parse command line arguments
-    let args: Vec<OsString> = std::env::args_os().collect();
-    let parsed = match parse_command_line(&options, args.into_iter()) {
+    // Parse command line arguments, tracing each decision if --trace is given
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let trace = take_trace_flag(&mut args);
+    let print_trace = |line: &str| eprintln!("trace: {}", line);
+    // A bad option table is a bug in this program, not a usage error
+    let parser = Parser::new(&options)?.with_hook(expand_rename_duplicates);
+    let parser = if trace { parser.with_trace(&print_trace) } else { parser };
+    let mut parsed = match parser.parse(args.into_iter()) {
         Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: {}\n", e);
+            process::exit(emit_help(&help_text(&options), HelpReason::UsageError));
+        }
+    };
+    
+    // Merge in options from --load-opts; command line values win
+    let config = match parsed.option_value("load-opts") {
+        Some(path) => match load_config_file(Path::new(path)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Error reading config file {:?}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+    let effective = match EffectiveConfig::resolve(&options, &parsed, &config) {
+        Ok(effective) => effective,
         Err(e) => {
             eprintln!("Error: {}", e);
-            eprintln!("\nUse --help for usage information.");
             process::exit(1);
         }
     };
+    effective.apply(&mut parsed);
+    // Options from the config file need the same fixups
+    if let Err(e) = parser.run_hooks(&mut parsed) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
     
-    // Debug output the parsed command
-    debug_print_parsed_command(&parsed);
+    if parsed.is_option_set("show-config") {
+        print!("{}", effective.describe());
+        return Ok(());
+    }
+    
+    // Debug output the parsed command (only in debug builds, and only with
+    // --trace so that default runs keep stderr clean)
+    #[cfg(debug_assertions)]
+    if trace {
+        debug_print_parsed_command(&parsed, &options);
+    }
     
     // Handle special options that cause immediate exit
     if parsed.is_option_set("help") {
-        let help_text = generate_help(
-            "uudecode",
-            "Decode an encoded file",
-            "[OPTIONS] [input-file...]",
-            &options
-        );
-        println!("{}", help_text);
+        emit_help(&help_text(&options), HelpReason::Requested);
+        return Ok(());
+    }
+    
+    if parsed.is_option_set("license") {
+        handle_license_output();
         return Ok(());
     }
     
@@ -112,43 +967,230 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
     
-    // Validate output-file option usage
-    if parsed.is_option_set("output-file") && parsed.arguments.len() > 1 {
-        eprintln!("Error: --output-file cannot be used when multiple input files are provided");
-        eprintln!("When decoding multiple files, each must specify its own output filename in the encoded data");
-        process::exit(1);
+    if parsed.is_option_set("dump-completion") {
+        print!("{}", generate_bash_completion("uudecode", &options));
+        return Ok(());
     }
     
-    // At this point we would implement the actual uudecode functionality
-    // For now, just indicate that parsing was successful
-    println!("uudecode: Command line parsing completed successfully!");
-    println!("This is where the actual decoding logic would be implemented.");
-    
-    // Print the configuration for verification
-    if parsed.is_option_set("ignore-chmod") {
-        println!("Configuration: Will ignore fchmod() errors");
-    } else {
-        println!("Configuration: Will respect file permission errors");
+    if parsed.is_option_set("self-test") {
+        match run_self_test() {
+            Ok(()) => {
+                println!("uudecode: self-test passed");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("uudecode: self-test FAILED: {}", e);
+                process::exit(1);
+            }
+        }
     }
     
-    if let Some(output_file) = parsed.option_value("output-file") {
-        println!("Configuration: Output will be written to: {:?}", output_file);
+    // With --stdin-list, stdin carries the input file names rather than data
+    let stdin_list = parsed.is_option_set("stdin-list");
+    if stdin_list && !parsed.arguments.is_empty() {
+        eprintln!("Error: --stdin-list cannot be combined with input file arguments");
+        process::exit(1);
+    }
+    let input_dir = parsed.option_value("dir").map(Path::new);
+    if input_dir.is_some() && !parsed.arguments.is_empty() {
+        eprintln!("Error: --dir cannot be combined with input file arguments");
+        process::exit(1);
+    }
+    let inputs = if let Some(dir) = input_dir {
+        match list_encoded_files(dir) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                eprintln!("Error reading input directory {:?}: {}", dir, e);
+                process::exit(1);
+            }
+        }
+    } else if stdin_list {
+        match read_input_list(std::io::stdin().lock()) {
+            Ok(inputs) => inputs,
+            Err(e) => {
+                eprintln!("Error reading input list from standard input: {}", e);
+                process::exit(1);
+            }
+        }
     } else {
-        println!("Configuration: Output filename will be taken from encoded data");
+        parsed.arguments.clone()
+    };
+    
+    // Standard input can only be read once: as encoded data (`-`, or no
+    // inputs at all) or as the --stdin-list of input names
+    let stdin_reads = inputs.iter().filter(|input| is_stdin_argument(input)).count() + usize::from(stdin_list);
+    if stdin_reads > 1 {
+        eprintln!("Error: standard input can only be read once; give '-' at most once and not with --stdin-list");
+        process::exit(1);
     }
     
-    // Print input file information
-    if parsed.arguments.is_empty() {
-        println!("Input: Reading from standard input");
-    } else {
-        println!("Input files ({}):", parsed.arguments.len());
-        for (i, file) in parsed.arguments.iter().enumerate() {
-            println!("  [{}]: {:?}", i + 1, file);
+    // Every block goes to the one output, so header names and -u don't apply
+    let concat = parsed.is_option_set("concat");
+    if let (true, Some(path)) = (concat, parsed.option_value("output-file"))
+        && !is_stdout_name(path)
+    {
+        match create_output(Path::new(path), parsed.is_option_set("no-clobber")) {
+            Ok(Some(_)) => {}
+            Ok(None) => {
+                report_clobber_skip(Path::new(path));
+                process::exit(1);
+            }
+            Err(e) => {
+                eprintln!("Error creating {}: {}", Path::new(path).display(), e);
+                process::exit(1);
+            }
         }
     }
     
+    // Validate output-file option usage; standard output is a stream, so
+    // blocks written there can't overwrite each other
+    let output_file_is_stdout = parsed.option_value("output-file").is_some_and(is_stdout_name);
+    if parsed.is_option_set("output-file") && inputs.len() > 1 && !concat && !output_file_is_stdout {
+        eprintln!("Error: --output-file cannot be used when multiple input files are provided");
+        eprintln!("When decoding multiple files, each must specify its own output filename in the encoded data");
+        process::exit(1);
+    }
+    
     // Handle save-opts and load-opts if specified
     print_config_file_options(&parsed);
     
+    let input_format = parsed.option_value("input-format")
+        .and_then(|v| v.to_str())
+        .and_then(|v| v.parse().ok());
+    
+    let decode_options = DecodeOptions {
+        strip_indent: parsed.is_option_set("strip-indent"),
+        base64_variant: parsed.option_value("base64-variant")
+            .and_then(|v| v.to_str())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or_default(),
+        input_format,
+        force_input_format: parsed.is_option_set("no-header"),
+        strict_headers: parsed.is_option_set("strict-headers"),
+        allow_truncated: parsed.is_option_set("no-trailer-check"),
+        max_header_scan: parsed.option_value("max-header-scan")
+            .and_then(|v| v.to_str())
+            .and_then(|v| v.parse().ok())
+            .unwrap_or(DEFAULT_MAX_HEADER_SCAN),
+        numbered_lines: parsed.is_option_set("numbered-lines"),
+        strict_line_lengths: parsed.is_option_set("strict"),
+        #[cfg(feature = "sha256")]
+        verify_sha256: parsed.is_option_set("verify-sha256"),
+    };
+    let mut run = DecodeRun {
+        output_file: parsed.option_value("output-file"),
+        output_dir: parsed.option_value("output-dir"),
+        concat,
+        options: decode_options,
+        names: OutputNames {
+            seen: HashSet::new(),
+            strict: parsed.is_option_set("strict"),
+            rename: parsed.option_value("rename").and_then(|v| v.to_str()).map(str::to_string),
+        },
+        parts: PartSequence::new(),
+        update: parsed.is_option_set("update"),
+        no_clobber: parsed.is_option_set("no-clobber"),
+        clobber_skipped: 0,
+        preserve_time: parsed.is_option_set("preserve-time"),
+        continue_on_error: parsed.is_option_set("continue-on-error"),
+        failures: 0,
+        data_uri: parsed.is_option_set("data-uri"),
+        ignore_chmod: parsed.is_option_set("ignore-chmod") || std::env::var_os("POSIXLY_CORRECT").is_some(),
+        allow_unsafe_names: parsed.is_option_set("allow-unsafe-names"),
+        warnings: Warnings { abort: parsed.is_option_set("abort-on-warning") },
+    };
+    let exit_zero_on = parsed.option_value("exit-zero-on")
+        .and_then(|v| parse_exit_conditions(v).ok())
+        .unwrap_or_default();
+    let exempt = |e: &(dyn std::error::Error + 'static)| {
+        exit_condition(e).is_some_and(|c| exit_zero_on.contains(&c))
+    };
+    let mut exit_status = 0;
+    
+    // Without any inputs the encoded data comes from standard input
+    let inputs = if inputs.is_empty() && !stdin_list { vec![OsString::from(STDIN_ARGUMENT)] } else { inputs };
+    
+    if parsed.is_option_set("probe") {
+        let mut probes = Vec::new();
+        for input_path in &inputs {
+            let source = if is_stdin_argument(input_path) {
+                "standard input".to_string()
+            } else {
+                Path::new(input_path).display().to_string()
+            };
+            let result = if is_stdin_argument(input_path) {
+                probe_input(std::io::stdin().lock(), &source, &run.options)
+            } else {
+                match File::open(input_path) {
+                    Ok(file) => probe_input(BufReader::new(file), &source, &run.options),
+                    Err(e) => {
+                        let (message, status) = input_error(Path::new(input_path), &e);
+                        eprintln!("Error: {}", message);
+                        exit_status = exit_status.max(status);
+                        continue;
+                    }
+                }
+            };
+            match result {
+                Ok(found) => probes.extend(found),
+                Err(e) => {
+                    eprintln!("Error probing {}: {}", source, e);
+                    exit_status = exit_status.max(1);
+                }
+            }
+        }
+        write_probe_json(&mut std::io::stdout().lock(), &probes)?;
+        if exit_status != 0 {
+            process::exit(exit_status);
+        }
+        return Ok(());
+    }
+    for input_path in &inputs {
+        let result = if is_stdin_argument(input_path) {
+            decode_input(std::io::stdin().lock(), "standard input", &mut run)
+                .map_err(|e| ("standard input".to_string(), e))
+        } else {
+            let file = match File::open(input_path) {
+                Ok(file) => file,
+                Err(e) => {
+                    let (message, status) = input_error(Path::new(input_path), &e);
+                    eprintln!("Error: {}", message);
+                    exit_status = exit_status.max(status);
+                    run.failures += 1;
+                    continue;
+                }
+            };
+            let source = Path::new(input_path).display().to_string();
+            decode_input(BufReader::new(file), &source, &mut run).map_err(|e| (source, e))
+        };
+        if let Err((source, e)) = result {
+            eprintln!("Error decoding {}: {}", source, e);
+            if !exempt(e.as_ref()) {
+                exit_status = exit_status.max(1);
+                run.failures += 1;
+            }
+        }
+    }
+    
+    if run.clobber_skipped > 0 && !exit_zero_on.contains(&ExitCondition::ClobberSkipped) {
+        exit_status = exit_status.max(1);
+    }
+    
+    if run.continue_on_error && run.failures > 0 {
+        eprintln!("uudecode: {} failure(s)", run.failures);
+        exit_status = exit_status.max(1);
+    }
+    
+    if run.parts.is_incomplete()
+        && let Err(e) = run.warnings.warn("multi-part sequence ended before its last part")
+    {
+        eprintln!("Error: {}", e);
+        exit_status = exit_status.max(1);
+    }
+    
+    if exit_status != 0 {
+        process::exit(exit_status);
+    }
+    
     Ok(())
 }
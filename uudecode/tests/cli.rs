@@ -0,0 +1,878 @@
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+
+/// Creates an empty scratch directory unique to this test
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("uudecode-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Encodes `data` as a complete block named `name`
+fn encode_block(name: &str, data: &[u8], use_base64: bool) -> Vec<u8> {
+    let mut out = Vec::new();
+    sharutils_core::write_uuencode_header(&mut out, 0o644, name, use_base64, false).unwrap();
+    sharutils_core::encode(&mut &data[..], &mut out, use_base64).unwrap();
+    sharutils_core::write_uuencode_trailer(&mut out, use_base64).unwrap();
+    out
+}
+
+/// Runs the uudecode binary inside `dir`, feeding `stdin` to it
+fn run_uudecode(dir: &Path, args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_uudecode"))
+        .args(args)
+        .current_dir(dir)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn uudecode");
+
+    // The binary may exit (e.g. on a usage error) without reading its input
+    if let Err(e) = child.stdin.take().unwrap().write_all(stdin) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe, "failed to write stdin: {}", e);
+    }
+    child.wait_with_output().expect("failed to wait for uudecode")
+}
+
+#[test]
+fn test_decodes_stdin_to_header_name() {
+    let dir = scratch_dir("stdin");
+    let output = run_uudecode(&dir, &[], &encode_block("out.txt", b"hello world", false));
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("out.txt")).unwrap(), b"hello world");
+    assert!(output.stderr.is_empty(), "unexpected stderr: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_duplicate_output_name_warns() {
+    let dir = scratch_dir("duplicate-warn");
+    let mut input = encode_block("same.txt", b"first", false);
+    input.extend(encode_block("same.txt", b"second", true));
+
+    let output = run_uudecode(&dir, &[], &input);
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Warning: duplicate output name \"same.txt\""));
+    assert_eq!(std::fs::read(dir.join("same.txt")).unwrap(), b"second");
+}
+
+#[test]
+fn test_duplicate_output_name_is_error_when_strict() {
+    let dir = scratch_dir("duplicate-strict");
+    let mut input = encode_block("same.txt", b"first", false);
+    input.extend(encode_block("same.txt", b"second", false));
+
+    let output = run_uudecode(&dir, &["--strict"], &input);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("duplicate output name \"same.txt\""));
+    assert_eq!(std::fs::read(dir.join("same.txt")).unwrap(), b"first");
+}
+
+#[test]
+fn test_rename_duplicates_appends_index() {
+    let dir = scratch_dir("duplicate-rename");
+    let mut input = encode_block("same.txt", b"first", false);
+    input.extend(encode_block("same.txt", b"second", false));
+    input.extend(encode_block("same.txt", b"third", false));
+
+    let output = run_uudecode(&dir, &["--rename-duplicates"], &input);
+
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(dir.join("same.txt")).unwrap(), b"first");
+    assert_eq!(std::fs::read(dir.join("same.txt.1")).unwrap(), b"second");
+    assert_eq!(std::fs::read(dir.join("same.txt.2")).unwrap(), b"third");
+
+    // Names already on disk are skipped rather than overwritten
+    let dir = scratch_dir("duplicate-rename-existing");
+    std::fs::write(dir.join("same.txt.1"), b"from an earlier run").unwrap();
+    let output = run_uudecode(&dir, &["--rename-duplicates"], &input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("same.txt.1")).unwrap(), b"from an earlier run");
+    assert_eq!(std::fs::read(dir.join("same.txt.2")).unwrap(), b"second");
+    assert_eq!(std::fs::read(dir.join("same.txt.3")).unwrap(), b"third");
+}
+
+#[test]
+fn test_rename_template_names_duplicates() {
+    let dir = scratch_dir("duplicate-template");
+    let mut input = encode_block("same.txt", b"first", false);
+    input.extend(encode_block("same.txt", b"second", false));
+    input.extend(encode_block("same.txt", b"third", false));
+
+    let output = run_uudecode(&dir, &["--rename=copy%i-of-%n"], &input);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("same.txt")).unwrap(), b"first");
+    assert_eq!(std::fs::read(dir.join("copy1-of-same.txt")).unwrap(), b"second");
+    assert_eq!(std::fs::read(dir.join("copy2-of-same.txt")).unwrap(), b"third");
+
+    // A second run leaves the first run's copies alone
+    let output = run_uudecode(&dir, &["--rename=copy%i-of-%n"], &input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("copy1-of-same.txt")).unwrap(), b"second");
+    assert_eq!(std::fs::read(dir.join("copy3-of-same.txt")).unwrap(), b"second");
+    assert_eq!(std::fs::read(dir.join("copy4-of-same.txt")).unwrap(), b"third");
+
+    let output = run_uudecode(&dir, &["--rename=%n.bak"], &input);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Rename template must contain %i"));
+}
+
+#[test]
+fn test_command_line_rename_wins_over_config_rename_duplicates() {
+    let dir = scratch_dir("rename-config");
+    std::fs::write(dir.join("uudecode.conf"), "rename-duplicates\n").unwrap();
+    let mut input = encode_block("same.txt", b"first", false);
+    input.extend(encode_block("same.txt", b"second", false));
+
+    let output = run_uudecode(&dir, &["--load-opts", "uudecode.conf", "--rename=copy%i-of-%n"], &input);
+
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("copy1-of-same.txt")).unwrap(), b"second");
+    assert!(!dir.join("same.txt.1").exists());
+
+    // Both from the config file is the same conflict as both on the command line
+    std::fs::write(dir.join("uudecode.conf"), "rename-duplicates\nrename=%n.%i\n").unwrap();
+    let output = run_uudecode(&dir, &["--load-opts", "uudecode.conf"], &input);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--rename cannot be combined with --rename-duplicates"));
+}
+
+/// Encodes `data` as block `name` preceded by a `# part N of M` comment
+fn encode_part(name: &str, data: &[u8], part: u32, total: u32) -> Vec<u8> {
+    let mut out = Vec::new();
+    let part = sharutils_core::PartNumber::new(part, total).unwrap();
+    sharutils_core::write_part_comment(&mut out, part).unwrap();
+    out.extend(encode_block(name, data, false));
+    out
+}
+
+#[test]
+fn test_parts_in_order_are_decoded() {
+    let dir = scratch_dir("parts-in-order");
+    let mut input = encode_part("one.txt", b"first", 1, 2);
+    input.extend(encode_part("two.txt", b"second", 2, 2));
+
+    let output = run_uudecode(&dir, &[], &input);
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("one.txt")).unwrap(), b"first");
+    assert_eq!(std::fs::read(dir.join("two.txt")).unwrap(), b"second");
+}
+
+#[test]
+fn test_parts_out_of_order_are_rejected() {
+    let dir = scratch_dir("parts-out-of-order");
+    let mut input = encode_part("two.txt", b"second", 2, 2);
+    input.extend(encode_part("one.txt", b"first", 1, 2));
+
+    let output = run_uudecode(&dir, &[], &input);
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("part 2 of 2 arrived out of order (expected part 1)"));
+}
+
+#[test]
+fn test_update_skips_identical_output() {
+    let dir = scratch_dir("update-identical");
+    std::fs::write(dir.join("same.txt"), b"same content").unwrap();
+
+    let output = run_uudecode(&dir, &["-u"], &encode_block("same.txt", b"same content", false));
+
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(dir.join("same.txt")).unwrap(), b"same content");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("same.txt: unchanged"));
+}
+
+#[test]
+fn test_update_writes_differing_output() {
+    let dir = scratch_dir("update-differing");
+    std::fs::write(dir.join("changed.txt"), b"old content that is longer").unwrap();
+
+    let output = run_uudecode(&dir, &["--update"], &encode_block("changed.txt", b"new content", true));
+
+    assert!(output.status.success());
+    assert_eq!(std::fs::read(dir.join("changed.txt")).unwrap(), b"new content");
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("unchanged"));
+}
+
+#[test]
+fn test_update_keeps_existing_output_when_decoding_fails() {
+    let dir = scratch_dir("update-truncated");
+    std::fs::write(dir.join("good.txt"), b"good content").unwrap();
+    let block = String::from_utf8(encode_block("good.txt", &[b'x'; 100], false)).unwrap();
+    let cut: String = block.split_inclusive('\n').take(3).collect();
+
+    let output = run_uudecode(&dir, &["-u"], cut.as_bytes());
+
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(dir.join("good.txt")).unwrap(), b"good content");
+    assert!(!dir.join("good.txt.tmp").exists());
+
+    // Nor does a failed block leave a partial new file behind
+    let output = run_uudecode(&dir, &[], cut.replace("good.txt", "new.txt").as_bytes());
+    assert!(!output.status.success());
+    assert!(!dir.join("new.txt").exists());
+}
+
+#[test]
+fn test_no_clobber_skips_existing_output() {
+    let dir = scratch_dir("no-clobber");
+    std::fs::write(dir.join("kept.txt"), b"precious").unwrap();
+    let mut input = encode_block("kept.txt", b"attacker data", false);
+    input.extend(encode_block("new.txt", b"fresh", false));
+
+    let output = run_uudecode(&dir, &["-n"], &input);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("kept.txt: exists, not overwritten (--no-clobber)"));
+    assert_eq!(std::fs::read(dir.join("kept.txt")).unwrap(), b"precious");
+    // Later blocks are still written
+    assert_eq!(std::fs::read(dir.join("new.txt")).unwrap(), b"fresh");
+
+    // The explicit --output-file target is protected too
+    let output = run_uudecode(&dir, &["--no-clobber", "-o", "kept.txt"], &encode_block("x", b"other", false));
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(dir.join("kept.txt")).unwrap(), b"precious");
+
+    // --exit-zero-on clobber-skipped makes the skip a success
+    let output = run_uudecode(&dir, &["-n", "--exit-zero-on", "clobber-skipped"], &input);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("kept.txt")).unwrap(), b"precious");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_no_clobber_does_not_follow_a_dangling_symlink() {
+    let dir = scratch_dir("no-clobber-symlink");
+    std::os::unix::fs::symlink(dir.join("elsewhere.txt"), dir.join("link.txt")).unwrap();
+
+    let output = run_uudecode(&dir, &["-n"], &encode_block("link.txt", b"redirected", false));
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("link.txt: exists, not overwritten (--no-clobber)"));
+    assert!(!dir.join("elsewhere.txt").exists());
+
+    // The same goes for the --concat target
+    let output = run_uudecode(&dir, &["-n", "--concat", "-o", "link.txt"], &encode_block("x", b"redirected", false));
+    assert!(!output.status.success());
+    assert!(!dir.join("elsewhere.txt").exists());
+}
+
+#[test]
+fn test_existing_output_is_overwritten_without_no_clobber() {
+    let dir = scratch_dir("clobber");
+    std::fs::write(dir.join("replaced.txt"), b"old content").unwrap();
+
+    let output = run_uudecode(&dir, &[], &encode_block("replaced.txt", b"new", false));
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("replaced.txt")).unwrap(), b"new");
+}
+
+#[test]
+fn test_numbered_lines_detect_dropped_line() {
+    let data: Vec<u8> = (0..500u32).map(|i| (i % 251) as u8).collect();
+    let mut input = Vec::new();
+    sharutils_core::write_uuencode_header(&mut input, 0o644, "data.bin", true, false).unwrap();
+    let mut body = sharutils_core::NumberedLineWriter::new(&mut input);
+    sharutils_core::encode(&mut &data[..], &mut body, true).unwrap();
+    sharutils_core::write_uuencode_trailer(&mut body, true).unwrap();
+
+    let dir = scratch_dir("numbered-lines");
+    let output = run_uudecode(&dir, &["--numbered-lines"], &input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("data.bin")).unwrap(), data);
+
+    // Dropping a data line is reported instead of producing corrupt output
+    let mut lines: Vec<&[u8]> = input.split_inclusive(|&b| b == b'\n').collect();
+    lines.remove(3);
+    let dir = scratch_dir("numbered-lines-dropped");
+    let output = run_uudecode(&dir, &["--numbered-lines"], &lines.concat());
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("a line was dropped or reordered"));
+}
+
+#[test]
+fn test_exit_zero_on_no_data() {
+    let dir = scratch_dir("exit-zero-on");
+
+    let output = run_uudecode(&dir, &[], b"");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run_uudecode(&dir, &["--exit-zero-on=no-data"], b"");
+    assert!(output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("No `begin' line"));
+
+    // Other failures still fail
+    let output = run_uudecode(&dir, &["--exit-zero-on=no-data"], b"begin 644 x\n#86)C\n");
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run_uudecode(&dir, &["--exit-zero-on=no-data,bogus"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unknown exit condition 'bogus'"));
+}
+
+#[test]
+fn test_preserve_time_applies_mtime_comment() {
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+    let mut input = Vec::new();
+    sharutils_core::write_mtime_comment(&mut input, 1_500_000_000).unwrap();
+    input.extend(encode_block("dated.txt", b"dated content", false));
+
+    let dir = scratch_dir("preserve-time");
+    let output = run_uudecode(&dir, &["--preserve-time"], &input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    let modified = std::fs::metadata(dir.join("dated.txt")).unwrap().modified().unwrap();
+    assert_eq!(modified, mtime);
+
+    // Without the flag the comment is ignored
+    let dir = scratch_dir("no-preserve-time");
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(output.status.success());
+    let modified = std::fs::metadata(dir.join("dated.txt")).unwrap().modified().unwrap();
+    assert_ne!(modified, mtime);
+}
+
+#[test]
+fn test_preserve_time_warns_about_an_out_of_range_mtime() {
+    let mut input = b"# mtime: 18446744073709551615\n".to_vec();
+    input.extend(encode_block("future.txt", b"content", false));
+
+    let dir = scratch_dir("preserve-time-overflow");
+    let output = run_uudecode(&dir, &["--preserve-time"], &input);
+    assert_eq!(output.status.code(), Some(0), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("future.txt")).unwrap(), b"content");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("future.txt: mtime 18446744073709551615 is out of range, not applied"), "{}", stderr);
+}
+
+#[test]
+fn test_input_format_hint() {
+    let dir = scratch_dir("input-format");
+    let output = run_uudecode(&dir, &["--input-format=base64"], &encode_block("hinted.txt", b"hinted", true));
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("hinted.txt")).unwrap(), b"hinted");
+
+    let dir = scratch_dir("input-format-mismatch");
+    let output = run_uudecode(&dir, &["--input-format=base64"], &encode_block("uu.txt", b"uu data", false));
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("expected Base64 input"));
+    assert!(!dir.join("uu.txt").exists());
+
+    // --no-header forces the hinted format onto a mislabeled block
+    let block = encode_block("forced.txt", b"forced", true);
+    let mut mislabeled = b"begin 644 forced.txt\n".to_vec();
+    mislabeled.extend(&block[block.iter().position(|&b| b == b'\n').unwrap() + 1..]);
+    let dir = scratch_dir("input-format-forced");
+    let output = run_uudecode(&dir, &["--input-format=base64", "--no-header"], &mislabeled);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("forced.txt")).unwrap(), b"forced");
+}
+
+#[test]
+fn test_dir_decodes_every_encoded_file() {
+    let dir = scratch_dir("dir");
+    let inputs = dir.join("inputs");
+    let outputs = dir.join("outputs");
+    std::fs::create_dir_all(&inputs).unwrap();
+    std::fs::create_dir_all(&outputs).unwrap();
+
+    let mut two_blocks = encode_block("one.txt", b"first", false);
+    two_blocks.extend(encode_block("two.txt", b"second", true));
+    std::fs::write(inputs.join("pair.uue"), two_blocks).unwrap();
+    std::fs::write(inputs.join("single.b64"), encode_block("three.txt", b"third", true)).unwrap();
+    std::fs::write(inputs.join("notes.txt"), encode_block("skipped.txt", b"not picked up", false)).unwrap();
+
+    let output = run_uudecode(&dir, &["--dir", "inputs", "--output-dir", "outputs"], b"");
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(outputs.join("one.txt")).unwrap(), b"first");
+    assert_eq!(std::fs::read(outputs.join("two.txt")).unwrap(), b"second");
+    assert_eq!(std::fs::read(outputs.join("three.txt")).unwrap(), b"third");
+    assert!(!outputs.join("skipped.txt").exists());
+}
+
+#[test]
+fn test_dir_reports_each_failing_file() {
+    let dir = scratch_dir("dir-errors");
+    std::fs::write(dir.join("bad.uue"), b"no block in here\n").unwrap();
+    std::fs::write(dir.join("good.uue"), encode_block("good.txt", b"good", false)).unwrap();
+
+    let output = run_uudecode(&dir, &["--dir=."], b"");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("bad.uue: No `begin' line"));
+    assert_eq!(std::fs::read(dir.join("good.txt")).unwrap(), b"good");
+}
+
+#[test]
+fn test_header_without_mode_defaults_with_warning() {
+    let input = b"begin   nomode.txt\n#86)C\n`\nend\n";
+
+    let dir = scratch_dir("header-no-mode");
+    let output = run_uudecode(&dir, &[], input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("nomode.txt")).unwrap(), b"abc");
+    assert!(String::from_utf8(output.stderr).unwrap().contains("nomode.txt: no valid mode in header, assuming 644"));
+
+    let dir = scratch_dir("header-no-mode-strict");
+    let output = run_uudecode(&dir, &["--strict-headers"], input);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("invalid mode"));
+    assert!(!dir.join("nomode.txt").exists());
+}
+
+#[test]
+fn test_no_trailer_check_keeps_partial_output() {
+    let data = vec![b'p'; 100];
+    let block = encode_block("partial.txt", &data, false);
+    let text = String::from_utf8(block).unwrap();
+    let cut: String = text.split_inclusive('\n').take(3).collect();
+
+    let dir = scratch_dir("truncated");
+    let output = run_uudecode(&dir, &[], cut.as_bytes());
+    assert!(!output.status.success());
+
+    let dir = scratch_dir("no-trailer-check");
+    let output = run_uudecode(&dir, &["--no-trailer-check"], cut.as_bytes());
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("partial.txt")).unwrap(), &data[..90]);
+    assert!(String::from_utf8(output.stderr).unwrap().contains("partial.txt ends before its end line"));
+}
+
+#[test]
+fn test_help_goes_to_stdout_and_usage_errors_to_stderr() {
+    let dir = scratch_dir("help");
+    let output = run_uudecode(&dir, &["--help"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("Usage: uudecode"));
+    // Debug builds also describe the parsed command on stderr
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("Usage:"));
+
+    let output = run_uudecode(&dir, &["--bogus"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("Error: argument 1: Unknown option: --bogus"), "{}", stderr);
+    assert!(stderr.contains("Usage: uudecode"));
+}
+
+#[test]
+fn test_prefilter_comment_is_undone() {
+    let mut input = b"# prefilter: rot13\n".to_vec();
+    input.extend(encode_block("secret.txt", b"Nggnpx ng qnja", false));
+    let dir = scratch_dir("prefilter");
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("secret.txt")).unwrap(), b"Attack at dawn");
+
+    let mut input = b"# prefilter: gzip\n".to_vec();
+    input.extend(encode_block("packed.txt", b"data", false));
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(!output.status.success());
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unknown prefilter 'gzip'"));
+}
+
+#[test]
+fn test_license_prints_full_text() {
+    let dir = scratch_dir("license");
+    let output = run_uudecode(&dir, &["--license"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with(sharutils_core::LICENSE_TEXT));
+    assert!(stdout.contains("GNU GENERAL PUBLIC LICENSE"));
+}
+
+#[test]
+fn test_concat_writes_blocks_in_order_to_one_file() {
+    let mut input = encode_block("first.bin", b"first half, ", false);
+    input.extend(encode_block("second.bin", b"second half", true));
+    let dir = scratch_dir("concat");
+    let output = run_uudecode(&dir, &["--concat", "-o", "whole.bin"], &input);
+    assert!(output.status.success(), "{}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("whole.bin")).unwrap(), b"first half, second half");
+    assert!(!dir.join("first.bin").exists());
+    assert!(!dir.join("second.bin").exists());
+    // No duplicate-name warnings for the shared output
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("duplicate output name"));
+
+    let output = run_uudecode(&dir, &["--concat"], &input);
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Option 'concat' requires option 'output-file'"));
+}
+
+#[test]
+fn test_stdin_is_read_only_once() {
+    let dir = scratch_dir("stdin-once");
+    let output = run_uudecode(&dir, &["-", "-"], &encode_block("twice.txt", b"data", false));
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("standard input can only be read once"));
+    assert!(!dir.join("twice.txt").exists());
+
+    // A `-` in the list read from standard input would read it again
+    let output = run_uudecode(&dir, &["--stdin-list"], b"-\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("standard input can only be read once"));
+}
+
+#[test]
+fn test_dash_decodes_stdin_between_files() {
+    let dir = scratch_dir("dash");
+    std::fs::write(dir.join("first.uue"), encode_block("first.txt", b"from a file", false)).unwrap();
+    let output = run_uudecode(&dir, &["first.uue", "-"], &encode_block("second.txt", b"from stdin", true));
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(!String::from_utf8(output.stderr).unwrap().contains("Error"));
+    assert_eq!(std::fs::read(dir.join("first.txt")).unwrap(), b"from a file");
+    assert_eq!(std::fs::read(dir.join("second.txt")).unwrap(), b"from stdin");
+}
+
+#[test]
+fn test_data_uri_decodes_payload() {
+    let dir = scratch_dir("data-uri");
+    let uri = b"data:application/octet-stream;base64,AAEC/2hlbGxvIHdvcmxk\n";
+    let output = run_uudecode(&dir, &["--data-uri", "-o", "bytes.bin"], uri);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("bytes.bin")).unwrap(), b"\x00\x01\x02\xffhello world");
+
+    // Without --output-file the URI has no name to offer
+    let output = run_uudecode(&dir, &["--data-uri"], uri);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("data")).unwrap(), b"\x00\x01\x02\xffhello world");
+
+    let output = run_uudecode(&dir, &["--data-uri", "-o", "plain.txt"], b"data:text/plain,hello");
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("not base64 encoded"));
+}
+
+#[test]
+fn test_empty_block_decodes_to_empty_file() {
+    let dir = scratch_dir("empty");
+    for (name, use_base64) in [("empty.txt", false), ("empty.b64", true)] {
+        let output = run_uudecode(&dir, &[], &encode_block(name, b"", use_base64));
+        assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+        assert_eq!(std::fs::read(dir.join(name)).unwrap(), b"");
+    }
+}
+
+#[test]
+fn test_probe_reports_every_block_without_decoding() {
+    let dir = scratch_dir("probe");
+    let mut input = encode_block("first.txt", b"hello world", false);
+    input.extend(encode_block("second.bin", &[7u8; 100], true));
+
+    let output = run_uudecode(&dir, &["--probe"], &input);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let json = String::from_utf8(output.stdout).unwrap();
+    let blocks: Vec<&str> = json.lines().filter(|line| line.trim_start().starts_with("{\"input\"")).collect();
+    assert_eq!(blocks.len(), 2, "{}", json);
+    assert!(json.starts_with("{\n  \"blocks\": [\n"), "{}", json);
+    assert!(blocks[0].contains("\"name\": \"first.txt\", \"mode\": \"644\", \"encoding\": \"uu\", \"size\": 11, \"lines\": 4, \"crc32\": \"0d4a1185\""), "{}", json);
+    assert!(blocks[1].contains("\"name\": \"second.bin\""), "{}", json);
+    assert!(blocks[1].contains("\"encoding\": \"base64\", \"size\": 100,"), "{}", json);
+    assert!(!dir.join("first.txt").exists() && !dir.join("second.bin").exists());
+
+    let output = run_uudecode(&dir, &["--probe"], b"no blocks here\n");
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "{\n  \"blocks\": []\n}\n");
+}
+
+#[test]
+fn test_abort_on_warning_makes_warnings_fatal() {
+    let input = b"begin   nomode.txt\n#86)C\n`\nend\n";
+    let dir = scratch_dir("abort-on-warning");
+    let output = run_uudecode(&dir, &["--abort-on-warning"], input);
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("nomode.txt: no valid mode in header, assuming 644 (warning treated as an error"), "{}", stderr);
+    assert!(!stderr.contains("Warning:"), "{}", stderr);
+    assert!(!dir.join("nomode.txt").exists());
+
+    // Warnings raised between blocks are fatal too
+    let mut input = encode_block("same.txt", b"first", false);
+    input.extend(encode_block("same.txt", b"second", false));
+    let output = run_uudecode(&dir, &["--abort-on-warning"], &input);
+    assert_eq!(output.status.code(), Some(1));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("duplicate output name \"same.txt\" overwrites"));
+    assert_eq!(std::fs::read(dir.join("same.txt")).unwrap(), b"first");
+}
+
+#[cfg(unix)]
+#[test]
+fn test_output_gets_header_mode() {
+    use std::os::unix::fs::PermissionsExt;
+    let dir = scratch_dir("header-mode");
+    let mode = |name: &str| std::fs::metadata(dir.join(name)).unwrap().permissions().mode() & 0o7777;
+    let block = |mode: u32, name: &str| {
+        let mut out = Vec::new();
+        sharutils_core::write_uuencode_header(&mut out, mode, name, false, false).unwrap();
+        sharutils_core::encode(&mut &b"#!/bin/sh\n"[..], &mut out, false).unwrap();
+        sharutils_core::write_uuencode_trailer(&mut out, false).unwrap();
+        out
+    };
+    let mut input = block(0o755, "run.sh");
+    input.extend(block(0o4750, "setuid.sh"));
+    input.extend(block(0o600, "private.txt"));
+
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(mode("run.sh"), 0o755);
+    assert_eq!(mode("setuid.sh"), 0o750);
+    assert_eq!(mode("private.txt"), 0o600);
+
+    // --output-file takes the mode too, and -c changes nothing when chmod works
+    let output = run_uudecode(&dir, &["-c", "-o", "renamed.sh"], &block(0o700, "run.sh"));
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(mode("renamed.sh"), 0o700);
+}
+
+#[test]
+fn test_output_file_dash_writes_stdout() {
+    let dir = scratch_dir("stdout");
+    let output = run_uudecode(&dir, &["-o", "-"], &encode_block("out.bin", b"piped \x00\xff bytes", false));
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"piped \x00\xff bytes");
+    assert!(!dir.join("-").exists());
+    assert!(!dir.join("out.bin").exists());
+
+    // Several inputs can all stream to stdout
+    std::fs::write(dir.join("a.uue"), encode_block("a.txt", b"first ", false)).unwrap();
+    std::fs::write(dir.join("b.uue"), encode_block("b.txt", b"second", true)).unwrap();
+    let output = run_uudecode(&dir, &["--output-file", "/dev/stdout", "a.uue", "b.uue"], b"");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"first second");
+}
+
+#[test]
+fn test_stdout_is_never_chmodded() {
+    let dir = scratch_dir("stdout-mode");
+    // A header naming /dev/stdout goes there too, and its mode isn't applied
+    let mut input = Vec::new();
+    sharutils_core::write_uuencode_header(&mut input, 0o000, "/dev/stdout", false, false).unwrap();
+    sharutils_core::encode(&mut &b"no chmod"[..], &mut input, false).unwrap();
+    sharutils_core::write_uuencode_trailer(&mut input, false).unwrap();
+
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, b"no chmod");
+    assert!(!String::from_utf8_lossy(&output.stderr).contains("cannot set mode"));
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        assert_ne!(std::fs::metadata("/dev/stdout").unwrap().permissions().mode() & 0o777, 0);
+    }
+}
+
+#[test]
+fn test_each_block_of_one_input_gets_its_own_file() {
+    let dir = scratch_dir("two-blocks");
+    let mut input = b"From: someone\n\nTwo attachments follow.\n".to_vec();
+    input.extend(encode_block("one.txt", b"first attachment", false));
+    input.extend(b"\nand the second:\n");
+    input.extend(encode_block("two.bin", b"second attachment", true));
+
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("one.txt")).unwrap(), b"first attachment");
+    assert_eq!(std::fs::read(dir.join("two.bin")).unwrap(), b"second attachment");
+
+    // One --output-file can't hold both
+    let output = run_uudecode(&dir, &["-o", "single.out"], &input);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("more than one block; use --concat"));
+    assert_eq!(std::fs::read(dir.join("single.out")).unwrap(), b"first attachment");
+}
+
+#[test]
+fn test_stdin_list_decodes_each_named_file() {
+    let dir = scratch_dir("stdin-list");
+    std::fs::write(dir.join("one.uue"), encode_block("one.txt", b"first", false)).unwrap();
+    std::fs::write(dir.join("two.uue"), encode_block("two.txt", b"second", true)).unwrap();
+
+    let output = run_uudecode(&dir, &["--stdin-list"], b"one.uue\ntwo.uue\n");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("one.txt")).unwrap(), b"first");
+    assert_eq!(std::fs::read(dir.join("two.txt")).unwrap(), b"second");
+}
+
+#[test]
+fn test_stdin_list_rejects_positional_inputs() {
+    let dir = scratch_dir("stdin-list-positional");
+    let output = run_uudecode(&dir, &["--stdin-list", "one.uue"], b"");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--stdin-list cannot be combined"));
+}
+
+#[test]
+fn test_max_header_scan_bounds_the_search() {
+    let dir = scratch_dir("max-header-scan");
+    let mut input = b"From: someone\nSubject: no attachment here\n".repeat(50);
+    input.extend(encode_block("late.txt", b"too late", false));
+
+    let output = run_uudecode(&dir, &["--max-header-scan=512"], &input);
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No `begin' line within the first 512 bytes"));
+    assert!(!dir.join("late.txt").exists());
+}
+
+#[cfg(feature = "sha256")]
+#[test]
+fn test_verify_sha256_roundtrip_and_tampering() {
+    let dir = scratch_dir("verify-sha256");
+    let mut block = encode_block("signed.txt", b"signed payload", false);
+    let mut hasher = sharutils_core::Sha256::new();
+    hasher.update(b"signed payload");
+    sharutils_core::write_sha256_line(&mut block, &hasher.finalize()).unwrap();
+
+    let output = run_uudecode(&dir, &["--verify-sha256"], &block);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("signed.txt")).unwrap(), b"signed payload");
+
+    // Flip one bit of the first data character
+    let header_len = block.iter().position(|&b| b == b'\n').unwrap() + 1;
+    block[header_len + 1] ^= 1;
+    let output = run_uudecode(&dir, &["--verify-sha256"], &block);
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("SHA-256 mismatch"));
+}
+
+#[test]
+fn test_continue_on_error_decodes_remaining_blocks() {
+    let dir = scratch_dir("continue-on-error");
+    let mut input = encode_block("bad.txt", b"will be corrupted", true);
+    let first_data_line = input.iter().position(|&b| b == b'\n').unwrap() + 1;
+    input[first_data_line] = b'!';
+    input.extend(encode_block("good.txt", b"intact", false));
+
+    let output = run_uudecode(&dir, &["-k"], &input);
+
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(dir.join("good.txt")).unwrap(), b"intact");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Error decoding standard input (line 2)"));
+    assert!(stderr.contains("uudecode: 1 failure(s)"));
+    // The failed block leaves no partial output behind
+    assert!(!dir.join("bad.txt").exists());
+
+    // Nor does a failed last block
+    let dir = scratch_dir("continue-on-error-last");
+    let mut last_bad = encode_block("good.txt", b"intact", false);
+    last_bad.extend(&input[..input.len() - encode_block("good.txt", b"intact", false).len()]);
+    let output = run_uudecode(&dir, &["-k"], &last_bad);
+    assert!(!output.status.success());
+    assert_eq!(std::fs::read(dir.join("good.txt")).unwrap(), b"intact");
+    assert!(!dir.join("bad.txt").exists());
+
+    // Without -k the first bad block stops the run
+    let dir = scratch_dir("stop-on-error");
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(!output.status.success());
+    assert!(!dir.join("good.txt").exists());
+}
+
+#[test]
+fn test_self_test_passes() {
+    let dir = scratch_dir("self-test");
+    let output = run_uudecode(&dir, &["--self-test"], b"");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("self-test passed"));
+}
+
+#[test]
+fn test_unsafe_header_names_are_refused() {
+    let dir = scratch_dir("unsafe-names");
+    let sub = dir.join("sub");
+    std::fs::create_dir_all(&sub).unwrap();
+    let absolute = dir.join("absolute.txt");
+    let mut input = encode_block("../escape.txt", b"climbed out", false);
+    input.extend(encode_block(absolute.to_str().unwrap(), b"anywhere", false));
+
+    let output = run_uudecode(&sub, &["-k"], &input);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("../escape.txt: refusing an absolute or '..' output name"), "{}", stderr);
+    assert!(stderr.contains("uudecode: 2 failure(s)"), "{}", stderr);
+    assert!(!dir.join("escape.txt").exists());
+    assert!(!absolute.exists());
+
+    // Without -k the first unsafe name stops the run
+    let output = run_uudecode(&sub, &[], &input);
+    assert!(!output.status.success());
+    assert!(!dir.join("escape.txt").exists());
+}
+
+#[test]
+fn test_allow_unsafe_names_writes_outside_the_directory() {
+    let dir = scratch_dir("allow-unsafe-names");
+    let sub = dir.join("sub");
+    std::fs::create_dir_all(&sub).unwrap();
+    let absolute = dir.join("absolute.txt");
+    let mut input = encode_block("../escape.txt", b"climbed out", false);
+    input.extend(encode_block(absolute.to_str().unwrap(), b"anywhere", false));
+
+    let output = run_uudecode(&sub, &["--allow-unsafe-names"], &input);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("escape.txt")).unwrap(), b"climbed out");
+    assert_eq!(std::fs::read(&absolute).unwrap(), b"anywhere");
+}
+
+#[test]
+fn test_output_dir_confines_header_names() {
+    let dir = scratch_dir("output-dir-confined");
+    let outputs = dir.join("outputs");
+    std::fs::create_dir_all(&outputs).unwrap();
+    let absolute = dir.join("x");
+
+    for name in [absolute.to_str().unwrap(), "../x"] {
+        let output = run_uudecode(&dir, &["--output-dir", "outputs"], &encode_block(name, b"escaped", false));
+        assert!(!output.status.success());
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert!(stderr.contains("refusing an absolute or '..' output name"), "{}", stderr);
+        assert!(!absolute.exists());
+    }
+    assert_eq!(std::fs::read_dir(&outputs).unwrap().count(), 0);
+
+    // Plain names still land inside the directory
+    let output = run_uudecode(&dir, &["--output-dir", "outputs"], &encode_block("x", b"inside", false));
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(outputs.join("x")).unwrap(), b"inside");
+}
+
+#[test]
+fn test_prose_begin_line_is_not_decoded() {
+    let dir = scratch_dir("prose-begin");
+    let mut input = b"Hi all,\nbegin the meeting at noon please.\n\n".to_vec();
+    input.extend(encode_block("agenda.txt", b"agenda", false));
+
+    let output = run_uudecode(&dir, &[], &input);
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(dir.join("agenda.txt")).unwrap(), b"agenda");
+    assert!(!dir.join("the meeting at noon please.").exists());
+}
+
+#[test]
+fn test_continue_on_error_fails_a_headerless_input_once() {
+    let dir = scratch_dir("continue-on-error-scan-limit");
+    let junk = b"no encoded data on this line\n".repeat(2000);
+
+    let output = run_uudecode(&dir, &["-k", "--max-header-scan=100"], &junk);
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("No `begin' line").count(), 1, "{}", stderr);
+    assert!(stderr.contains("uudecode: 1 failure(s)"), "{}", stderr);
+
+    // The same goes for an input whose only begin lines are malformed
+    let output = run_uudecode(&dir, &["-k"], b"begin 648 x\nbegin 644\ntext\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr.matches("No `begin' line").count(), 1, "{}", stderr);
+    assert!(stderr.contains("uudecode: 1 failure(s)"), "{}", stderr);
+}
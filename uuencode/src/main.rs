@@ -1,65 +1,318 @@
-use std::ffi::OsString;
+use std::ffi::{OsStr, OsString};
+use std::fs::{File, OpenOptions};
+use std::io::{BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::Path;
 use std::process;
+use std::time::{Duration, UNIX_EPOCH};
 use sharutils_core::{
-    OptionDefinition, standard_options, parse_command_line, 
-    generate_help, validate_version_mode, validate_file_path,
-    handle_version_output, handle_more_help, print_config_file_options
+    COMPLETION_SHELLS, generate_bash_completion, validate_choice, ValueHint,
+    OptionDefinition, standard_options, take_trace_flag, Parser, ParseError, ParsedCommand,
+    generate_help, emit_help, HelpReason, validate_version_mode, validate_file_path, validate_writable_path,
+    handle_version_output, handle_license_output, handle_more_help, print_config_file_options,
+    load_config_file, EffectiveConfig,
+    validate_positive_integer, validate_non_negative_integer, encode_with_checkpoints, encode_base64_wrapped, write_part_comment, write_mtime_comment, Checkpoint,
+    AtomicFile, CountingWriter, IndentWriter, NumberedLineWriter, PartNumber, input_error,
+    ProgressThrottle, SystemClock, run_self_test, validate_utf8, validate_mode, parse_mode, NO_SHORT_FLAG,
+    PrefilterPipeline, PrefilterReader, ValidationError, Warnings, write_prefilter_comment
 };
 #[cfg(debug_assertions)]
 use sharutils_core::debug_print_parsed_command;
+#[cfg(feature = "sha256")]
+use sharutils_core::{Sha256, write_sha256_line};
+
+/// Validates a --prefilter value
+fn validate_prefilter(value: &OsStr) -> Result<(), ValidationError> {
+    value.to_str()
+        .ok_or_else(|| "Invalid UTF-8 in prefilter".to_string())
+        .and_then(|v| v.parse::<PrefilterPipeline>())
+        .map(|_| ())
+        .map_err(ValidationError::new)
+}
 
 /// Returns uuencode-specific command line options
 fn uuencode_options() -> Vec<OptionDefinition> {
-    vec![
-        OptionDefinition {
-            flag: 'm',
-            name: "base64".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Convert using base64 instead of traditional uuencoding".to_string(),
-        },
-        OptionDefinition {
-            flag: 'e',
-            name: "encode-file-name".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Encode the output file name".to_string(),
-        },
-        OptionDefinition {
-            flag: 'v',
-            name: "version".to_string(),
-            has_value: true,
-            default_value: Some(OsString::from("copyright")),  // Only when -v is specified without value
-            validator: Some(validate_version_mode),
-            help_text: "Output version information and exit [=MODE]".to_string(),
-        },
-        OptionDefinition {
-            flag: '!',
-            name: "more-help".to_string(),
-            has_value: false,
-            default_value: None,
-            validator: None,
-            help_text: "Extended usage information passed through pager".to_string(),
-        },
-        OptionDefinition {
-            flag: 'R',
-            name: "save-opts".to_string(),
-            has_value: true,
-            default_value: None,  // No automatic default - only when explicitly specified
-            validator: Some(validate_file_path),
-            help_text: "Save the option state to a config file [=FILE]".to_string(),
-        },
-        OptionDefinition {
-            flag: 'r',
-            name: "load-opts".to_string(),
-            has_value: true,
-            default_value: None,
-            validator: Some(validate_file_path),
-            help_text: "Load options from the config file FILE".to_string(),
-        },
-    ]
+    #[allow(unused_mut)]
+    let mut options = vec![
+        OptionDefinition::new('m', "base64")
+            .negatable()
+            .help("Convert using base64 instead of traditional uuencoding"),
+        OptionDefinition::new('e', "encode-file-name")
+            .help("Encode the output file name"),
+        OptionDefinition::new('v', "version")
+            .optional_value()  // Bare -v means the default (copyright) mode
+            .validator(validate_version_mode)
+            .help("Output version information and exit [=MODE]"),
+        OptionDefinition::new('!', "more-help")
+            .help("Extended usage information passed through pager"),
+        OptionDefinition::new('R', "save-opts")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Save the option state to a config file [=FILE]"),
+        OptionDefinition::new('r', "load-opts")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Load options from the config file FILE"),
+        OptionDefinition::new(NO_SHORT_FLAG, "verbose")
+            .help("Report a summary on stderr after a successful encode"),
+        OptionDefinition::new(NO_SHORT_FLAG, "indent")
+            .value()
+            .validator(validate_positive_integer)
+            .help("Prefix every output line with N spaces"),
+        OptionDefinition::new(NO_SHORT_FLAG, "progress")
+            .help("Periodically report the number of bytes encoded on stderr"),
+        OptionDefinition::new(NO_SHORT_FLAG, "line-buffered")
+            .help("Flush the output after every line, for watching a slow encode (slower)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "multi")
+            .help("Encode each input file argument into its own block"),
+        OptionDefinition::new('k', "continue-on-error")
+            .help("With --multi, report and skip inputs that cannot be read"),
+        OptionDefinition::new(NO_SHORT_FLAG, "default-name")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Header name to use for stdin when no output-name is given"),
+        OptionDefinition::new(NO_SHORT_FLAG, "part")
+            .value()
+            .validator(validate_positive_integer)
+            .help("Precede the block with a '# part N of M' comment (needs --total)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "total")
+            .value()
+            .validator(validate_positive_integer)
+            .help("Total number of parts M for --part"),
+        OptionDefinition::new(NO_SHORT_FLAG, "output")
+            .value()
+            .validator(validate_writable_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Write the encoded output to FILE instead of stdout"),
+        OptionDefinition::new(NO_SHORT_FLAG, "checkpoint")
+            .value()
+            .validator(validate_file_path)
+            .value_hint(ValueHint::FilePath)
+            .help("Periodically record the encode position in FILE"),
+        OptionDefinition::new(NO_SHORT_FLAG, "resume")
+            .requires("checkpoint")
+            .requires("output")
+            .help("Continue an interrupted encode from its --checkpoint"),
+        OptionDefinition::new(NO_SHORT_FLAG, "footer-summary")
+            .conflicts("resume")  // The counts would only cover the resumed part
+            .help("Follow each block with a '# encoded N bytes in M lines' comment"),
+        OptionDefinition::new(NO_SHORT_FLAG, "preserve-time")
+            .help("Record the input file's modification time in a '# mtime:' comment"),
+        OptionDefinition::new(NO_SHORT_FLAG, "sync")
+            .requires("output")
+            .conflicts("resume")
+            .help("Write --output via a temporary file, fsync it and rename it into place"),
+        OptionDefinition::new(NO_SHORT_FLAG, "mode")
+            .value()
+            .validator(validate_mode)
+            .help("Mode for the header, octal (644) or symbolic (u=rw,g=r,o=r)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "wrap")
+            .value()
+            .validator(validate_non_negative_integer)
+            .conflicts("checkpoint")
+            .help("Wrap base64 output at COLS columns (0 for no wrapping); base64 only"),
+        OptionDefinition::new(NO_SHORT_FLAG, "numbered-lines")
+            .conflicts("checkpoint")  // They change the output offsets a checkpoint records
+            .help("Prefix each data and trailer line with a sequence number and checksum"),
+        OptionDefinition::new(NO_SHORT_FLAG, "prefilter")
+            .value()
+            .validator(validate_prefilter)
+            .help("Transform the input before encoding: none or rot13 (recorded in a '# prefilter:' comment)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "comment")
+            .value()
+            .validator(validate_utf8)
+            .value_from_file()
+            .help("Precede each block with TEXT as '# ' comment lines (@FILE reads TEXT from FILE)"),
+        OptionDefinition::new(NO_SHORT_FLAG, "show-config")
+            .help("Print every option's effective value and where it came from, then exit"),
+        OptionDefinition::new(NO_SHORT_FLAG, "self-test")
+            .help("Run an internal encode/decode roundtrip and exit"),
+        OptionDefinition::new(NO_SHORT_FLAG, "abort-on-warning")
+            .help("Treat every warning as an error and exit nonzero"),
+        OptionDefinition::new(NO_SHORT_FLAG, "dump-completion")
+            .value()
+            .validator(validate_choice(&COMPLETION_SHELLS))
+            .hidden()
+            .help("Print a completion script for SHELL (bash) and exit"),
+        OptionDefinition::new(NO_SHORT_FLAG, "canonical")
+            .conflicts("mode")
+            .conflicts("wrap")
+            .conflicts("indent")
+            .conflicts("numbered-lines")
+            .conflicts("preserve-time")
+            .hidden()
+            .help("Write byte-stable output for golden tests: mode 644, LF endings, standard line length"),
+    ];
+    #[cfg(feature = "sha256")]
+    options.push(OptionDefinition::new(NO_SHORT_FLAG, "sha256")
+        .conflicts("resume")
+        .help("Follow each block with a '# sha256: <hex>' line of its input"));
+    options
+}
+
+/// Wraps the input source and counts the bytes read through it, optionally
+/// reporting progress on stderr
+struct CountingReader<R> {
+    inner: R,
+    count: u64,
+    progress: Option<ProgressThrottle<SystemClock>>,
+    /// Digest of the current block's input (`--sha256`)
+    #[cfg(feature = "sha256")]
+    sha256: Option<Sha256>,
+}
+
+impl<R: Read> Read for CountingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> std::io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.count += n as u64;
+        #[cfg(feature = "sha256")]
+        if let Some(hasher) = &mut self.sha256 {
+            hasher.update(&buf[..n]);
+        }
+        if let Some(progress) = &mut self.progress
+            && progress.should_emit()
+        {
+            eprintln!("uuencode: {} bytes encoded", self.count);
+        }
+        Ok(n)
+    }
+}
+
+/// Flushes after every write that completes a line, with `--line-buffered`
+struct LineFlusher<W> {
+    inner: W,
+    line_buffered: bool,
+}
+
+impl<W: Write> Write for LineFlusher<W> {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        if self.line_buffered && buf[..n].contains(&b'\n') {
+            self.inner.flush()?;
+        }
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// What one block took: input bytes encoded and output lines written
+struct EncodeStats {
+    bytes: u64,
+    lines: u64,
+}
+
+impl EncodeStats {
+    /// Writes the `# encoded N bytes in M lines` comment that follows a block
+    fn write_summary<W: Write>(&self, output: &mut W) -> std::io::Result<()> {
+        writeln!(output, "# encoded {} bytes in {} lines", self.bytes, self.lines)
+    }
+}
+
+/// Post-parse hook: `--canonical` pins the settings that would otherwise
+/// come from the host. Lines already end in LF and keep their standard
+/// length, since the options that change either conflict with it.
+fn apply_canonical(parsed: &mut ParsedCommand) -> Result<(), ParseError> {
+    if parsed.is_option_set("canonical") {
+        parsed.options.insert("mode".to_string(), Some(OsString::from("644")));
+    }
+    Ok(())
+}
+
+/// Flushes everything written to `output` so far, describing a failure
+fn flush_output<W: Write>(output: &mut W) -> Result<(), String> {
+    output_error(output.flush())
+}
+
+/// Describes a failure to get the encoded output written
+fn output_error(result: std::io::Result<()>) -> Result<(), String> {
+    result.map_err(|e| format!("failed to write output: {}", e))
+}
+
+/// Number of encoded lines between checkpoints (about 180 KiB of input)
+const CHECKPOINT_INTERVAL_LINES: u64 = 4096;
+
+/// Returns a warning if `path`'s extension is not a conventional one for the
+/// chosen encoding (`.uue`/`.uu` for uuencoding, `.b64`/`.base64` for base64)
+fn output_extension_warning(path: &Path, use_base64: bool) -> Option<String> {
+    let expected: &[&str] = if use_base64 { &["b64", "base64"] } else { &["uue", "uu"] };
+    let extension = path.extension().and_then(|e| e.to_str()).map(str::to_ascii_lowercase);
+    match extension {
+        Some(ext) if expected.contains(&ext.as_str()) => None,
+        _ => Some(format!(
+            "output file {:?} does not have a .{} extension for {} output",
+            path,
+            expected[0],
+            if use_base64 { "base64" } else { "uuencoded" },
+        )),
+    }
+}
+
+/// Destination for the encoded stream
+enum OutputTarget {
+    Stdout(std::io::Stdout),
+    File(BufWriter<File>),
+    /// `--sync`: only renamed into place by `finish`
+    Atomic(BufWriter<AtomicFile>),
+}
+
+impl OutputTarget {
+    /// Flushes all output, committing an atomic file to its final name
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            OutputTarget::Stdout(mut out) => out.flush(),
+            OutputTarget::File(mut out) => out.flush(),
+            OutputTarget::Atomic(out) => out.into_inner().map_err(|e| e.into_error())?.commit(),
+        }
+    }
+}
+
+impl Write for OutputTarget {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            OutputTarget::Stdout(out) => out.write(buf),
+            OutputTarget::File(out) => out.write(buf),
+            OutputTarget::Atomic(out) => out.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            OutputTarget::Stdout(out) => out.flush(),
+            OutputTarget::File(out) => out.flush(),
+            OutputTarget::Atomic(out) => out.flush(),
+        }
+    }
+}
+
+/// Exits with `status` once `output` is dropped: `process::exit` skips
+/// destructors, which would leave a `--sync` output's temporary file behind
+fn exit_discarding<W>(output: W, status: i32) -> ! {
+    drop(output);
+    process::exit(status)
+}
+
+/// Opens a partially written output file, discarding anything written after
+/// the checkpoint and positioning the file for appending
+fn open_for_resume(path: &Path, output_offset: u64) -> std::io::Result<File> {
+    let mut file = OpenOptions::new().write(true).open(path)?;
+    file.set_len(output_offset)?;
+    file.seek(SeekFrom::End(0))?;
+    Ok(file)
+}
+
+/// Returns the `--help` text
+fn help_text(options: &[OptionDefinition]) -> String {
+    generate_help(
+        "uuencode",
+        "Encode a file into email-friendly text",
+        "[OPTIONS] [input-file] output-name",
+        options
+    )
 }
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
@@ -72,30 +325,66 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Add uuencode-specific options including custom version option
     options.extend(uuencode_options());
     
-    // Parse command line arguments
-    let args: Vec<OsString> = std::env::args_os().collect();
-    let parsed = match parse_command_line(&options, args.into_iter()) {
+    // Parse command line arguments, tracing each decision if --trace is given
+    let mut args: Vec<OsString> = std::env::args_os().collect();
+    let trace = take_trace_flag(&mut args);
+    let print_trace = |line: &str| eprintln!("trace: {}", line);
+    // A bad option table is a bug in this program, not a usage error
+    let parser = Parser::new(&options)?.with_hook(apply_canonical);
+    let parser = if trace { parser.with_trace(&print_trace) } else { parser };
+    let mut parsed = match parser.parse(args.into_iter()) {
         Ok(p) => p,
+        Err(e) => {
+            eprintln!("Error: {}\n", e);
+            process::exit(emit_help(&help_text(&options), HelpReason::UsageError));
+        }
+    };
+    
+    // Merge in options from --load-opts; command line values win
+    let config = match parsed.option_value("load-opts") {
+        Some(path) => match load_config_file(Path::new(path)) {
+            Ok(config) => config,
+            Err(e) => {
+                eprintln!("Error reading config file {:?}: {}", path, e);
+                process::exit(1);
+            }
+        },
+        None => Vec::new(),
+    };
+    let effective = match EffectiveConfig::resolve(&options, &parsed, &config) {
+        Ok(effective) => effective,
         Err(e) => {
             eprintln!("Error: {}", e);
-            eprintln!("\nUse --help for usage information.");
             process::exit(1);
         }
     };
+    effective.apply(&mut parsed);
+    // Options from the config file need the same fixups
+    if let Err(e) = parser.run_hooks(&mut parsed) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
     
-    // Debug output the parsed command (only in debug builds)
+    if parsed.is_option_set("show-config") {
+        print!("{}", effective.describe());
+        return Ok(());
+    }
+    
+    // Debug output the parsed command (only in debug builds, and only with
+    // --trace so that default runs keep stderr clean)
     #[cfg(debug_assertions)]
-    debug_print_parsed_command(&parsed);
+    if trace {
+        debug_print_parsed_command(&parsed, &options);
+    }
     
     // Handle special options that cause immediate exit
     if parsed.is_option_set("help") {
-        let help_text = generate_help(
-            "uuencode",
-            "Encode a file into email-friendly text",
-            "[OPTIONS] [input-file] output-name",
-            &options
-        );
-        println!("{}", help_text);
+        emit_help(&help_text(&options), HelpReason::Requested);
+        return Ok(());
+    }
+    
+    if parsed.is_option_set("license") {
+        handle_license_output();
         return Ok(());
     }
     
@@ -114,14 +403,42 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
         return Ok(());
     }
     
-    // Validate argument count - uuencode requires at least output-name
-    if parsed.arguments.is_empty() {
-        eprintln!("Error: Missing required output-name argument");
-        eprintln!("Usage: uuencode [OPTIONS] [input-file] output-name");
+    if parsed.is_option_set("dump-completion") {
+        print!("{}", generate_bash_completion("uuencode", &options));
+        return Ok(());
+    }
+    
+    if parsed.is_option_set("self-test") {
+        match run_self_test() {
+            Ok(()) => {
+                println!("uuencode: self-test passed");
+                return Ok(());
+            }
+            Err(e) => {
+                eprintln!("uuencode: self-test FAILED: {}", e);
+                process::exit(1);
+            }
+        }
+    }
+    
+    let multi = parsed.is_option_set("multi");
+    
+    let default_name = parsed.option_value("default-name");
+    
+    // Validate argument count - uuencode requires at least output-name,
+    // unless --default-name supplies it for stdin input
+    if parsed.arguments.is_empty() && (multi || default_name.is_none()) {
+        if multi {
+            eprintln!("Error: --multi requires at least one input file");
+            eprintln!("Usage: uuencode --multi [OPTIONS] input-file...");
+        } else {
+            eprintln!("Error: Missing required output-name argument");
+            eprintln!("Usage: uuencode [OPTIONS] [input-file] output-name");
+        }
         process::exit(1);
     }
     
-    if parsed.arguments.len() > 2 {
+    if parsed.arguments.len() > 2 && !multi {
         eprintln!("Error: Too many arguments provided");
         eprintln!("Usage: uuencode [OPTIONS] [input-file] output-name");
         process::exit(1);
@@ -133,83 +450,444 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     // Parse options for encoding behavior  
     let use_base64 = parsed.is_option_set("base64");
     let encode_filename = parsed.is_option_set("encode-file-name");
+    let verbose = parsed.is_option_set("verbose");
+    let warnings = Warnings { abort: parsed.is_option_set("abort-on-warning") };
+    let indent = parsed.option_value("indent")
+        .and_then(|v| v.to_str())
+        .and_then(|v| v.parse::<usize>().ok())
+        .unwrap_or(0);
     
-    // Determine input source and output filename
-    let (input_file, output_name) = match parsed.arguments.len() {
-        1 => {
-            // Read from stdin, output name is first argument
-            (None, &parsed.arguments[0])
-        },
-        2 => {
-            // Read from file, output name is second argument  
-            (Some(&parsed.arguments[0]), &parsed.arguments[1])
-        },
-        _ => unreachable!()
-    };
+    // With -k, inputs that cannot be opened are reported and skipped
+    let continue_on_error = parsed.is_option_set("continue-on-error");
     
-    // Get file mode (permissions) - default to 644 for stdin
-    let file_mode = if let Some(input_path) = input_file {
-        // Try to get actual file permissions
-        match std::fs::metadata(input_path) {
-            Ok(metadata) => {
-                #[cfg(unix)]
-                {
-                    use std::os::unix::fs::PermissionsExt;
-                    metadata.permissions().mode() & 0o777
-                }
-                #[cfg(not(unix))]
-                {
-                    0o644 // Default mode for non-Unix systems
-                }
-            }
-            Err(e) => {
-                eprintln!("Error accessing input file {:?}: {}", input_path, e);
+    // Determine each input source and the name to put in its header
+    let jobs: Vec<(Option<&OsString>, String)> = if multi {
+        // Every argument is an input file, named after its final path component
+        for input_path in &parsed.arguments {
+            if !continue_on_error && !Path::new(input_path).is_file() {
+                eprintln!("Error: input file {:?} does not exist or is not a file", input_path);
                 process::exit(1);
             }
         }
+        parsed.arguments.iter()
+            .map(|input_path| {
+                let name = Path::new(input_path).file_name().unwrap_or(input_path);
+                (Some(input_path), name.to_string_lossy().into_owned())
+            })
+            .collect()
     } else {
-        0o644 // Default mode for stdin
+        match parsed.arguments.len() {
+            0 => {
+                // Read from stdin, output name comes from --default-name
+                vec![(None, default_name.unwrap_or_default().to_string_lossy().into_owned())]
+            },
+            1 => {
+                // Read from stdin, output name is first argument
+                vec![(None, parsed.arguments[0].to_string_lossy().into_owned())]
+            },
+            2 => {
+                // Read from file (or stdin for `-`), output name is second argument
+                let input_file = (!parsed.argument_is_stdin(0)).then(|| &parsed.arguments[0]);
+                vec![(input_file, parsed.arguments[1].to_string_lossy().into_owned())]
+            },
+            _ => unreachable!()
+        }
     };
     
-    // Open input source
-    let mut input: Box<dyn std::io::Read> = if let Some(input_path) = input_file {
-        match std::fs::File::open(input_path) {
-            Ok(file) => Box::new(file),
+    // Multi-part numbering needs both halves of "part N of M"
+    let part_option = |name: &str| parsed.option_value(name)
+        .and_then(|v| v.to_str())
+        .and_then(|v| v.parse::<u32>().ok());
+    let part = match (part_option("part"), part_option("total")) {
+        (None, None) => None,
+        (Some(part), Some(total)) if !multi => match PartNumber::new(part, total) {
+            Ok(part) => Some(part),
             Err(e) => {
-                eprintln!("Error opening input file {:?}: {}", input_path, e);
+                eprintln!("Error: {}", e);
                 process::exit(1);
             }
+        },
+        _ => {
+            eprintln!("Error: --part and --total must be given together for a single block");
+            process::exit(1);
         }
-    } else {
-        Box::new(std::io::stdin())
     };
     
-    let mut output = std::io::stdout();
-    
-    // Write header
-    let output_name_str = output_name.to_string_lossy();
-    if let Err(e) = sharutils_core::write_uuencode_header(
-        &mut output, 
-        file_mode, 
-        &output_name_str, 
-        use_base64,
-        encode_filename
-    ) {
-        eprintln!("Error writing header: {}", e);
+    let comment = parsed.option_value("comment").and_then(|v| v.to_str());
+    let preserve_time = parsed.is_option_set("preserve-time");
+    let mode_override = parsed.option_value("mode")
+        .and_then(|v| v.to_str())
+        .and_then(|v| parse_mode(v).ok());
+    let output_path = parsed.option_value("output").map(Path::new);
+    let checkpoint_path = parsed.option_value("checkpoint").map(Path::new);
+    let single_file_input = !multi && jobs[0].0.is_some();
+    
+    if checkpoint_path.is_some() && (!single_file_input || indent > 0) {
+        eprintln!("Error: --checkpoint requires a single input file and cannot be combined with --indent");
+        process::exit(1);
+    }
+    
+    if let Some(warning) = output_path.and_then(|path| output_extension_warning(path, use_base64))
+        && let Err(e) = warnings.warn(warning)
+    {
+        eprintln!("Error: {}", e);
         process::exit(1);
     }
     
-    // Encode the data
-    if let Err(e) = sharutils_core::encode(&mut input, &mut output, use_base64) {
-        eprintln!("Error during encoding: {}", e);
+    // --wrap is a base64 (MIME-style) setting and has no meaning for
+    // uuencoding. Checked here rather than with `requires`, as -m often
+    // comes from a config file.
+    let wrap = parsed.option_value("wrap")
+        .and_then(|v| v.to_str())
+        .and_then(|v| v.parse::<usize>().ok());
+    if wrap.is_some() && !use_base64 {
+        eprintln!("Error: --wrap only applies to base64 output (-m)");
         process::exit(1);
     }
     
-    // Write trailer
-    if let Err(e) = sharutils_core::write_uuencode_trailer(&mut output, use_base64) {
-        eprintln!("Error writing trailer: {}", e);
+    let numbered_lines = parsed.is_option_set("numbered-lines");
+    
+    // Checkpoint offsets count prefiltered bytes, which needn't match the input
+    let prefilter = parsed.option_value("prefilter")
+        .and_then(|v| v.to_str())
+        .filter(|v| *v != "none");
+    if prefilter.is_some() && checkpoint_path.is_some() {
+        eprintln!("Error: --prefilter cannot be combined with --checkpoint");
         process::exit(1);
     }
     
+    let footer_summary = parsed.is_option_set("footer-summary");
+    let sync = parsed.is_option_set("sync");
+    
+    // Pick up an interrupted encode where its checkpoint left off
+    let resume_from = match checkpoint_path {
+        Some(path) if parsed.is_option_set("resume") && path.exists() => {
+            match Checkpoint::load(path) {
+                Ok(checkpoint) => Some(checkpoint),
+                Err(e) => {
+                    eprintln!("Error reading checkpoint {:?}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+        }
+        _ => None,
+    };
+    
+    let progress = parsed.is_option_set("progress")
+        .then(|| ProgressThrottle::new(SystemClock::new(), Duration::from_secs(1)));
+    let mut input: CountingReader<Box<dyn Read>> = CountingReader {
+        inner: Box::new(std::io::empty()),
+        count: 0,
+        progress,
+        #[cfg(feature = "sha256")]
+        sha256: None,
+    };
+    #[cfg(feature = "sha256")]
+    let sha256 = parsed.is_option_set("sha256");
+    
+    let output_target = match output_path {
+        Some(path) => {
+            let file = match resume_from {
+                Some(checkpoint) => open_for_resume(path, checkpoint.output_offset)
+                    .map(|file| OutputTarget::File(BufWriter::new(file))),
+                None if sync => AtomicFile::create(path)
+                    .map(|file| OutputTarget::Atomic(BufWriter::new(file))),
+                None => File::create(path).map(|file| OutputTarget::File(BufWriter::new(file))),
+            };
+            match file {
+                Ok(file) => file,
+                Err(e) => {
+                    eprintln!("Error opening output file {:?}: {}", path, e);
+                    process::exit(1);
+                }
+            }
+        }
+        None => OutputTarget::Stdout(std::io::stdout()),
+    };
+    // Lines are counted for --footer-summary
+    let mut output = CountingWriter::new(LineFlusher {
+        inner: IndentWriter::new(output_target, indent),
+        line_buffered: parsed.is_option_set("line-buffered"),
+    });
+    
+    let job_count = jobs.len();
+    let mut failures = 0;
+    let mut exit_status = 0;
+    
+    for (input_file, output_name) in jobs {
+        let (bytes_before, lines_before) = (input.count, output.lines());
+        // Get file mode (permissions) - default to 644 for stdin
+        let file_mode = if let Some(mode) = mode_override {
+            mode
+        } else if let Some(input_path) = input_file {
+            // Try to get actual file permissions
+            match std::fs::metadata(input_path) {
+                Ok(metadata) => {
+                    #[cfg(unix)]
+                    {
+                        use std::os::unix::fs::PermissionsExt;
+                        metadata.permissions().mode() & 0o777
+                    }
+                    #[cfg(not(unix))]
+                    {
+                        0o644 // Default mode for non-Unix systems
+                    }
+                }
+                Err(e) => {
+                    let (message, status) = input_error(Path::new(input_path), &e);
+                    eprintln!("Error: {}", message);
+                    if continue_on_error {
+                        failures += 1;
+                        exit_status = exit_status.max(status);
+                        continue;
+                    }
+                    exit_discarding(output, status);
+                }
+            }
+        } else {
+            0o644 // Default mode for stdin
+        };
+        
+        // Open input source
+        #[cfg(feature = "sha256")]
+        if sha256 {
+            input.sha256 = Some(Sha256::new());
+        }
+        input.inner = if let Some(input_path) = input_file {
+            let opened = File::open(input_path).and_then(|mut file| {
+                if let Some(checkpoint) = resume_from {
+                    file.seek(SeekFrom::Start(checkpoint.input_offset))?;
+                }
+                Ok(file)
+            });
+            match opened {
+                Ok(file) => Box::new(file),
+                Err(e) => {
+                    let (message, status) = input_error(Path::new(input_path), &e);
+                    eprintln!("Error: {}", message);
+                    if continue_on_error {
+                        failures += 1;
+                        exit_status = exit_status.max(status);
+                        continue;
+                    }
+                    exit_discarding(output, status);
+                }
+            }
+        } else {
+            Box::new(std::io::stdin())
+        };
+        // Each block gets fresh stages; the value was checked by its validator
+        let pipeline = prefilter.map(|names| names.parse::<PrefilterPipeline>().unwrap());
+        
+        // Write header, unless it is already part of the resumed output
+        let start = match resume_from {
+            Some(checkpoint) => checkpoint,
+            None => {
+                let mut header = Vec::new();
+                if let Some(comment) = comment {
+                    for line in comment.lines() {
+                        writeln!(header, "# {}", line)?;
+                    }
+                }
+                // Standard input has no modification time to record
+                let mtime = input_file
+                    .filter(|_| preserve_time)
+                    .and_then(|path| std::fs::metadata(path).ok()?.modified().ok())
+                    .and_then(|time| time.duration_since(UNIX_EPOCH).ok());
+                if let Some(mtime) = mtime {
+                    write_mtime_comment(&mut header, mtime.as_secs())?;
+                }
+                if let Some(pipeline) = &pipeline {
+                    write_prefilter_comment(&mut header, pipeline)?;
+                }
+                if let Some(part) = part {
+                    write_part_comment(&mut header, part)?;
+                }
+                let written = sharutils_core::write_uuencode_header(
+                    &mut header, 
+                    file_mode, 
+                    &output_name, 
+                    use_base64,
+                    encode_filename
+                ).and_then(|_| output.write_all(&header));
+                if let Err(e) = written {
+                    eprintln!("Error writing header: {}", e);
+                    exit_discarding(output, 1);
+                }
+                Checkpoint { input_offset: 0, output_offset: header.len() as u64 }
+            }
+        };
+        
+        if let Some(pipeline) = pipeline {
+            let raw = std::mem::replace(&mut input.inner, Box::new(std::io::empty()));
+            input.inner = Box::new(PrefilterReader::new(raw, pipeline));
+        }
+        
+        // Encode the data, recording checkpoints if requested
+        let interval = if checkpoint_path.is_some() { CHECKPOINT_INTERVAL_LINES } else { 0 };
+        let mut numbered;
+        let mut body: &mut dyn Write = if numbered_lines {
+            numbered = NumberedLineWriter::new(&mut output);
+            &mut numbered
+        } else {
+            &mut output
+        };
+        let encoded = match wrap {
+            Some(wrap) => encode_base64_wrapped(&mut input, &mut body, wrap).map(|_| ()),
+            None => encode_with_checkpoints(&mut input, &mut body, use_base64, start, interval, |cp| {
+                match checkpoint_path {
+                    Some(path) => cp.save(path),
+                    None => Ok(()),
+                }
+            }).map(|_| ()),
+        };
+        if let Err(e) = encoded {
+            eprintln!("Error: {}", e);
+            exit_discarding(output, 1);
+        }
+        
+        // Write trailer; numbered along with the data, so that losing the
+        // last data lines is detected too
+        if let Err(e) = sharutils_core::write_uuencode_trailer(&mut body, use_base64) {
+            eprintln!("Error writing trailer: {}", e);
+            exit_discarding(output, 1);
+        }
+        
+        #[cfg(feature = "sha256")]
+        if let Some(hasher) = input.sha256.take()
+            && let Err(e) = write_sha256_line(&mut output, &hasher.finalize())
+        {
+            eprintln!("Error writing trailer: {}", e);
+            exit_discarding(output, 1);
+        }
+        
+        if footer_summary {
+            let stats = EncodeStats { bytes: input.count - bytes_before, lines: output.lines() - lines_before };
+            if let Err(e) = stats.write_summary(&mut output) {
+                eprintln!("Error writing trailer: {}", e);
+                exit_discarding(output, 1);
+            }
+        }
+    }
+    
+    // Flush explicitly: a full disk or closed pipe would otherwise only show
+    // up as data silently missing when the process exits
+    if let Err(e) = flush_output(&mut output).and_then(|_| output_error(output.into_inner().inner.into_inner().finish())) {
+        eprintln!("Error: {}", e);
+        process::exit(1);
+    }
+    
+    // The encode completed, so there is nothing left to resume
+    if let Some(path) = checkpoint_path {
+        let _ = std::fs::remove_file(path);
+    }
+    
+    // Success is silent unless the caller explicitly asked for a summary
+    if verbose {
+        let target = output_path.map_or_else(|| "stdout".to_string(), |p| p.display().to_string());
+        eprintln!("uuencode: encoded {} bytes to {}", input.count, target);
+    }
+    
+    if failures > 0 {
+        eprintln!("uuencode: {} of {} inputs failed", failures, job_count);
+        process::exit(exit_status);
+    }
+    
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use sharutils_core::Parser;
+
+    #[test]
+    fn test_parser_lists_uuencode_options() {
+        let options = uuencode_options();
+        let parser = Parser::new(&options).unwrap();
+
+        let names = parser.option_names();
+        assert_eq!(names.len(), options.len());
+        for expected in ["base64", "encode-file-name", "version", "more-help", "verbose", "multi"] {
+            assert!(names.contains(&expected), "missing option {}", expected);
+        }
+
+        let flags = parser.flags();
+        assert_eq!(flags, vec!['m', 'e', 'v', '!', 'R', 'r', 'k']);
+        assert!(!flags.contains(&NO_SHORT_FLAG));
+    }
+
+    #[test]
+    fn test_built_options_keep_their_settings() {
+        let options = uuencode_options();
+        let find = |name: &str| options.iter().find(|o| o.name == name).unwrap();
+
+        assert!(find("base64").negatable && !find("base64").has_value);
+        let version = find("version");
+        assert!(version.has_optional_value && version.validator.is_some());
+        let canonical = find("canonical");
+        assert!(canonical.hidden);
+        assert_eq!(canonical.conflicts, ["mode", "wrap", "indent", "numbered-lines", "preserve-time"]);
+        assert!(find("comment").value_from_file);
+        assert!(options.iter().all(|o| !o.help_text.is_empty()));
+    }
+
+    /// Accepts every write, but fails to flush, like a full disk
+    struct FailingFlush;
+
+    impl Write for FailingFlush {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Err(std::io::Error::new(std::io::ErrorKind::StorageFull, "No space left on device"))
+        }
+    }
+
+    /// Collects writes, counting how much had been written at each flush
+    #[derive(Default)]
+    struct FlushRecorder {
+        written: Vec<u8>,
+        flushed_at: Vec<usize>,
+    }
+
+    impl Write for FlushRecorder {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.written.extend_from_slice(buf);
+            Ok(buf.len())
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            self.flushed_at.push(self.written.len());
+            Ok(())
+        }
+    }
+
+    #[test]
+    fn test_line_buffered_flushes_each_line() {
+        let data = [b'x'; 100];
+        for line_buffered in [false, true] {
+            let mut output = CountingWriter::new(LineFlusher { inner: FlushRecorder::default(), line_buffered });
+            encode_with_checkpoints(&mut &data[..], &mut output, false, Checkpoint::default(), 0, |_| Ok(())).unwrap();
+            assert_eq!(output.lines(), 3);
+            let recorder = output.into_inner().inner;
+            if line_buffered {
+                // Each line is complete by the time it is flushed
+                assert_eq!(recorder.flushed_at.len(), 3);
+                assert!(recorder.flushed_at.iter().all(|&at| recorder.written[at - 1] == b'\n'));
+                assert_eq!(recorder.flushed_at.last(), Some(&recorder.written.len()));
+            } else {
+                assert!(recorder.flushed_at.is_empty());
+            }
+        }
+    }
+
+    #[test]
+    fn test_flush_error_is_reported() {
+        let mut output = CountingWriter::new(LineFlusher { inner: IndentWriter::new(FailingFlush, 0), line_buffered: false });
+        sharutils_core::write_uuencode_trailer(&mut output, false).unwrap();
+        assert_eq!(flush_output(&mut output), Err("failed to write output: No space left on device".to_string()));
+        assert_eq!(flush_output(&mut Vec::new()), Ok(()));
+    }
+}
@@ -0,0 +1,567 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+/// Creates an empty scratch directory unique to this test
+fn scratch_dir(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("uuencode-test-{}-{}", std::process::id(), name));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs the uuencode binary with the given arguments, feeding `stdin` to it
+fn run_uuencode(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_uuencode"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("failed to spawn uuencode");
+
+    // The binary may exit (e.g. on a usage error) without reading its input
+    if let Err(e) = child.stdin.take().unwrap().write_all(stdin) {
+        assert_eq!(e.kind(), std::io::ErrorKind::BrokenPipe, "failed to write stdin: {}", e);
+    }
+    child.wait_with_output().expect("failed to wait for uuencode")
+}
+
+#[test]
+fn test_default_encode_is_silent_on_stderr() {
+    let output = run_uuencode(&["data.bin"], b"hello world");
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "unexpected stderr: {}", String::from_utf8_lossy(&output.stderr));
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("begin 644 data.bin\n"));
+    assert!(stdout.ends_with("end\n"));
+}
+
+#[test]
+fn test_verbose_reports_summary_on_stderr() {
+    let output = run_uuencode(&["--verbose", "data.bin"], b"hello world");
+
+    assert!(output.status.success());
+    // Just the summary: the parsed-command dump is for --trace
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert_eq!(stderr, "uuencode: encoded 11 bytes to stdout\n");
+
+    // The banner must never leak into the encoded stream
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(!stdout.contains("encoded 11 bytes"));
+}
+
+#[test]
+fn test_indent_prefixes_every_line() {
+    let output = run_uuencode(&["--indent=4", "data.bin"], b"hello world");
+
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("    begin 644 data.bin\n"));
+    assert!(stdout.lines().all(|line| line.starts_with("    ")));
+    assert!(stdout.ends_with("    end\n"));
+}
+
+#[test]
+fn test_multi_encodes_each_input_into_its_own_block() {
+    let dir = scratch_dir("multi");
+    let mut args = vec!["--multi".to_string()];
+    for (name, content) in [("a.txt", "alpha"), ("b.txt", "bravo"), ("c.txt", "charlie")] {
+        let path = dir.join(name);
+        std::fs::write(&path, content).unwrap();
+        args.push(path.to_string_lossy().into_owned());
+    }
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    let output = run_uuencode(&args, b"");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let headers: Vec<&str> = stdout.lines().filter(|l| l.starts_with("begin ")).collect();
+    assert_eq!(headers.len(), 3);
+    assert!(headers[0].ends_with(" a.txt"));
+    assert!(headers[1].ends_with(" b.txt"));
+    assert!(headers[2].ends_with(" c.txt"));
+    assert_eq!(stdout.lines().filter(|l| *l == "end").count(), 3);
+}
+
+#[test]
+fn test_multi_rejects_missing_input() {
+    let dir = scratch_dir("multi-missing");
+    let missing = dir.join("missing.txt");
+    let output = run_uuencode(&["--multi", missing.to_str().unwrap()], b"");
+
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+}
+
+#[test]
+fn test_resume_from_checkpoint_matches_uninterrupted_run() {
+    let dir = scratch_dir("resume");
+    let input = dir.join("input.bin");
+    let data: Vec<u8> = (0..9000u32).map(|i| (i % 253) as u8).collect();
+    std::fs::write(&input, &data).unwrap();
+    let input = input.to_str().unwrap();
+
+    let expected_path = dir.join("expected.uue");
+    let output = run_uuencode(
+        &["--output", expected_path.to_str().unwrap(), input, "input.bin"],
+        b"",
+    );
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let expected = std::fs::read(&expected_path).unwrap();
+
+    // Simulate an interrupted run: a checkpoint after 100 lines, followed by
+    // a partially written line that never made it into a checkpoint
+    let header_len = expected.iter().position(|&b| b == b'\n').unwrap() + 1;
+    let output_offset = header_len + 100 * 62;
+    let partial_path = dir.join("partial.uue");
+    let mut partial = expected[..output_offset].to_vec();
+    partial.extend_from_slice(b"M12345");
+    std::fs::write(&partial_path, &partial).unwrap();
+    let checkpoint_path = dir.join("encode.ckpt");
+    std::fs::write(&checkpoint_path, format!("{} {}\n", 100 * 45, output_offset)).unwrap();
+
+    let output = run_uuencode(
+        &[
+            "--checkpoint", checkpoint_path.to_str().unwrap(),
+            "--resume",
+            "--output", partial_path.to_str().unwrap(),
+            input, "input.bin",
+        ],
+        b"",
+    );
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(std::fs::read(&partial_path).unwrap(), expected);
+    assert!(!checkpoint_path.exists());
+}
+
+#[test]
+fn test_default_name_for_stdin_without_positionals() {
+    let output = run_uuencode(&["--default-name", "data"], b"from stdin");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("begin 644 data\n"));
+}
+
+#[test]
+fn test_missing_output_name_is_still_an_error() {
+    let output = run_uuencode(&[], b"from stdin");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Missing required output-name"));
+}
+
+#[test]
+fn test_part_comment_precedes_block() {
+    let output = run_uuencode(&["--part", "2", "--total", "3", "data.bin"], b"second part");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("# part 2 of 3\nbegin 644 data.bin\n"));
+}
+
+#[test]
+fn test_part_greater_than_total_is_rejected() {
+    let output = run_uuencode(&["--part", "4", "--total", "3", "data.bin"], b"");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Part 4 is greater than total 3"));
+}
+
+#[test]
+fn test_self_test_passes() {
+    let output = run_uuencode(&["--self-test"], b"");
+
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8_lossy(&output.stdout).contains("self-test passed"));
+}
+
+#[test]
+fn test_comment_read_from_file() {
+    let dir = scratch_dir("comment-file");
+    let banner = dir.join("banner.txt");
+    std::fs::write(&banner, "Release notes\nsecond line\n").unwrap();
+    let comment = format!("@{}", banner.display());
+
+    let output = run_uuencode(&["--comment", &comment, "data.bin"], b"payload");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("# Release notes\n# second line\nbegin 644 data.bin\n"));
+}
+
+#[test]
+fn test_sync_output_is_complete_and_temp_file_is_gone() {
+    let dir = scratch_dir("sync");
+    let output_path = dir.join("out.uue");
+    let data: Vec<u8> = (0..5000u32).map(|i| (i % 251) as u8).collect();
+
+    let output = run_uuencode(&["--sync", "--output", output_path.to_str().unwrap(), "data.bin"], &data);
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let written = std::fs::read_to_string(&output_path).unwrap();
+    assert!(written.starts_with("begin 644 data.bin\n"));
+    assert!(written.ends_with("`\nend\n"));
+    assert!(!dir.join("out.uue.tmp").exists());
+}
+
+#[test]
+fn test_failed_sync_encode_leaves_no_temp_file() {
+    let dir = scratch_dir("sync-failed");
+    let output_path = dir.join("out.uue");
+    let missing = dir.join("missing.bin");
+
+    let output = run_uuencode(&["--sync", "--output", output_path.to_str().unwrap(), missing.to_str().unwrap(), "data.bin"], b"");
+
+    assert_eq!(output.status.code(), Some(66), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(!output_path.exists());
+    assert!(!dir.join("out.uue.tmp").exists());
+}
+
+#[test]
+fn test_sync_requires_output() {
+    let output = run_uuencode(&["--sync", "data.bin"], b"");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("Option 'sync' requires option 'output'"));
+}
+
+#[test]
+fn test_matching_output_extension_is_silent() {
+    let dir = scratch_dir("extension-match");
+    let output_path = dir.join("out.b64");
+
+    let output = run_uuencode(&["-m", "--output", output_path.to_str().unwrap(), "data.bin"], b"payload");
+
+    assert!(output.status.success());
+    assert!(output.stderr.is_empty(), "unexpected stderr: {}", String::from_utf8_lossy(&output.stderr));
+}
+
+#[test]
+fn test_mismatched_output_extension_warns() {
+    let dir = scratch_dir("extension-mismatch");
+    let output_path = dir.join("out.uue");
+
+    let output = run_uuencode(&["-m", "--output", output_path.to_str().unwrap(), "data.bin"], b"payload");
+
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("does not have a .b64 extension for base64 output"));
+    assert!(output_path.exists());
+}
+
+#[cfg(feature = "sha256")]
+#[test]
+fn test_sha256_line_follows_block() {
+    let output = run_uuencode(&["--sha256", "data.bin"], b"abc");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.ends_with(
+        "end\n# sha256: ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad\n"
+    ));
+}
+
+#[test]
+fn test_symbolic_mode_sets_header_mode() {
+    let output = run_uuencode(&["--mode", "u=rw,g=r,o=", "data.bin"], b"payload");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("begin 640 data.bin\n"));
+}
+
+#[test]
+fn test_show_config_reports_sources() {
+    let dir = scratch_dir("show-config");
+    let config = dir.join("uuencode.conf");
+    std::fs::write(&config, "indent=2\nbase64\n").unwrap();
+
+    let output = run_uuencode(
+        &["--load-opts", config.to_str().unwrap(), "--indent=4", "--show-config"],
+        b"",
+    );
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("indent = \"4\" (command line)\n"));
+    assert!(stdout.contains("base64 set (config file)\n"));
+    assert!(stdout.contains("comment not set\n"));
+}
+
+#[test]
+fn test_load_opts_applies_config_file() {
+    let dir = scratch_dir("load-opts");
+    let config = dir.join("uuencode.conf");
+    std::fs::write(&config, "# always base64\nbase64\n").unwrap();
+
+    let output = run_uuencode(&["--load-opts", config.to_str().unwrap(), "data.bin"], b"payload");
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("begin-base64 644 data.bin\n"));
+}
+
+/// Returns the data lines of a single base64 block
+fn base64_data_lines(stdout: &str) -> Vec<&str> {
+    let lines: Vec<&str> = stdout.lines().collect();
+    assert!(lines[0].starts_with("begin-base64 "));
+    assert_eq!(*lines.last().unwrap(), "====");
+    lines[1..lines.len() - 1].to_vec()
+}
+
+#[test]
+fn test_wrap_base64_at_60_columns() {
+    let data = vec![b'x'; 500];
+    let output = run_uuencode(&["-m", "--wrap=60", "data.bin"], &data);
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = base64_data_lines(&stdout);
+    assert!(lines.len() > 1);
+    assert!(lines.iter().all(|l| l.len() <= 60));
+}
+
+#[test]
+fn test_wrap_zero_writes_one_data_line() {
+    let data = vec![b'x'; 500];
+    let output = run_uuencode(&["-m", "--wrap=0", "data.bin"], &data);
+
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines = base64_data_lines(&stdout);
+    assert_eq!(lines.len(), 1);
+    assert_eq!(lines[0].len(), 668);
+}
+
+#[test]
+fn test_wrap_rejected_for_traditional_encoding() {
+    let output = run_uuencode(&["--wrap=60", "data.bin"], b"payload");
+
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("--wrap only applies to base64 output"));
+}
+
+#[cfg(unix)]
+#[test]
+#[ignore = "root bypasses file permissions; run with --ignored as an unprivileged user"]
+fn test_unreadable_input_reports_permission_denied() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = scratch_dir("unreadable");
+    let input = dir.join("secret.bin");
+    std::fs::write(&input, b"secret").unwrap();
+    std::fs::set_permissions(&input, std::fs::Permissions::from_mode(0o000)).unwrap();
+    assert!(std::fs::File::open(&input).is_err(), "file permissions are not enforced for this user");
+
+    let output = run_uuencode(&[input.to_str().unwrap(), "secret.bin"], b"");
+
+    assert_eq!(output.status.code(), Some(77));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains(&format!("Permission denied reading {}", input.display())));
+}
+
+#[test]
+fn test_missing_input_reports_not_found() {
+    let dir = scratch_dir("missing-input");
+    let input = dir.join("missing.bin");
+
+    let output = run_uuencode(&[input.to_str().unwrap(), "missing.bin"], b"");
+
+    assert_eq!(output.status.code(), Some(66));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("No such input file"));
+}
+
+#[test]
+fn test_continue_on_error_skips_bad_input() {
+    let dir = scratch_dir("continue-on-error");
+    let good = dir.join("good.txt");
+    std::fs::write(&good, "good data").unwrap();
+    let missing = dir.join("missing.txt");
+
+    let output = run_uuencode(&["-k", "--multi", missing.to_str().unwrap(), good.to_str().unwrap()], b"");
+
+    // Still the exit status for a missing input
+    assert_eq!(output.status.code(), Some(66));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("begin 644 good.txt\n"));
+    assert!(stdout.ends_with("end\n"));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("No such input file"));
+    assert!(stderr.contains("uuencode: 1 of 2 inputs failed"));
+}
+
+#[test]
+fn test_numbered_lines_prefix_data_and_trailer_lines() {
+    let data = vec![b'n'; 100];
+    let output = run_uuencode(&["--numbered-lines", "data.bin"], &data);
+    assert!(output.status.success());
+
+    let text = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = text.lines().collect();
+    assert_eq!(lines[0], "begin 644 data.bin");
+    assert!(lines[1].starts_with("000001 "));
+    assert!(lines[3].starts_with("000003 "));
+    assert!(lines[4].starts_with("000004 ") && lines[4].ends_with(" `"), "{}", lines[4]);
+    assert!(lines[5].starts_with("000005 ") && lines[5].ends_with(" end"), "{}", lines[5]);
+    assert_eq!(lines.len(), 6);
+}
+
+#[test]
+fn test_preserve_time_records_source_mtime() {
+    let dir = scratch_dir("preserve-time");
+    let input = dir.join("dated.txt");
+    std::fs::write(&input, "dated content").unwrap();
+    let mtime = std::time::UNIX_EPOCH + std::time::Duration::from_secs(1_500_000_000);
+    std::fs::File::options().write(true).open(&input).unwrap().set_modified(mtime).unwrap();
+
+    let output = run_uuencode(&["--preserve-time", "--comment", "note", input.to_str().unwrap(), "dated.txt"], b"");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("# note\n# mtime: 1500000000\nbegin 644 dated.txt\n"), "{}", stdout);
+
+    // The block decodes with the recorded time attached to its header
+    let (header, data) = sharutils_core::decode_bytes(stdout.as_bytes()).unwrap();
+    assert_eq!(data, b"dated content");
+    assert_eq!(header.mtime, Some(1_500_000_000));
+
+    let output = run_uuencode(&[input.to_str().unwrap(), "dated.txt"], b"");
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("# mtime:"));
+}
+
+#[test]
+fn test_footer_summary_matches_output() {
+    let data = vec![b'f'; 1000];
+    let output = run_uuencode(&["--footer-summary", "data.bin"], &data);
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let lines: Vec<&str> = stdout.lines().collect();
+    let (summary, block) = lines.split_last().unwrap();
+    assert_eq!(*summary, format!("# encoded 1000 bytes in {} lines", block.len()));
+    assert_eq!(block.last(), Some(&"end"));
+
+    // The summary is a comment, so the block still decodes
+    let (_, decoded) = sharutils_core::decode_bytes(stdout.as_bytes()).unwrap();
+    assert_eq!(decoded, data);
+}
+
+#[test]
+fn test_help_goes_to_stdout_and_usage_errors_to_stderr() {
+    let output = run_uuencode(&["--help"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    assert!(String::from_utf8(output.stdout).unwrap().starts_with("Usage: uuencode"));
+    assert!(output.stderr.is_empty());
+
+    let output = run_uuencode(&["-m", "--wrap"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(output.stdout.is_empty());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Usage: uuencode"), "{}", stderr);
+}
+
+#[test]
+fn test_prefilter_rot13_roundtrip() {
+    let output = run_uuencode(&["--prefilter=rot13", "secret.txt"], b"Attack at dawn");
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.starts_with("# prefilter: rot13\nbegin 644 secret.txt\n"), "{}", stdout);
+
+    // The block carries the transformed data, which the pipeline restores
+    let (header, decoded) = sharutils_core::decode_bytes(stdout.as_bytes()).unwrap();
+    assert_eq!(decoded, b"Nggnpx ng qnja");
+    assert_eq!(header.prefilter.as_deref(), Some("rot13"));
+    let mut pipeline: sharutils_core::PrefilterPipeline = "rot13".parse().unwrap();
+    assert_eq!(pipeline.revert(&decoded).unwrap(), b"Attack at dawn");
+
+    let output = run_uuencode(&["--prefilter=none", "plain.txt"], b"plain");
+    assert!(!String::from_utf8(output.stdout).unwrap().contains("# prefilter:"));
+
+    let output = run_uuencode(&["--prefilter=gzip", "data.gz"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("Unknown prefilter 'gzip'"));
+}
+
+#[test]
+fn test_license_prints_full_text() {
+    let output = run_uuencode(&["--license"], b"");
+    assert_eq!(output.status.code(), Some(0));
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("GNU GENERAL PUBLIC LICENSE"));
+    assert!(stdout.contains("TERMS AND CONDITIONS"));
+}
+
+#[test]
+fn test_canonical_matches_golden_files() {
+    let data = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/data");
+    let dir = scratch_dir("canonical");
+    let input = dir.join("golden.txt");
+    std::fs::copy(data.join("canonical.txt"), &input).unwrap();
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        std::fs::set_permissions(&input, std::fs::Permissions::from_mode(0o600)).unwrap();
+    }
+
+    // The host's file mode must not leak into the header
+    for (args, golden) in [(&[][..], "canonical.uue"), (&["-m"][..], "canonical-base64.uue")] {
+        let mut args = args.to_vec();
+        args.extend(["--canonical", input.to_str().unwrap(), "golden.txt"]);
+        let output = run_uuencode(&args, b"");
+        assert!(output.status.success());
+        assert_eq!(output.stdout, std::fs::read(data.join(golden)).unwrap(), "{}", golden);
+    }
+
+    let output = run_uuencode(&["--canonical", "--mode=600", "data.bin"], b"");
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr).unwrap().contains("--canonical cannot be combined with --mode"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn test_dash_input_reads_stdin() {
+    let output = run_uuencode(&["-", "data.bin"], b"hello world");
+    assert!(output.status.success(), "stderr: {}", String::from_utf8_lossy(&output.stderr));
+    assert_eq!(output.stdout, run_uuencode(&["data.bin"], b"hello world").stdout);
+}
+
+#[test]
+fn test_empty_input_encodes_empty_block() {
+    let output = run_uuencode(&["empty.txt"], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"begin 644 empty.txt\n`\nend\n");
+
+    let output = run_uuencode(&["-m", "empty.txt"], b"");
+    assert!(output.status.success());
+    assert_eq!(output.stdout, b"begin-base64 644 empty.txt\n====\n");
+}
+
+#[test]
+fn test_abort_on_warning_rejects_mismatched_extension() {
+    let dir = scratch_dir("extension-abort");
+    let output_path = dir.join("out.uue");
+
+    let output = run_uuencode(&["-m", "--abort-on-warning", "--output", output_path.to_str().unwrap(), "data.bin"], b"payload");
+
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("Error: output file"), "{}", stderr);
+    assert!(stderr.contains("(warning treated as an error by --abort-on-warning)"), "{}", stderr);
+    assert!(!output_path.exists());
+}
+
+#[test]
+fn test_dump_completion_prints_bash_script() {
+    let output = run_uuencode(&["--dump-completion", "bash"], b"");
+    assert!(output.status.success());
+    let script = String::from_utf8(output.stdout).unwrap();
+    assert!(script.ends_with("complete -F _uuencode uuencode\n"));
+    assert!(script.contains("--base64") && script.contains("--no-base64"));
+    // --output takes a file name; the hidden options stay out
+    assert!(script.contains("--output|--checkpoint)\n            COMPREPLY=($(compgen -f"), "{}", script);
+    assert!(!script.contains("--canonical") && !script.contains("--dump-completion"));
+
+    assert!(!run_uuencode(&["--dump-completion", "zsh"], b"").status.success());
+}